@@ -0,0 +1,277 @@
+use crate::contract::map_validate;
+use crate::state::*;
+use cosmwasm_std::{DepsMut, Empty, Env, MessageInfo, Response, StdError};
+
+use crate::error::ContractError;
+
+pub fn add_admins(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    admins: Vec<String>,
+) -> Result<Response, ContractError> {
+    //// do not accept funds ////
+    if !info.funds.is_empty() {
+        return Err(StdError::generic_err("Funds not accepted").into());
+    }
+
+    let mut admin_list = ADMIN_LIST.load(deps.storage)?;
+    if !admin_list.can_modify(info.sender.as_str()) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    //// skip admins that are already registered ////
+    for admin in map_validate(deps.api, &admins)? {
+        if !admin_list.is_admin(&admin) {
+            admin_list.admins.push(admin);
+        }
+    }
+    ADMIN_LIST.save(deps.storage, &admin_list)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "add_admins")
+        .add_attribute("sender", info.sender))
+}
+
+pub fn remove_admins(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    admins: Vec<String>,
+) -> Result<Response, ContractError> {
+    //// do not accept funds ////
+    if !info.funds.is_empty() {
+        return Err(StdError::generic_err("Funds not accepted").into());
+    }
+
+    let mut admin_list = ADMIN_LIST.load(deps.storage)?;
+    if !admin_list.can_modify(info.sender.as_str()) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let removed = map_validate(deps.api, &admins)?;
+    admin_list.admins.retain(|admin| !removed.contains(admin));
+
+    //// never leave the contract without an admin ////
+    if admin_list.admins.is_empty() {
+        return Err(StdError::generic_err("Cannot remove all admins").into());
+    }
+    ADMIN_LIST.save(deps.storage, &admin_list)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "remove_admins")
+        .add_attribute("sender", info.sender))
+}
+
+pub fn freeze(deps: DepsMut, _env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+    let mut admin_list = ADMIN_LIST.load(deps.storage)?;
+    if !admin_list.can_modify(info.sender.as_str()) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    admin_list.mutable = false;
+    ADMIN_LIST.save(deps.storage, &admin_list)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "freeze")
+        .add_attribute("sender", info.sender))
+}
+
+pub fn set_arbitration(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    arbitrator: Arbitrator,
+    evidence_days: u64,
+    ruling_days: u64,
+) -> Result<Response, ContractError> {
+    let admin_list = ADMIN_LIST.load(deps.storage)?;
+    if !admin_list.is_admin(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if ruling_days == 0 {
+        return Err(StdError::generic_err("Ruling period cannot be zero").into());
+    }
+
+    let arbitrator = match arbitrator {
+        Arbitrator::Address(addr) => Arbitrator::Address(deps.api.addr_validate(addr.as_str())?),
+        Arbitrator::Multisig(addr) => Arbitrator::Multisig(deps.api.addr_validate(addr.as_str())?),
+    };
+    ARBITRATION.save(
+        deps.storage,
+        &ArbitrationConfig {
+            arbitrator: arbitrator.clone(),
+            evidence_days,
+            ruling_days,
+        },
+    )?;
+
+    Ok(Response::new()
+        .add_attribute("method", "set_arbitration")
+        .add_attribute("arbitrator", arbitrator.addr().as_str()))
+}
+
+pub fn add_kyc_providers(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    providers: Vec<String>,
+) -> Result<Response, ContractError> {
+    let admin_list = ADMIN_LIST.load(deps.storage)?;
+    if !admin_list.is_admin(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    for provider in map_validate(deps.api, &providers)? {
+        KYC_PROVIDERS.save(deps.storage, &provider, &Empty {})?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "add_kyc_providers")
+        .add_attribute("providers", providers.join(",")))
+}
+
+pub fn remove_kyc_providers(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    providers: Vec<String>,
+) -> Result<Response, ContractError> {
+    let admin_list = ADMIN_LIST.load(deps.storage)?;
+    if !admin_list.is_admin(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    for provider in map_validate(deps.api, &providers)? {
+        KYC_PROVIDERS.remove(deps.storage, &provider);
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "remove_kyc_providers")
+        .add_attribute("providers", providers.join(",")))
+}
+
+pub fn set_kyc_policy(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    policy: KycPolicy,
+) -> Result<Response, ContractError> {
+    let admin_list = ADMIN_LIST.load(deps.storage)?;
+    if !admin_list.is_admin(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    KYC_POLICY.save(deps.storage, &policy)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "set_kyc_policy")
+        .add_attribute("sender", info.sender))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::{execute, instantiate};
+    use crate::msg::{ExecuteMsg, InstantiateMsg};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::Addr;
+
+    #[test]
+    fn only_admins_can_set_config() {
+        let mut deps = mock_dependencies();
+        let msg = InstantiateMsg {
+            admins: vec!["alice".to_string()],
+            mutable: true,
+        };
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let set_config = ExecuteMsg::SetConfig {
+            nft_address: Addr::unchecked("nft"),
+            owner: Addr::unchecked("alice"),
+            accepted_assets: vec![],
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("bob", &[]),
+            set_config.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            set_config,
+        )
+        .unwrap();
+        let config = CONFIG.load(&deps.storage).unwrap();
+        assert_eq!(config.nft_address, Addr::unchecked("nft"));
+    }
+
+    #[test]
+    fn add_remove_and_freeze_admins() {
+        let mut deps = mock_dependencies();
+        let msg = InstantiateMsg {
+            admins: vec!["alice".to_string()],
+            mutable: true,
+        };
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        //// non admin cannot add admins ////
+        let err = add_admins(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("bob", &[]),
+            vec!["bob".to_string()],
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        add_admins(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            vec!["bob".to_string(), "alice".to_string()],
+        )
+        .unwrap();
+        let admin_list = ADMIN_LIST.load(&deps.storage).unwrap();
+        assert_eq!(
+            admin_list.admins,
+            vec![Addr::unchecked("alice"), Addr::unchecked("bob")]
+        );
+
+        remove_admins(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("bob", &[]),
+            vec!["alice".to_string()],
+        )
+        .unwrap();
+        let err = remove_admins(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("bob", &[]),
+            vec!["bob".to_string()],
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Std(StdError::generic_err("Cannot remove all admins"))
+        );
+
+        //// once frozen the admin list can no longer change ////
+        freeze(deps.as_mut(), mock_env(), mock_info("bob", &[])).unwrap();
+        let err = add_admins(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("bob", &[]),
+            vec!["carl".to_string()],
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+    }
+}
@@ -0,0 +1,388 @@
+use crate::delegation::held_exposure;
+use crate::helpers::transfer_msg;
+use crate::invoice::{complete_acceptance, execute_payment, Payer};
+use crate::org::payout_address;
+use crate::state::*;
+use cosmwasm_std::{Addr, Coin, DepsMut, Env, MessageInfo, Response, StdError, StdResult, Storage};
+
+use crate::error::ContractError;
+
+pub fn set_approval_policy(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    multisig: Option<String>,
+    thresholds: Vec<Coin>,
+) -> Result<Response, ContractError> {
+    if !CONTACT_INFO.has(deps.storage, &info.sender) {
+        return Err(StdError::generic_err("Profile does not exist").into());
+    }
+
+    match multisig {
+        Some(multisig) => {
+            if thresholds.is_empty() {
+                return Err(StdError::generic_err("At least one threshold is required").into());
+            }
+            let policy = ApprovalPolicy {
+                multisig: deps.api.addr_validate(&multisig)?,
+                thresholds,
+            };
+            APPROVAL_POLICY.save(deps.storage, &info.sender, &policy)?;
+        }
+        None => APPROVAL_POLICY.remove(deps.storage, &info.sender),
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "set_approval_policy")
+        .add_attribute("sender", info.sender))
+}
+
+/// holds the action for the payee's multisig if the invoice needs approval, returns `None` otherwise
+pub fn hold_for_approval(
+    storage: &mut dyn Storage,
+    env: &Env,
+    invoice: &Invoice,
+    action: ApprovalAction,
+    requested_by: Addr,
+) -> StdResult<Option<Response>> {
+    let policy = match APPROVAL_POLICY.may_load(storage, &invoice.payee_address)? {
+        Some(policy) if policy.requires_approval(&invoice.receivable) => policy,
+        _ => return Ok(None),
+    };
+    if PENDING_APPROVALS.has(storage, invoice.id) {
+        return Err(StdError::generic_err("Approval already pending"));
+    }
+
+    PENDING_APPROVALS.save(
+        storage,
+        invoice.id,
+        &PendingApproval {
+            invoice_id: invoice.id,
+            action,
+            approver: policy.multisig.clone(),
+            requested_by,
+            requested_at: env.block.time,
+        },
+    )?;
+
+    Ok(Some(
+        Response::new()
+            .add_attribute("method", "request_approval")
+            .add_attribute("invoice_id", invoice.id.to_string())
+            .add_attribute("approver", policy.multisig),
+    ))
+}
+
+/// runs the held action, sent by the multisig once its proposal passed
+pub fn approve_invoice(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    invoice_id: u64,
+) -> Result<Response, ContractError> {
+    let pending = PENDING_APPROVALS.load(deps.storage, invoice_id)?;
+    if pending.approver != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    PENDING_APPROVALS.remove(deps.storage, invoice_id);
+
+    let invoice = INVOICE.load(deps.storage, invoice_id)?;
+    let member = Some(pending.requested_by.clone()).filter(|by| *by != invoice.payee_address);
+    let response = match pending.action {
+        ApprovalAction::Accept {} => complete_acceptance(deps, env, invoice, member)?,
+        ApprovalAction::Pay { denom, amount } => execute_payment(
+            deps,
+            env,
+            Payer {
+                address: pending.requested_by,
+                member: None,
+            },
+            denom,
+            amount,
+            invoice_id,
+            true,
+        )?,
+    };
+    Ok(response.add_attribute("approved_by", info.sender))
+}
+
+/// drops the held action and returns a held payment, by the multisig, the payee or the requester
+pub fn reject_approval(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    invoice_id: u64,
+) -> Result<Response, ContractError> {
+    let pending = PENDING_APPROVALS.load(deps.storage, invoice_id)?;
+    let invoice = INVOICE.load(deps.storage, invoice_id)?;
+    if ![
+        &pending.approver,
+        &pending.requested_by,
+        &invoice.payee_address,
+    ]
+    .contains(&&info.sender)
+    {
+        return Err(ContractError::Unauthorized {});
+    }
+    PENDING_APPROVALS.remove(deps.storage, invoice_id);
+
+    //// a delegate gets back the allowance the held action took ////
+    let delegation_key = (&invoice.payee_address, &pending.requested_by);
+    if let Some(mut delegation) = DELEGATIONS.may_load(deps.storage, delegation_key)? {
+        if let Some(exposure) = held_exposure(&invoice, &pending.requested_by, &pending.action) {
+            delegation.restore(&exposure);
+            DELEGATIONS.save(deps.storage, delegation_key, &delegation)?;
+        }
+    }
+
+    let mut response = Response::new()
+        .add_attribute("method", "reject_approval")
+        .add_attribute("invoice_id", invoice_id.to_string())
+        .add_attribute("sender", info.sender);
+    if let ApprovalAction::Pay { denom, amount } = pending.action {
+        let recipient = payout_address(deps.storage, &pending.requested_by)?;
+        response = response
+            .add_message(transfer_msg(&denom, &recipient, amount)?)
+            .add_attribute("refunded", amount.to_string());
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::execute;
+    use crate::msg::ExecuteMsg;
+    use crate::query::{get_approval_proposal, get_pending_approval, get_pending_approvals};
+    use crate::testing::*;
+    use cosmwasm_std::testing::{mock_env, mock_info};
+    use cosmwasm_std::{coin, coins, BankMsg, CosmosMsg, Uint128};
+
+    const MULTISIG: &str = "multisig";
+
+    fn set_policy(deps: &mut MockDeps, threshold: u128) {
+        let msg = ExecuteMsg::SetApprovalPolicy {
+            multisig: Some(MULTISIG.to_string()),
+            thresholds: vec![coin(threshold, "uatom")],
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(PAYEE, &[]), msg).unwrap();
+    }
+
+    fn raise_invoice(deps: &mut MockDeps) -> u64 {
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            create_invoice_msg(),
+        )
+        .unwrap();
+        INVOICE_ID.load(&deps.storage).unwrap() - 1
+    }
+
+    #[test]
+    fn high_value_invoices_wait_for_the_multisig() {
+        let mut deps = setup();
+        set_policy(&mut deps, 500);
+        let invoice_id = raise_invoice(&mut deps);
+
+        //// acceptance is held instead of applied ////
+        let accept = ExecuteMsg::AcceptInvoice { invoice_id };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[]),
+            accept.clone(),
+        )
+        .unwrap();
+        let invoice = INVOICE.load(&deps.storage, invoice_id).unwrap();
+        assert_eq!(invoice.status, Status::Raised);
+        let pending = get_pending_approval(deps.as_ref(), invoice_id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(pending.action, ApprovalAction::Accept {});
+        assert_eq!(pending.approver, Addr::unchecked(MULTISIG));
+
+        let err = execute(deps.as_mut(), mock_env(), mock_info(PAYEE, &[]), accept).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Std(StdError::generic_err("Approval already pending"))
+        );
+
+        //// the proposal for the multisig calls back into core ////
+        let proposal = get_approval_proposal(deps.as_ref(), mock_env(), invoice_id).unwrap();
+        match proposal {
+            CosmosMsg::Wasm(cosmwasm_std::WasmMsg::Execute { contract_addr, .. }) => {
+                assert_eq!(contract_addr, MULTISIG)
+            }
+            _ => panic!("expected a wasm message"),
+        }
+
+        let approve = ExecuteMsg::ApproveInvoice { invoice_id };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[]),
+            approve.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(MULTISIG, &[]),
+            approve.clone(),
+        )
+        .unwrap();
+        let invoice = INVOICE.load(&deps.storage, invoice_id).unwrap();
+        assert_eq!(invoice.status, Status::Accepted);
+        assert_eq!(invoice.accepted_by, None);
+
+        //// payments are held by core until approved ////
+        let pay = ExecuteMsg::PayInvoice { invoice_id };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &coins(1000, "uatom")),
+            pay,
+        )
+        .unwrap();
+        let invoice = INVOICE.load(&deps.storage, invoice_id).unwrap();
+        assert_eq!(invoice.status, Status::Accepted);
+        let outstanding =
+            get_pending_approvals(deps.as_ref(), Some(Addr::unchecked(MULTISIG)), None, None)
+                .unwrap();
+        assert_eq!(outstanding.len(), 1);
+        assert!(
+            get_pending_approvals(deps.as_ref(), Some(Addr::unchecked(PAYEE)), None, None)
+                .unwrap()
+                .is_empty()
+        );
+
+        let res = execute(deps.as_mut(), mock_env(), mock_info(MULTISIG, &[]), approve).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: ISSUER.to_string(),
+                amount: coins(1000, "uatom"),
+            })
+        );
+        let invoice = INVOICE.load(&deps.storage, invoice_id).unwrap();
+        assert_eq!(invoice.status, Status::Paid);
+        assert!(get_pending_approval(deps.as_ref(), invoice_id)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn rejected_payments_are_refunded() {
+        let mut deps = setup();
+
+        //// invoices at the threshold are accepted directly ////
+        set_policy(&mut deps, 1000);
+        let invoice_id = raise_invoice(&mut deps);
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[]),
+            ExecuteMsg::AcceptInvoice { invoice_id },
+        )
+        .unwrap();
+        let invoice = INVOICE.load(&deps.storage, invoice_id).unwrap();
+        assert_eq!(invoice.status, Status::Accepted);
+
+        set_policy(&mut deps, 500);
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &coins(400, "uatom")),
+            ExecuteMsg::PayInvoice { invoice_id },
+        )
+        .unwrap();
+        let pending = get_pending_approval(deps.as_ref(), invoice_id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            pending.action,
+            ApprovalAction::Pay {
+                denom: cw20::Denom::Native("uatom".to_string()),
+                amount: Uint128::new(400),
+            }
+        );
+
+        let reject = ExecuteMsg::RejectApproval { invoice_id };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            reject.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        let res = execute(deps.as_mut(), mock_env(), mock_info(PAYEE, &[]), reject).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: PAYEE.to_string(),
+                amount: coins(400, "uatom"),
+            })
+        );
+        let invoice = INVOICE.load(&deps.storage, invoice_id).unwrap();
+        assert_eq!(invoice.amount_paid.amount, Uint128::zero());
+        assert!(get_pending_approval(deps.as_ref(), invoice_id)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn rejected_delegate_payments_restore_the_allowance() {
+        let mut deps = setup();
+        set_policy(&mut deps, 1000);
+        let invoice_id = raise_invoice(&mut deps);
+        let msgs = vec![
+            ExecuteMsg::AcceptInvoice { invoice_id },
+            ExecuteMsg::SetDelegation {
+                delegate: "bot".to_string(),
+                permissions: DelegatePermissions {
+                    accept: false,
+                    pay: true,
+                    create: false,
+                },
+                spend_limits: coins(1000, "uatom"),
+                expires: None,
+            },
+        ];
+        for msg in msgs {
+            execute(deps.as_mut(), mock_env(), mock_info(PAYEE, &[]), msg).unwrap();
+        }
+        set_policy(&mut deps, 500);
+
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("bot", &coins(400, "uatom")),
+            ExecuteMsg::PayInvoice { invoice_id },
+        )
+        .unwrap();
+        let key = (&Addr::unchecked(PAYEE), &Addr::unchecked("bot"));
+        let delegation = DELEGATIONS.load(&deps.storage, key).unwrap();
+        assert_eq!(delegation.spend_limits, coins(600, "uatom"));
+
+        //// the bot gets its funds back and the payee its allowance ////
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[]),
+            ExecuteMsg::RejectApproval { invoice_id },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "bot".to_string(),
+                amount: coins(400, "uatom"),
+            })
+        );
+        let delegation = DELEGATIONS.load(&deps.storage, key).unwrap();
+        assert_eq!(delegation.spend_limits, coins(1000, "uatom"));
+    }
+}
@@ -0,0 +1,298 @@
+use crate::state::*;
+use cosmwasm_std::{Addr, DepsMut, Env, MessageInfo, Response, StdError, StdResult, Storage};
+use cw20::Denom;
+
+use crate::error::ContractError;
+
+const MAX_ALIAS_LENGTH: usize = 64;
+
+/// loads the sender's entry for `address`, which has to be one of its contacts
+fn load_contact(storage: &dyn Storage, owner: &Addr, address: &Addr) -> StdResult<Contact> {
+    if !CONTACT_INFO.has(storage, owner) {
+        return Err(StdError::generic_err("Profile does not exist"));
+    }
+    CONTACTS
+        .may_load(storage, (owner, address))?
+        .ok_or_else(|| StdError::generic_err("Not in contact list"))
+}
+
+pub fn set_contact_alias(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    address: Addr,
+    alias: Option<String>,
+) -> Result<Response, ContractError> {
+    let mut contact = load_contact(deps.storage, &info.sender, &address)?;
+
+    if let Some(alias) = &alias {
+        if alias.is_empty() || alias.len() > MAX_ALIAS_LENGTH {
+            return Err(StdError::generic_err("Alias must be 1 to 64 characters").into());
+        }
+        match CONTACT_ALIASES.may_load(deps.storage, (&info.sender, alias))? {
+            Some(aliased) if aliased != address => {
+                return Err(StdError::generic_err("Alias already in use").into());
+            }
+            _ => (),
+        }
+    }
+
+    if let Some(previous) = &contact.alias {
+        CONTACT_ALIASES.remove(deps.storage, (&info.sender, previous));
+    }
+    if let Some(alias) = &alias {
+        CONTACT_ALIASES.save(deps.storage, (&info.sender, alias), &address)?;
+    }
+    contact.alias = alias.clone();
+    CONTACTS.save(deps.storage, (&info.sender, &address), &contact)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "set_contact_alias")
+        .add_attribute("sender", info.sender)
+        .add_attribute("contact", address)
+        .add_attribute("alias", alias.unwrap_or_default()))
+}
+
+pub fn tag_contact(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    address: Addr,
+    tag: Option<ContactTag>,
+) -> Result<Response, ContractError> {
+    let mut contact = load_contact(deps.storage, &info.sender, &address)?;
+    contact.tag = tag;
+    CONTACTS.save(deps.storage, (&info.sender, &address), &contact)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "tag_contact")
+        .add_attribute("sender", info.sender)
+        .add_attribute("contact", address))
+}
+
+pub fn set_contact_defaults(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    address: Addr,
+    default_asset: Option<Denom>,
+    default_terms: Option<PaymentTerms>,
+) -> Result<Response, ContractError> {
+    let mut contact = load_contact(deps.storage, &info.sender, &address)?;
+
+    if let Some(denom) = &default_asset {
+        let config = CONFIG.load(deps.storage)?;
+        if !config
+            .accepted_assets
+            .iter()
+            .any(|asset| asset.denom == *denom)
+        {
+            return Err(StdError::generic_err("Asset not accepted").into());
+        }
+    }
+
+    contact.default_asset = default_asset;
+    contact.default_terms = default_terms;
+    CONTACTS.save(deps.storage, (&info.sender, &address), &contact)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "set_contact_defaults")
+        .add_attribute("sender", info.sender)
+        .add_attribute("contact", address))
+}
+
+/// drops the owner's address book entry and alias for `address`
+pub fn remove_contact_entry(
+    storage: &mut dyn Storage,
+    owner: &Addr,
+    address: &Addr,
+) -> StdResult<()> {
+    if let Some(contact) = CONTACTS.may_load(storage, (owner, address))? {
+        if let Some(alias) = &contact.alias {
+            CONTACT_ALIASES.remove(storage, (owner, alias));
+        }
+        CONTACTS.remove(storage, (owner, address));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::{execute, query};
+    use crate::msg::{ExecuteMsg, QueryMsg};
+    use crate::query::AddressBookResponse;
+    use crate::testing::*;
+    use cosmwasm_std::testing::{mock_env, mock_info};
+    use cosmwasm_std::{from_binary, Decimal};
+
+    const CAROL: &str = "carol";
+
+    /// adds carol as a third contact of the issuer
+    fn setup_address_book() -> MockDeps {
+        let mut deps = setup();
+        let msgs = vec![
+            (CAROL, create_profile_msg()),
+            (
+                ISSUER,
+                ExecuteMsg::CreateRequest {
+                    address: Addr::unchecked(CAROL),
+                },
+            ),
+            (
+                CAROL,
+                ExecuteMsg::AcceptRequest {
+                    address: Addr::unchecked(ISSUER),
+                },
+            ),
+        ];
+        for (sender, msg) in msgs {
+            execute(deps.as_mut(), mock_env(), mock_info(sender, &[]), msg).unwrap();
+        }
+        deps
+    }
+
+    fn contacts(deps: &MockDeps, tag: Option<ContactTag>) -> Vec<Addr> {
+        let msg = QueryMsg::GetContacts {
+            owner: Addr::unchecked(ISSUER),
+            tag,
+            start_after: None,
+            limit: None,
+        };
+        let entries: Vec<AddressBookResponse> =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        entries
+            .into_iter()
+            .map(|entry| entry.entry.contact_address)
+            .collect()
+    }
+
+    #[test]
+    fn aliases_resolve_per_owner() {
+        let mut deps = setup_address_book();
+        let alias = |address: &str, alias: &str| ExecuteMsg::SetContactAlias {
+            address: Addr::unchecked(address),
+            alias: Some(alias.to_string()),
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            alias(PAYEE, "acme"),
+        )
+        .unwrap();
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            alias(CAROL, "acme"),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Std(StdError::generic_err("Alias already in use"))
+        );
+        //// aliases are per owner ////
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(CAROL, &[]),
+            alias(ISSUER, "acme"),
+        )
+        .unwrap();
+
+        let resolve = QueryMsg::ResolveContactAlias {
+            owner: Addr::unchecked(ISSUER),
+            alias: "acme".to_string(),
+        };
+        let address: Addr =
+            from_binary(&query(deps.as_ref(), mock_env(), resolve.clone()).unwrap()).unwrap();
+        assert_eq!(address, Addr::unchecked(PAYEE));
+
+        //// removing the contact drops the alias ////
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[]),
+            ExecuteMsg::RemoveContact {
+                address: Addr::unchecked(ISSUER),
+            },
+        )
+        .unwrap();
+        query(deps.as_ref(), mock_env(), resolve).unwrap_err();
+    }
+
+    #[test]
+    fn contacts_are_filtered_by_tag_and_carry_defaults() {
+        let mut deps = setup_address_book();
+        let msgs = vec![
+            ExecuteMsg::TagContact {
+                address: Addr::unchecked(PAYEE),
+                tag: Some(ContactTag::Customer),
+            },
+            ExecuteMsg::TagContact {
+                address: Addr::unchecked(CAROL),
+                tag: Some(ContactTag::Both),
+            },
+            ExecuteMsg::SetContactDefaults {
+                address: Addr::unchecked(PAYEE),
+                default_asset: Some(Denom::Native("uatom".to_string())),
+                default_terms: Some(PaymentTerms {
+                    discount_rate: Some(Decimal::percent(1)),
+                    discount_days: 5,
+                    late_fee: None,
+                    grace_period_days: 0,
+                }),
+            },
+        ];
+        for msg in msgs {
+            execute(deps.as_mut(), mock_env(), mock_info(ISSUER, &[]), msg).unwrap();
+        }
+
+        assert_eq!(
+            contacts(&deps, Some(ContactTag::Customer)),
+            vec![Addr::unchecked(CAROL), Addr::unchecked(PAYEE)]
+        );
+        assert_eq!(
+            contacts(&deps, Some(ContactTag::Supplier)),
+            vec![Addr::unchecked(CAROL)]
+        );
+
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            ExecuteMsg::SetContactDefaults {
+                address: Addr::unchecked(CAROL),
+                default_asset: Some(Denom::Native("uosmo".to_string())),
+                default_terms: None,
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Std(StdError::generic_err("Asset not accepted"))
+        );
+
+        //// invoices without terms or denom pick up the contact's defaults ////
+        let mut msg = create_invoice_msg();
+        if let ExecuteMsg::CreateInvoice {
+            receivable,
+            amount_paid,
+            ..
+        } = &mut msg
+        {
+            receivable.denom = String::new();
+            amount_paid.denom = String::new();
+        }
+        execute(deps.as_mut(), mock_env(), mock_info(ISSUER, &[]), msg).unwrap();
+        let invoice_id = INVOICE_ID.load(&deps.storage).unwrap() - 1;
+        let invoice = INVOICE.load(&deps.storage, invoice_id).unwrap();
+        assert_eq!(
+            invoice.payment_terms.unwrap().discount_rate,
+            Some(Decimal::percent(1))
+        );
+        assert_eq!(invoice.receivable.denom, "uatom");
+        assert_eq!(invoice.amount_paid.denom, "uatom");
+    }
+}
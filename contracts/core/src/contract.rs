@@ -5,11 +5,23 @@ use cosmwasm_std::{
     StdResult,
 };
 
+use crate::admin;
+use crate::approval;
+use crate::contact;
+use crate::credit_note;
+use crate::delegation;
+use crate::dispute;
 use crate::error::ContractError;
+use crate::escrow;
+use crate::fee;
 use crate::invoice::*;
+use crate::kyc;
+use crate::migration;
 use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
+use crate::org;
 use crate::profile;
 use crate::query::*;
+use crate::recurring;
 use crate::state::*;
 use cw2::set_contract_version;
 // version info for migration info
@@ -24,6 +36,17 @@ pub fn instantiate(
     msg: InstantiateMsg,
 ) -> StdResult<Response> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    //// contract cannot be configured without at least one admin ////
+    if msg.admins.is_empty() {
+        return Err(StdError::generic_err("At least one admin is required"));
+    }
+    let admin_list = AdminList {
+        admins: map_validate(deps.api, &msg.admins)?,
+        mutable: msg.mutable,
+    };
+    ADMIN_LIST.save(deps.storage, &admin_list)?;
+
     INVOICE_ID.save(deps.storage, &1000000)?;
     Ok(Response::default())
 }
@@ -40,6 +63,22 @@ pub fn execute(
     // Note: implement this function with different type to add support for custom messages
     // and then import the rest of this contract code.
     msg: ExecuteMsg,
+) -> Result<Response<Empty>, ContractError> {
+    //// state is only partly migrated until a batched migration has finished ////
+    if PENDING_MIGRATION.exists(deps.storage) {
+        return Err(StdError::generic_err("Migration in progress").into());
+    }
+
+    dispatch(deps, env, info, msg, None)
+}
+
+/// runs `msg` for `info.sender`, `acting` is the org member or delegate sending it for them
+pub fn dispatch(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+    acting: Option<Addr>,
 ) -> Result<Response<Empty>, ContractError> {
     match msg {
         ExecuteMsg::CreateRequest { address } => profile::create_request(deps, env, info, address),
@@ -60,73 +99,409 @@ pub fn execute(
             company_name,
             address,
         ),
+        ExecuteMsg::RejectRequest { address } => profile::reject_request(deps, env, info, address),
+        ExecuteMsg::CancelRequest { address } => profile::cancel_request(deps, env, info, address),
+        ExecuteMsg::RemoveContact { address } => profile::remove_contact(deps, env, info, address),
+        ExecuteMsg::BlockAddress { address } => profile::block_address(deps, env, info, address),
+        ExecuteMsg::UnblockAddress { address } => {
+            profile::unblock_address(deps, env, info, address)
+        }
+        ExecuteMsg::SetContactAlias { address, alias } => {
+            contact::set_contact_alias(deps, env, info, address, alias)
+        }
+        ExecuteMsg::TagContact { address, tag } => {
+            contact::tag_contact(deps, env, info, address, tag)
+        }
+        ExecuteMsg::SetContactDefaults {
+            address,
+            default_asset,
+            default_terms,
+        } => contact::set_contact_defaults(deps, env, info, address, default_asset, default_terms),
+        ExecuteMsg::UpdateProfile { update } => profile::update_profile(deps, env, info, update),
+        ExecuteMsg::DeactivateProfile {} => profile::deactivate_profile(deps, env, info),
+        ExecuteMsg::EraseProfile { address } => profile::erase_profile(deps, env, info, address),
+        ExecuteMsg::CreatePrivateProfile {
+            company_name,
+            commitments,
+            encrypted_pii,
+            viewing_key,
+        } => profile::create_private_profile(
+            deps,
+            env,
+            info,
+            company_name,
+            commitments,
+            encrypted_pii,
+            viewing_key,
+        ),
+        ExecuteMsg::SetViewingKey { viewing_key } => {
+            profile::set_viewing_key(deps, env, info, viewing_key)
+        }
+        ExecuteMsg::SharePii {
+            contact,
+            encrypted_pii,
+        } => profile::share_pii(deps, env, info, contact, encrypted_pii),
+        ExecuteMsg::CreateOrgProfile {
+            group,
+            name,
+            email_id,
+            phone_number,
+            company_name,
+            address,
+            payout,
+        } => {
+            let params = OrgProfileParams {
+                group,
+                name,
+                email_id,
+                phone_number,
+                company_name,
+                address,
+                payout,
+            };
+            org::create_org_profile(deps, env, info, params)
+        }
+        ExecuteMsg::SetOrgPayout { org, payout } => {
+            org::set_org_payout(deps, env, info, org, payout)
+        }
+        ExecuteMsg::SetOrgRoles { org, member, roles } => {
+            org::set_org_roles(deps, env, info, org, member, roles)
+        }
+        ExecuteMsg::ExecuteAs { org, msg } => org::execute_as(deps, env, info, org, *msg),
+        ExecuteMsg::MemberChangedHook(msg) => org::member_changed_hook(deps, env, info, msg),
+        ExecuteMsg::SetApprovalPolicy {
+            multisig,
+            thresholds,
+        } => approval::set_approval_policy(deps, env, info, multisig, thresholds),
+        ExecuteMsg::ApproveInvoice { invoice_id } => {
+            approval::approve_invoice(deps, env, info, invoice_id)
+        }
+        ExecuteMsg::RejectApproval { invoice_id } => {
+            approval::reject_approval(deps, env, info, invoice_id)
+        }
+        ExecuteMsg::SetDelegation {
+            delegate,
+            permissions,
+            spend_limits,
+            expires,
+        } => delegation::set_delegation(
+            deps,
+            env,
+            info,
+            delegate,
+            permissions,
+            spend_limits,
+            expires,
+        ),
+        ExecuteMsg::RevokeDelegation { delegate } => {
+            delegation::revoke_delegation(deps, env, info, delegate)
+        }
+        ExecuteMsg::ExecuteAsDelegate { owner, msg } => {
+            delegation::execute_as_delegate(deps, env, info, owner, *msg)
+        }
+        ExecuteMsg::SetFeeConfig {
+            payment_fee_bps,
+            creation_fee,
+            caps,
+        } => fee::set_fee_config(deps, env, info, payment_fee_bps, creation_fee, caps),
+        ExecuteMsg::AddFeeExemptions { addresses } => {
+            fee::add_fee_exemptions(deps, env, info, addresses)
+        }
+        ExecuteMsg::RemoveFeeExemptions { addresses } => {
+            fee::remove_fee_exemptions(deps, env, info, addresses)
+        }
+        ExecuteMsg::WithdrawFees {
+            denom,
+            amount,
+            recipient,
+        } => fee::withdraw_fees(deps, env, info, denom, amount, recipient),
         ExecuteMsg::SetConfig {
             nft_address,
             owner,
             accepted_assets,
-        } => set_config(deps, nft_address, owner, accepted_assets),
+        } => set_config(deps, info, nft_address, owner, accepted_assets),
         ExecuteMsg::CreateInvoice {
             payee_address,
             receivable,
             amount_paid,
             service_type,
             doc_uri,
-        } => create_invoice(
-            deps,
-            env,
-            info,
-            payee_address,
-            receivable,
-            amount_paid,
-            service_type,
-            doc_uri,
-        ),
-        ExecuteMsg::AcceptInvoice { invoice_id } => accept_invoice(deps, env, info, invoice_id),
-        ExecuteMsg::PayInvoice { invoice_id } => pay_invoice(deps, env, info, invoice_id),
+            due_date,
+            payment_terms,
+            settlement,
+            installments,
+            line_items,
+        } => {
+            let params = InvoiceParams {
+                payee_address,
+                receivable,
+                amount_paid,
+                service_type,
+                doc_uri,
+                due_date,
+                payment_terms,
+                settlement,
+                installments,
+                line_items,
+            };
+            create_invoice(deps, env, info, params, acting)
+        }
+        ExecuteMsg::AcceptInvoice { invoice_id } => {
+            accept_invoice(deps, env, info, invoice_id, acting)
+        }
+        ExecuteMsg::PayInvoice { invoice_id } => pay_invoice(deps, env, info, invoice_id, acting),
+        ExecuteMsg::RejectInvoice { invoice_id, reason } => {
+            reject_invoice(deps, env, info, invoice_id, reason)
+        }
+        ExecuteMsg::CancelInvoice { invoice_id } => cancel_invoice(deps, env, info, invoice_id),
+        ExecuteMsg::VoidInvoice { invoice_id, reason } => {
+            void_invoice(deps, env, info, invoice_id, reason)
+        }
+        ExecuteMsg::AddAdmins { admins } => admin::add_admins(deps, env, info, admins),
+        ExecuteMsg::RemoveAdmins { admins } => admin::remove_admins(deps, env, info, admins),
+        ExecuteMsg::Freeze {} => admin::freeze(deps, env, info),
+        ExecuteMsg::Receive(msg) => receive_cw20(deps, env, info, msg),
+        ExecuteMsg::ConfirmDelivery { invoice_id } => {
+            escrow::confirm_delivery(deps, env, info, invoice_id)
+        }
+        ExecuteMsg::ReleaseEscrow { invoice_id } => {
+            escrow::release_escrow(deps, env, info, invoice_id)
+        }
+        ExecuteMsg::RefundEscrow { invoice_id } => {
+            escrow::refund_escrow(deps, env, info, invoice_id)
+        }
+        ExecuteMsg::CreateRecurring { template } => {
+            recurring::create_recurring(deps, env, info, template)
+        }
+        ExecuteMsg::CancelRecurring { recurring_id } => {
+            recurring::cancel_recurring(deps, env, info, recurring_id)
+        }
+        ExecuteMsg::TriggerRecurring { recurring_id } => {
+            recurring::trigger_recurring(deps, env, info, recurring_id)
+        }
+        ExecuteMsg::CreateCreditNote {
+            invoice_id,
+            amount,
+            reason,
+        } => credit_note::create_credit_note(deps, env, info, invoice_id, amount, reason),
+        ExecuteMsg::AcknowledgeCreditNote { credit_note_id } => {
+            credit_note::acknowledge_credit_note(deps, env, info, credit_note_id)
+        }
+        ExecuteMsg::CancelCreditNote { credit_note_id } => {
+            credit_note::cancel_credit_note(deps, env, info, credit_note_id)
+        }
+        ExecuteMsg::RefundCreditNote { credit_note_id } => {
+            credit_note::refund_credit_note(deps, env, info, credit_note_id)
+        }
+        ExecuteMsg::AddKycProviders { providers } => {
+            admin::add_kyc_providers(deps, env, info, providers)
+        }
+        ExecuteMsg::RemoveKycProviders { providers } => {
+            admin::remove_kyc_providers(deps, env, info, providers)
+        }
+        ExecuteMsg::SetKycPolicy { policy } => admin::set_kyc_policy(deps, env, info, policy),
+        ExecuteMsg::UpdateKyc {
+            address,
+            status,
+            expires_at,
+            attestation,
+        } => kyc::update_kyc(deps, env, info, address, status, expires_at, attestation),
+        ExecuteMsg::SetArbitration {
+            arbitrator,
+            evidence_days,
+            ruling_days,
+        } => admin::set_arbitration(deps, env, info, arbitrator, evidence_days, ruling_days),
+        ExecuteMsg::OpenDispute {
+            invoice_id,
+            evidence_uri,
+        } => dispute::open_dispute(deps, env, info, invoice_id, evidence_uri),
+        ExecuteMsg::SubmitEvidence {
+            dispute_id,
+            evidence_uri,
+        } => dispute::submit_evidence(deps, env, info, dispute_id, evidence_uri),
+        ExecuteMsg::ResolveDispute { dispute_id, ruling } => {
+            dispute::resolve_dispute(deps, env, info, dispute_id, ruling)
+        }
+        ExecuteMsg::ExpireDispute { dispute_id } => {
+            dispute::expire_dispute(deps, env, info, dispute_id)
+        }
     }
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
-        QueryMsg::GetInvoice { invoice_id } => to_binary(&get_invoice(deps, invoice_id)?),
+        QueryMsg::GetInvoice { invoice_id } => to_binary(&get_invoice(deps, env, invoice_id)?),
         QueryMsg::GetConfig {} => to_binary(&get_config(deps)?),
         QueryMsg::GetLatestInvoiceId {} => to_binary(&get_latest_invoice_id(deps)?),
         QueryMsg::GetContactInfo { address } => to_binary(&get_contact_info(deps, address)?),
-        QueryMsg::GetPendingInvoices { address } => {
-            to_binary(&get_pending_invoices(deps, address)?)
+        QueryMsg::GetInvoices {
+            filter,
+            start_after,
+            limit,
+        } => to_binary(&get_invoices(deps, env, filter, start_after, limit)?),
+        QueryMsg::GetPendingInvoices {
+            address,
+            status,
+            start_after,
+            limit,
+        } => to_binary(&get_pending_invoices(
+            deps,
+            env,
+            address,
+            status,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::GetExecutedInvoices {
+            address,
+            start_after,
+            limit,
+        } => to_binary(&get_executed_invoices(deps, address, start_after, limit)?),
+        QueryMsg::GetTotalReceivables {
+            address,
+            start_after,
+            limit,
+        } => to_binary(&get_total_receivables(deps, address, start_after, limit)?),
+        QueryMsg::GetTotalPayables {
+            address,
+            start_after,
+            limit,
+        } => to_binary(&get_total_payables(deps, address, start_after, limit)?),
+        QueryMsg::GetPendingContactRequests {
+            address,
+            start_after,
+            limit,
+        } => to_binary(&get_pending_contact_requests(
+            deps,
+            address,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::GetSentContactRequests {
+            address,
+            start_after,
+            limit,
+        } => to_binary(&get_sent_contact_requests(
+            deps,
+            address,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::GetAllContacts {
+            address,
+            start_after,
+            limit,
+        } => to_binary(&get_all_contacts(deps, address, start_after, limit)?),
+        QueryMsg::GetAdminList {} => to_binary(&get_admin_list(deps)?),
+        QueryMsg::GetAgingReport {
+            address,
+            start_after,
+            limit,
+        } => to_binary(&get_aging_report(deps, env, address, start_after, limit)?),
+        QueryMsg::GetAmountDue { invoice_id } => to_binary(&get_amount_due(deps, env, invoice_id)?),
+        QueryMsg::GetInvoicePayments { invoice_id } => {
+            to_binary(&get_invoice_payments(deps, invoice_id)?)
         }
-        QueryMsg::GetExecutedInvoices { address } => {
-            to_binary(&get_executed_invoices(deps, address)?)
+        QueryMsg::GetEscrow { invoice_id } => to_binary(&get_escrow(deps, invoice_id)?),
+        QueryMsg::GetInstallments { invoice_id } => {
+            to_binary(&get_installments(deps, env, invoice_id)?)
         }
-        QueryMsg::GetTotalReceivables { address } => {
-            to_binary(&get_total_receivables(deps, address)?)
+        QueryMsg::GetTaxSummary { invoice_id } => to_binary(&get_tax_summary(deps, invoice_id)?),
+        QueryMsg::GetRecurring { recurring_id } => {
+            to_binary(&get_recurring(deps, env, recurring_id)?)
         }
-        QueryMsg::GetTotalPayables { address } => to_binary(&get_total_payables(deps, address)?),
-        QueryMsg::GetPendingContactRequests { address } => {
-            to_binary(&get_pending_contact_requests(deps, address)?)
+        QueryMsg::GetRecurringInvoices {
+            address,
+            start_after,
+            limit,
+        } => to_binary(&get_recurring_invoices(deps, address, start_after, limit)?),
+        QueryMsg::GetCreditNote { credit_note_id } => {
+            to_binary(&get_credit_note(deps, credit_note_id)?)
         }
-        QueryMsg::GetSentContactRequests { address } => {
-            to_binary(&get_sent_contact_requests(deps, address)?)
+        QueryMsg::GetInvoiceCreditNotes { invoice_id } => {
+            to_binary(&get_invoice_credit_notes(deps, invoice_id)?)
+        }
+        QueryMsg::GetPendingMigration {} => to_binary(&PENDING_MIGRATION.may_load(deps.storage)?),
+        QueryMsg::GetContacts {
+            owner,
+            tag,
+            start_after,
+            limit,
+        } => to_binary(&get_contacts(deps, owner, tag, start_after, limit)?),
+        QueryMsg::ResolveContactAlias { owner, alias } => {
+            to_binary(&resolve_contact_alias(deps, owner, alias)?)
+        }
+        QueryMsg::GetOrg { org } => to_binary(&get_org(deps, org)?),
+        QueryMsg::GetOrgMembers {
+            org,
+            start_after,
+            limit,
+        } => to_binary(&get_org_members(deps, org, start_after, limit)?),
+        QueryMsg::GetFeeConfig {} => to_binary(&get_fee_config(deps)?),
+        QueryMsg::GetAssetFees { denom } => to_binary(&get_asset_fees(deps, denom)?),
+        QueryMsg::GetTreasury { start_after, limit } => {
+            to_binary(&get_treasury(deps, start_after, limit)?)
+        }
+        QueryMsg::GetFeeExemptions { start_after, limit } => {
+            to_binary(&get_fee_exemptions(deps, start_after, limit)?)
+        }
+        QueryMsg::GetDelegation { owner, delegate } => {
+            to_binary(&get_delegation(deps, owner, delegate)?)
+        }
+        QueryMsg::GetDelegations {
+            owner,
+            start_after,
+            limit,
+        } => to_binary(&get_delegations(deps, owner, start_after, limit)?),
+        QueryMsg::CanExecute { owner, sender, msg } => {
+            to_binary(&can_execute(deps, env, owner, sender, msg)?)
+        }
+        QueryMsg::GetApprovalPolicy { address } => to_binary(&get_approval_policy(deps, address)?),
+        QueryMsg::GetPendingApproval { invoice_id } => {
+            to_binary(&get_pending_approval(deps, invoice_id)?)
+        }
+        QueryMsg::GetPendingApprovals {
+            approver,
+            start_after,
+            limit,
+        } => to_binary(&get_pending_approvals(deps, approver, start_after, limit)?),
+        QueryMsg::GetApprovalProposal { invoice_id } => {
+            to_binary(&get_approval_proposal(deps, env, invoice_id)?)
+        }
+        QueryMsg::GetBlockedAddresses {
+            address,
+            start_after,
+            limit,
+        } => to_binary(&get_blocked_addresses(deps, address, start_after, limit)?),
+        QueryMsg::GetSharedPii { owner, viewer } => {
+            to_binary(&get_shared_pii(deps, owner, viewer)?)
+        }
+        QueryMsg::VerifyPii {
+            address,
+            field,
+            value,
+            salt,
+        } => to_binary(&verify_pii(deps, address, field, value, salt)?),
+        QueryMsg::GetKycProviders { start_after, limit } => {
+            to_binary(&get_kyc_providers(deps, start_after, limit)?)
+        }
+        QueryMsg::GetKycPolicy {} => to_binary(&get_kyc_policy(deps)?),
+        QueryMsg::GetKycStatus { address } => to_binary(&get_kyc_status(deps, env, address)?),
+        QueryMsg::GetArbitration {} => to_binary(&get_arbitration(deps)?),
+        QueryMsg::GetDispute { dispute_id } => to_binary(&get_dispute(deps, dispute_id)?),
+        QueryMsg::GetOpenDisputes {
+            address,
+            start_after,
+            limit,
+        } => to_binary(&get_open_disputes(deps, address, start_after, limit)?),
+        QueryMsg::GetRulingProposal { dispute_id, ruling } => {
+            to_binary(&get_ruling_proposal(deps, env, dispute_id, ruling)?)
         }
-        QueryMsg::GetAllContacts { address } => to_binary(&get_all_contacts(deps, address)?),
     }
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
-    let ver = cw2::get_contract_version(deps.storage)?;
-    // ensure we are migrating from an allowed contract
-    if ver.contract != CONTRACT_NAME {
-        return Err(StdError::generic_err("Can only upgrade from same type").into());
-    }
-    // note: better to do proper semver compare, but string compare *usually* works
-    if ver.version.as_str() > CONTRACT_VERSION {
-        return Err(StdError::generic_err("Cannot upgrade from a newer version").into());
-    }
-    // set the new version
-    cw2::set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
-    //do any desired state migrations...
-    //INVOICE.clear(deps.storage);
-    Ok(Response::default())
+pub fn migrate(deps: DepsMut, _env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    migration::migrate(deps.storage, CONTRACT_NAME, CONTRACT_VERSION, msg)
 }
@@ -0,0 +1,442 @@
+use crate::helpers::{denom_key, receivable_holder, transfer_msg};
+use crate::org::payout_address;
+use crate::state::*;
+use cosmwasm_std::{Addr, Coin, DepsMut, Env, MessageInfo, Response, StdError, Uint128};
+use cw20::Denom;
+
+use crate::error::ContractError;
+
+pub fn create_credit_note(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    invoice_id: u64,
+    amount: Uint128,
+    reason: String,
+) -> Result<Response, ContractError> {
+    //// do not accept funds ////
+    if !info.funds.is_empty() {
+        return Err(StdError::generic_err("Funds not accepted").into());
+    }
+
+    //// the credit is given up by whoever holds the receivable, not the original issuer ////
+    let invoice = INVOICE.load(deps.storage, invoice_id)?;
+    let config = CONFIG.load(deps.storage)?;
+    if receivable_holder(&deps.querier, &env, &config, &invoice)? != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    //// raised invoices are cancelled instead, paid ones can still be credited ////
+    match invoice.status {
+        Status::Raised => return Err(StdError::generic_err("Invoice not yet accepted").into()),
+        Status::Paid => (),
+        _ if invoice.status.is_closed() => {
+            return Err(StdError::generic_err("Invoice is closed").into())
+        }
+        _ => (),
+    }
+    invoice.ensure_not_disputed()?;
+
+    if amount.is_zero() {
+        return Err(StdError::generic_err("Amount cannot be zero").into());
+    }
+
+    //// all credit notes together cannot exceed the receivable ////
+    let mut credit_note_ids = INVOICE_CREDIT_NOTES
+        .may_load(deps.storage, &invoice_id)?
+        .unwrap_or_default();
+    let mut total = amount;
+    for credit_note_id in credit_note_ids.iter() {
+        let credit_note = CREDIT_NOTE.load(deps.storage, credit_note_id)?;
+        if credit_note.status != CreditNoteStatus::Cancelled {
+            total += credit_note.amount.amount;
+        }
+    }
+    if total > invoice.receivable.amount {
+        return Err(StdError::generic_err("Credit exceeds receivable").into());
+    }
+
+    let credit_note_id = CREDIT_NOTE_ID.may_load(deps.storage)?.unwrap_or_default() + 1;
+    let credit_note = CreditNote {
+        id: credit_note_id,
+        invoice_id,
+        from: info.sender,
+        payee_address: invoice.payee_address,
+        amount: Coin {
+            denom: invoice.receivable.denom,
+            amount,
+        },
+        reason,
+        status: CreditNoteStatus::Pending,
+        created_at: env.block.time,
+        acknowledged_at: None,
+        refund_due: Uint128::zero(),
+    };
+    CREDIT_NOTE.save(deps.storage, &credit_note_id, &credit_note)?;
+    CREDIT_NOTE_ID.save(deps.storage, &credit_note_id)?;
+
+    credit_note_ids.push(credit_note_id);
+    INVOICE_CREDIT_NOTES.save(deps.storage, &invoice_id, &credit_note_ids)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "create_credit_note")
+        .add_attribute("credit_note_id", credit_note_id.to_string())
+        .add_attribute("invoice_id", invoice_id.to_string())
+        .add_attribute("amount", amount.to_string()))
+}
+
+pub fn acknowledge_credit_note(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    credit_note_id: u64,
+) -> Result<Response, ContractError> {
+    let mut credit_note = CREDIT_NOTE.load(deps.storage, &credit_note_id)?;
+
+    //// only the payee can acknowledge the credit ////
+    if credit_note.payee_address != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    if credit_note.status != CreditNoteStatus::Pending {
+        return Err(StdError::generic_err("Credit note is not pending").into());
+    }
+
+    let mut invoice = INVOICE.load(deps.storage, credit_note.invoice_id)?;
+    if invoice.status.is_closed() && invoice.status != Status::Paid {
+        return Err(StdError::generic_err("Invoice is closed").into());
+    }
+    invoice.ensure_not_disputed()?;
+
+    //// a credit given up by a former holder no longer binds the receivable ////
+    let config = CONFIG.load(deps.storage)?;
+    if receivable_holder(&deps.querier, &env, &config, &invoice)? != credit_note.from {
+        return Err(StdError::generic_err("Receivable changed hands since the credit note").into());
+    }
+
+    //// the credit reduces what is still due, the rest is refunded to the payee ////
+    let applied = credit_note
+        .amount
+        .amount
+        .min(invoice.outstanding_principal());
+    let refund_due = credit_note.amount.amount - applied;
+    invoice.credited += applied;
+    invoice.apply_to_installments(applied);
+    invoice.due_amount.amount = invoice.outstanding_principal();
+    if invoice.status != Status::Paid && invoice.outstanding_principal().is_zero() {
+        invoice.status = Status::Paid;
+        invoice.paid_at = Some(env.block.time);
+    }
+    INVOICE.save(deps.storage, invoice.id, &invoice)?;
+
+    credit_note.status = CreditNoteStatus::Acknowledged;
+    credit_note.acknowledged_at = Some(env.block.time);
+    credit_note.refund_due = refund_due;
+    CREDIT_NOTE.save(deps.storage, &credit_note_id, &credit_note)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "acknowledge_credit_note")
+        .add_attribute("credit_note_id", credit_note_id.to_string())
+        .add_attribute("invoice_id", invoice.id.to_string())
+        .add_attribute("credited", applied.to_string())
+        .add_attribute("refund_due", refund_due.to_string()))
+}
+
+pub fn cancel_credit_note(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    credit_note_id: u64,
+) -> Result<Response, ContractError> {
+    let mut credit_note = CREDIT_NOTE.load(deps.storage, &credit_note_id)?;
+    if credit_note.from != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    if credit_note.status != CreditNoteStatus::Pending {
+        return Err(StdError::generic_err("Credit note is not pending").into());
+    }
+
+    credit_note.status = CreditNoteStatus::Cancelled;
+    CREDIT_NOTE.save(deps.storage, &credit_note_id, &credit_note)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "cancel_credit_note")
+        .add_attribute("credit_note_id", credit_note_id.to_string()))
+}
+
+pub fn refund_credit_note(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    credit_note_id: u64,
+) -> Result<Response, ContractError> {
+    let funds = match info.funds.as_slice() {
+        [fund] => fund,
+        _ => return Err(StdError::generic_err("Accepts only one token").into()),
+    };
+
+    execute_refund(
+        deps,
+        env,
+        info.sender,
+        Denom::Native(funds.denom.clone()),
+        funds.amount,
+        credit_note_id,
+    )
+}
+
+/// forwards the refundable balance of a credit note to the payee
+pub(crate) fn execute_refund(
+    deps: DepsMut,
+    _env: Env,
+    sender: Addr,
+    denom: Denom,
+    amount: Uint128,
+    credit_note_id: u64,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if !config
+        .accepted_assets
+        .iter()
+        .any(|asset| asset.denom == denom)
+    {
+        return Err(StdError::generic_err("Token not accepted").into());
+    }
+
+    let mut credit_note = CREDIT_NOTE.load(deps.storage, &credit_note_id)?;
+    if credit_note.status != CreditNoteStatus::Acknowledged || credit_note.refund_due.is_zero() {
+        return Err(StdError::generic_err("No refund due").into());
+    }
+    if denom_key(&denom) != credit_note.amount.denom || amount != credit_note.refund_due {
+        return Err(StdError::generic_err("Refund must match the amount due").into());
+    }
+
+    credit_note.status = CreditNoteStatus::Refunded;
+    CREDIT_NOTE.save(deps.storage, &credit_note_id, &credit_note)?;
+
+    let recipient = payout_address(deps.storage, &credit_note.payee_address)?;
+    let msg = transfer_msg(&denom, &recipient, amount)?;
+
+    Ok(Response::new()
+        .add_message(msg)
+        .add_attribute("method", "refund_credit_note")
+        .add_attribute("credit_note_id", credit_note_id.to_string())
+        .add_attribute("sender", sender)
+        .add_attribute("amount", amount.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::invoice::{accept_invoice, pay_invoice};
+    use crate::query::{get_total_payables, get_total_receivables};
+    use crate::testing::*;
+    use cosmwasm_std::testing::{mock_env, mock_info};
+    use cosmwasm_std::{coin, BankMsg, SubMsg};
+
+    fn accepted_invoice() -> (MockDeps, u64) {
+        let (mut deps, invoice_id) = setup_with_invoice(None);
+        accept_invoice(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[]),
+            invoice_id,
+            None,
+        )
+        .unwrap();
+        mock_nft_owner(&mut deps, ISSUER);
+        (deps, invoice_id)
+    }
+
+    #[test]
+    fn acknowledged_credit_reduces_amount_due() {
+        let (mut deps, invoice_id) = accepted_invoice();
+
+        let err = create_credit_note(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[]),
+            invoice_id,
+            Uint128::new(200),
+            "damaged goods".to_string(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        create_credit_note(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            invoice_id,
+            Uint128::new(200),
+            "damaged goods".to_string(),
+        )
+        .unwrap();
+        let credit_note_id = CREDIT_NOTE_ID.load(&deps.storage).unwrap();
+
+        let err = create_credit_note(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            invoice_id,
+            Uint128::new(900),
+            "everything".to_string(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            StdError::generic_err("Credit exceeds receivable").into()
+        );
+
+        //// nothing changes until the payee acknowledges ////
+        let totals = get_total_receivables(deps.as_ref(), Addr::unchecked(ISSUER), None, None)
+            .unwrap()
+            .totals;
+        assert_eq!(totals, vec![coin(1000, "uatom")]);
+
+        acknowledge_credit_note(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[]),
+            credit_note_id,
+        )
+        .unwrap();
+        let invoice = INVOICE.load(&deps.storage, invoice_id).unwrap();
+        assert_eq!(invoice.credited, Uint128::new(200));
+        assert_eq!(
+            invoice.amount_due(&mock_env().block).principal,
+            Uint128::new(800)
+        );
+        let totals = get_total_receivables(deps.as_ref(), Addr::unchecked(ISSUER), None, None)
+            .unwrap()
+            .totals;
+        assert_eq!(totals, vec![coin(800, "uatom")]);
+        let totals = get_total_payables(deps.as_ref(), Addr::unchecked(PAYEE), None, None)
+            .unwrap()
+            .totals;
+        assert_eq!(totals, vec![coin(800, "uatom")]);
+    }
+
+    #[test]
+    fn only_the_receivable_holder_issues_credit() {
+        let (mut deps, invoice_id) = accepted_invoice();
+        create_credit_note(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            invoice_id,
+            Uint128::new(200),
+            "damaged goods".to_string(),
+        )
+        .unwrap();
+        let stale_id = CREDIT_NOTE_ID.load(&deps.storage).unwrap();
+        mock_nft_owner(&mut deps, "financier");
+
+        //// credit pending at the sale cannot be acknowledged against the buyer ////
+        let err =
+            acknowledge_credit_note(deps.as_mut(), mock_env(), mock_info(PAYEE, &[]), stale_id)
+                .unwrap_err();
+        assert_eq!(
+            err,
+            StdError::generic_err("Receivable changed hands since the credit note").into()
+        );
+
+        //// the issuer sold the receivable and cannot reduce it anymore ////
+        let err = create_credit_note(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            invoice_id,
+            Uint128::new(200),
+            "damaged goods".to_string(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        create_credit_note(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("financier", &[]),
+            invoice_id,
+            Uint128::new(200),
+            "damaged goods".to_string(),
+        )
+        .unwrap();
+        let credit_note_id = CREDIT_NOTE_ID.load(&deps.storage).unwrap();
+        let credit_note = CREDIT_NOTE.load(&deps.storage, &credit_note_id).unwrap();
+        assert_eq!(credit_note.from, Addr::unchecked("financier"));
+    }
+
+    #[test]
+    fn credit_on_paid_invoice_is_refunded() {
+        let (mut deps, invoice_id) = accepted_invoice();
+        pay_invoice(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[coin(980, "uatom")]),
+            invoice_id,
+            None,
+        )
+        .unwrap();
+
+        create_credit_note(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            invoice_id,
+            Uint128::new(100),
+            "short delivery".to_string(),
+        )
+        .unwrap();
+        let credit_note_id = CREDIT_NOTE_ID.load(&deps.storage).unwrap();
+        acknowledge_credit_note(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[]),
+            credit_note_id,
+        )
+        .unwrap();
+
+        //// the refundable balance shows up on both sides ////
+        let totals = get_total_receivables(deps.as_ref(), Addr::unchecked(PAYEE), None, None)
+            .unwrap()
+            .totals;
+        assert_eq!(totals, vec![coin(100, "uatom")]);
+        let totals = get_total_payables(deps.as_ref(), Addr::unchecked(ISSUER), None, None)
+            .unwrap()
+            .totals;
+        assert_eq!(totals, vec![coin(100, "uatom")]);
+
+        let err = refund_credit_note(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[coin(50, "uatom")]),
+            credit_note_id,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            StdError::generic_err("Refund must match the amount due").into()
+        );
+
+        let res = refund_credit_note(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[coin(100, "uatom")]),
+            credit_note_id,
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: PAYEE.to_string(),
+                amount: vec![coin(100, "uatom")],
+            })]
+        );
+        let credit_note = CREDIT_NOTE.load(&deps.storage, &credit_note_id).unwrap();
+        assert_eq!(credit_note.status, CreditNoteStatus::Refunded);
+        let totals = get_total_payables(deps.as_ref(), Addr::unchecked(ISSUER), None, None)
+            .unwrap()
+            .totals;
+        assert!(totals.is_empty());
+    }
+}
@@ -0,0 +1,384 @@
+use crate::contract::dispatch;
+use crate::helpers::denom_key;
+use crate::msg::ExecuteMsg;
+use crate::state::*;
+use cosmwasm_std::{
+    Addr, BlockInfo, Coin, DepsMut, Env, MessageInfo, Response, StdError, StdResult, Storage,
+};
+use cw_utils::Expiration;
+
+use crate::error::ContractError;
+
+fn delegate_action(msg: &ExecuteMsg) -> Option<DelegateAction> {
+    match msg {
+        ExecuteMsg::AcceptInvoice { .. } => Some(DelegateAction::Accept),
+        ExecuteMsg::PayInvoice { .. } => Some(DelegateAction::Pay),
+        ExecuteMsg::CreateInvoice { .. } => Some(DelegateAction::Create),
+        _ => None,
+    }
+}
+
+pub fn set_delegation(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    delegate: String,
+    permissions: DelegatePermissions,
+    spend_limits: Vec<Coin>,
+    expires: Option<Expiration>,
+) -> Result<Response, ContractError> {
+    if !CONTACT_INFO.has(deps.storage, &info.sender) {
+        return Err(StdError::generic_err("Profile does not exist").into());
+    }
+    let delegate = deps.api.addr_validate(&delegate)?;
+    if delegate == info.sender {
+        return Err(StdError::generic_err("Cannot delegate to yourself").into());
+    }
+    let expires = expires.unwrap_or_default();
+    if expires.is_expired(&env.block) {
+        return Err(StdError::generic_err("Expiration is in the past").into());
+    }
+
+    let delegation = Delegation {
+        permissions,
+        spend_limits,
+        expires,
+    };
+    DELEGATIONS.save(deps.storage, (&info.sender, &delegate), &delegation)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "set_delegation")
+        .add_attribute("owner", info.sender)
+        .add_attribute("delegate", delegate))
+}
+
+pub fn revoke_delegation(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    delegate: Addr,
+) -> Result<Response, ContractError> {
+    if !DELEGATIONS.has(deps.storage, (&info.sender, &delegate)) {
+        return Err(StdError::generic_err("Delegation does not exist").into());
+    }
+    DELEGATIONS.remove(deps.storage, (&info.sender, &delegate));
+
+    Ok(Response::new()
+        .add_attribute("method", "revoke_delegation")
+        .add_attribute("owner", info.sender)
+        .add_attribute("delegate", delegate))
+}
+
+/// loads the delegation if it is live and covers the action
+fn active_delegation(
+    storage: &dyn Storage,
+    block: &BlockInfo,
+    owner: &Addr,
+    delegate: &Addr,
+    action: &DelegateAction,
+) -> StdResult<Delegation> {
+    let delegation = DELEGATIONS
+        .may_load(storage, (owner, delegate))?
+        .ok_or_else(|| StdError::generic_err("Delegation does not exist"))?;
+    if delegation.expires.is_expired(block) {
+        return Err(StdError::generic_err("Delegation expired"));
+    }
+    if !delegation.permissions.allows(action) {
+        return Err(StdError::generic_err(format!(
+            "Delegate cannot {:?}",
+            action
+        )));
+    }
+    Ok(delegation)
+}
+
+/// exposure a delegate payment adds for the owner, `None` if the delegate accepted the invoice
+/// and its receivable was counted then
+pub fn payment_exposure(invoice: &Invoice, delegate: &Addr, payment: Coin) -> Option<Coin> {
+    if invoice.accepted_by.as_ref() == Some(delegate) {
+        None
+    } else {
+        Some(payment)
+    }
+}
+
+/// exposure the held action took off the delegation, given back when the approval is rejected
+pub fn held_exposure(invoice: &Invoice, delegate: &Addr, action: &ApprovalAction) -> Option<Coin> {
+    match action {
+        ApprovalAction::Accept {} => Some(invoice.receivable.clone()),
+        ApprovalAction::Pay { denom, amount } => {
+            let payment = Coin {
+                denom: denom_key(denom),
+                amount: *amount,
+            };
+            payment_exposure(invoice, delegate, payment)
+        }
+    }
+}
+
+/// checks the delegate may send `msg` with `funds` for the owner, returns the delegation with
+/// the exposure taken off its spend limits
+pub fn check_delegation(
+    storage: &dyn Storage,
+    block: &BlockInfo,
+    owner: &Addr,
+    delegate: &Addr,
+    msg: &ExecuteMsg,
+    funds: &[Coin],
+) -> StdResult<Delegation> {
+    let action = delegate_action(msg)
+        .ok_or_else(|| StdError::generic_err("Message cannot be sent by a delegate"))?;
+    let mut delegation = active_delegation(storage, block, owner, delegate, &action)?;
+    match msg {
+        ExecuteMsg::AcceptInvoice { invoice_id } => {
+            let invoice = INVOICE.load(storage, *invoice_id)?;
+            delegation.spend(&invoice.receivable)?;
+        }
+        ExecuteMsg::PayInvoice { invoice_id } => {
+            let fund = match funds {
+                [fund] => fund,
+                _ => return Err(StdError::generic_err("Accepts only one token")),
+            };
+            let invoice = INVOICE.load(storage, *invoice_id)?;
+            if let Some(exposure) = payment_exposure(&invoice, delegate, fund.clone()) {
+                delegation.spend(&exposure)?;
+            }
+        }
+        _ => (),
+    }
+    Ok(delegation)
+}
+
+/// lets a delegate pay for the owner directly, e.g. with a cw20 send, `None` only checks the
+/// delegation for payments that add no exposure or whose allowance was already taken
+pub fn spend_allowance(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    owner: &Addr,
+    delegate: &Addr,
+    spend: Option<&Coin>,
+) -> StdResult<()> {
+    let mut delegation = active_delegation(storage, block, owner, delegate, &DelegateAction::Pay)?;
+    if let Some(spend) = spend {
+        delegation.spend(spend)?;
+        DELEGATIONS.save(storage, (owner, delegate), &delegation)?;
+    }
+    Ok(())
+}
+
+/// runs `msg` with the owner as sender, recording the delegate as the one acting
+pub fn execute_as_delegate(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    owner: Addr,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    let delegation = check_delegation(
+        deps.storage,
+        &env.block,
+        &owner,
+        &info.sender,
+        &msg,
+        &info.funds,
+    )?;
+    DELEGATIONS.save(deps.storage, (&owner, &info.sender), &delegation)?;
+
+    let owner_info = MessageInfo {
+        sender: owner,
+        funds: info.funds,
+    };
+    let response = dispatch(deps, env, owner_info, msg, Some(info.sender.clone()))?;
+
+    Ok(response.add_attribute("delegate", info.sender))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::execute;
+    use crate::query::{can_execute, get_delegation, get_delegations};
+    use crate::testing::*;
+    use cosmwasm_std::testing::{mock_env, mock_info};
+    use cosmwasm_std::{coin, coins, to_binary, CosmosMsg, Uint128, WasmMsg};
+
+    const BOT: &str = "bot";
+
+    fn as_bot(msg: ExecuteMsg) -> ExecuteMsg {
+        ExecuteMsg::ExecuteAsDelegate {
+            owner: Addr::unchecked(PAYEE),
+            msg: Box::new(msg),
+        }
+    }
+
+    fn can_bot_execute(deps: &MockDeps, msg: ExecuteMsg, funds: Vec<Coin>) -> bool {
+        let msg = CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: mock_env().contract.address.to_string(),
+            msg: to_binary(&msg).unwrap(),
+            funds,
+        });
+        can_execute(
+            deps.as_ref(),
+            mock_env(),
+            Addr::unchecked(PAYEE),
+            BOT.to_string(),
+            msg,
+        )
+        .unwrap()
+        .can_execute
+    }
+
+    #[test]
+    fn delegates_act_within_their_permissions_and_limits() {
+        let mut deps = setup();
+        let expires = Expiration::AtHeight(mock_env().block.height + 100);
+        let msg = ExecuteMsg::SetDelegation {
+            delegate: BOT.to_string(),
+            permissions: DelegatePermissions {
+                accept: true,
+                pay: true,
+                create: false,
+            },
+            spend_limits: coins(1600, "uatom"),
+            expires: Some(expires),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(PAYEE, &[]), msg).unwrap();
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            create_invoice_msg(),
+        )
+        .unwrap();
+        let invoice_id = INVOICE_ID.load(&deps.storage).unwrap() - 1;
+
+        //// only granted actions go through ////
+        assert!(!can_bot_execute(&deps, create_invoice_msg(), vec![]));
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(BOT, &[]),
+            as_bot(create_invoice_msg()),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Std(StdError::generic_err("Delegate cannot Create"))
+        );
+
+        //// accepting counts the receivable, paying what the bot accepted adds nothing ////
+        let accept = ExecuteMsg::AcceptInvoice { invoice_id };
+        assert!(can_bot_execute(&deps, accept.clone(), vec![]));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(BOT, &[]),
+            as_bot(accept),
+        )
+        .unwrap();
+        let invoice = INVOICE.load(&deps.storage, invoice_id).unwrap();
+        assert_eq!(invoice.status, Status::Accepted);
+        assert_eq!(invoice.accepted_by, Some(Addr::unchecked(BOT)));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(BOT, &coins(1000, "uatom")),
+            as_bot(ExecuteMsg::PayInvoice { invoice_id }),
+        )
+        .unwrap();
+        let delegation =
+            get_delegation(deps.as_ref(), Addr::unchecked(PAYEE), Addr::unchecked(BOT))
+                .unwrap()
+                .unwrap();
+        assert_eq!(delegation.spend_limits, vec![coin(600, "uatom")]);
+
+        //// payments on invoices the payee accepted are taken off the allowance ////
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            create_invoice_msg(),
+        )
+        .unwrap();
+        let invoice_id = INVOICE_ID.load(&deps.storage).unwrap() - 1;
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[]),
+            ExecuteMsg::AcceptInvoice { invoice_id },
+        )
+        .unwrap();
+        let pay = ExecuteMsg::PayInvoice { invoice_id };
+        assert!(!can_bot_execute(&deps, pay.clone(), coins(700, "uatom")));
+        assert!(can_bot_execute(&deps, pay.clone(), coins(500, "uatom")));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(BOT, &coins(500, "uatom")),
+            as_bot(pay.clone()),
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(BOT, &coins(100, "uatom")),
+            pay.clone(),
+        )
+        .unwrap();
+        let delegation =
+            get_delegation(deps.as_ref(), Addr::unchecked(PAYEE), Addr::unchecked(BOT))
+                .unwrap()
+                .unwrap();
+        assert_eq!(delegation.spend_limits, vec![coin(0, "uatom")]);
+        let invoice = INVOICE.load(&deps.storage, invoice_id).unwrap();
+        assert_eq!(invoice.amount_paid.amount, Uint128::new(600));
+
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(BOT, &coins(1, "uatom")),
+            pay.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Std(StdError::generic_err("Allowance exceeded"))
+        );
+
+        //// expired and revoked delegations are refused ////
+        let mut env = mock_env();
+        env.block.height += 100;
+        let err = execute(
+            deps.as_mut(),
+            env,
+            mock_info(BOT, &[]),
+            as_bot(ExecuteMsg::AcceptInvoice { invoice_id }),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Std(StdError::generic_err("Delegation expired"))
+        );
+
+        let msg = ExecuteMsg::RevokeDelegation {
+            delegate: Addr::unchecked(BOT),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(PAYEE, &[]), msg).unwrap();
+        assert!(
+            get_delegations(deps.as_ref(), Addr::unchecked(PAYEE), None, None)
+                .unwrap()
+                .is_empty()
+        );
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(BOT, &coins(100, "uatom")),
+            pay,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Std(StdError::generic_err("Only payee can pay the invoice"))
+        );
+    }
+}
@@ -0,0 +1,586 @@
+use crate::escrow::{holds_escrow, take_escrow};
+use crate::fee::charge_payment_fee;
+use crate::helpers::{denom_key, receivable_holder, transfer_msg};
+use crate::org::payout_address;
+use crate::state::*;
+use cosmwasm_std::{Decimal, DepsMut, Env, MessageInfo, Response, StdError, Uint128};
+
+use crate::error::ContractError;
+
+pub fn open_dispute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    invoice_id: u64,
+    evidence_uri: String,
+) -> Result<Response, ContractError> {
+    //// do not accept funds ////
+    if !info.funds.is_empty() {
+        return Err(StdError::generic_err("Funds not accepted").into());
+    }
+
+    let arbitration = ARBITRATION
+        .may_load(deps.storage)?
+        .ok_or_else(|| StdError::generic_err("Arbitration not configured"))?;
+
+    let mut invoice = INVOICE.load(deps.storage, invoice_id)?;
+    if invoice.from != info.sender && invoice.payee_address != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    //// accepted invoices can be disputed, paid ones only while the payment is held in escrow ////
+    let held = invoice.status == Status::Paid && holds_escrow(deps.storage, invoice_id)?;
+    if !matches!(invoice.status, Status::Accepted | Status::PartiallyPaid) && !held {
+        return Err(StdError::generic_err("Invoice cannot be disputed").into());
+    }
+    if invoice.dispute_id.is_some() {
+        return Err(StdError::generic_err("Invoice is under dispute").into());
+    }
+
+    let evidence_deadline = env
+        .block
+        .time
+        .plus_seconds(arbitration.evidence_days * SECONDS_PER_DAY);
+    let ruling_deadline = evidence_deadline.plus_seconds(arbitration.ruling_days * SECONDS_PER_DAY);
+
+    let dispute_id = DISPUTE_ID.may_load(deps.storage)?.unwrap_or_default() + 1;
+    let dispute = Dispute {
+        id: dispute_id,
+        invoice_id,
+        from: invoice.from.clone(),
+        payee_address: invoice.payee_address.clone(),
+        opened_by: info.sender.clone(),
+        status: DisputeStatus::Open,
+        evidence: vec![Evidence {
+            submitter: info.sender.clone(),
+            uri: evidence_uri,
+            submitted_at: env.block.time,
+        }],
+        opened_at: env.block.time,
+        evidence_deadline,
+        ruling_deadline,
+        ruling: None,
+        closed_at: None,
+    };
+    DISPUTE.save(deps.storage, dispute_id, &dispute)?;
+    DISPUTE_ID.save(deps.storage, &dispute_id)?;
+
+    invoice.dispute_id = Some(dispute_id);
+    INVOICE.save(deps.storage, invoice_id, &invoice)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "open_dispute")
+        .add_attribute("dispute_id", dispute_id.to_string())
+        .add_attribute("invoice_id", invoice_id.to_string())
+        .add_attribute("opened_by", info.sender))
+}
+
+pub fn submit_evidence(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    dispute_id: u64,
+    evidence_uri: String,
+) -> Result<Response, ContractError> {
+    let mut dispute = DISPUTE.load(deps.storage, dispute_id)?;
+    if dispute.from != info.sender && dispute.payee_address != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if dispute.status != DisputeStatus::Open {
+        return Err(StdError::generic_err("Dispute is closed").into());
+    }
+    if env.block.time > dispute.evidence_deadline {
+        return Err(StdError::generic_err("Evidence period is over").into());
+    }
+
+    dispute.evidence.push(Evidence {
+        submitter: info.sender.clone(),
+        uri: evidence_uri,
+        submitted_at: env.block.time,
+    });
+    DISPUTE.save(deps.storage, dispute_id, &dispute)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "submit_evidence")
+        .add_attribute("dispute_id", dispute_id.to_string())
+        .add_attribute("submitter", info.sender))
+}
+
+pub fn resolve_dispute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    dispute_id: u64,
+    ruling: Ruling,
+) -> Result<Response, ContractError> {
+    //// the arbitrator account or the multisig executing a passed proposal ////
+    let arbitration = ARBITRATION.load(deps.storage)?;
+    if arbitration.arbitrator.addr() != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut dispute = DISPUTE.load(deps.storage, dispute_id)?;
+    if dispute.status != DisputeStatus::Open {
+        return Err(StdError::generic_err("Dispute is closed").into());
+    }
+    if env.block.time > dispute.ruling_deadline {
+        return Err(StdError::generic_err("Ruling deadline has passed").into());
+    }
+
+    let mut invoice = INVOICE.load(deps.storage, dispute.invoice_id)?;
+    let config = CONFIG.load(deps.storage)?;
+    let holder = receivable_holder(&deps.querier, &env, &config, &invoice)?;
+
+    //// direct payments already went to the holder, there is nothing to refund or split ////
+    if invoice.settlement == SettlementMode::Direct && !matches!(ruling, Ruling::Release {}) {
+        return Err(StdError::generic_err("Nothing is held for a direct invoice").into());
+    }
+
+    let mut response = Response::new();
+    match &ruling {
+        Ruling::Release {} => {
+            //// the delivery is deemed confirmed, later payments skip the escrow ////
+            if let SettlementMode::Escrow { .. } = invoice.settlement {
+                let asset = config
+                    .accepted_assets
+                    .iter()
+                    .find(|asset| asset.matches(&invoice.receivable.denom))
+                    .ok_or_else(|| StdError::generic_err("Asset not accepted"))?;
+                let mut escrow = match ESCROW.may_load(deps.storage, &invoice.id)? {
+                    Some(escrow) => escrow,
+                    None => EscrowBalance {
+                        denom: asset.denom.clone(),
+                        amount: Uint128::zero(),
+                        delivery_confirmed: false,
+                    },
+                };
+                escrow.delivery_confirmed = true;
+                ESCROW.save(deps.storage, &invoice.id, &escrow)?;
+            }
+            let released = take_escrow(deps.storage, invoice.id, &holder, Some(&invoice.from))?;
+            if let Some(msg) = released {
+                response = response.add_message(msg);
+            }
+        }
+        Ruling::Refund {} => {
+            let refunded = take_escrow(deps.storage, invoice.id, &invoice.payee_address, None)?;
+            if let Some(msg) = refunded {
+                response = response.add_message(msg);
+            }
+            invoice.status = Status::Refunded;
+        }
+        Ruling::Split { payee_share } => {
+            if *payee_share > Decimal::one() {
+                return Err(StdError::generic_err("Payee share cannot exceed one").into());
+            }
+            if let Some(mut escrow) = ESCROW.may_load(deps.storage, &invoice.id)? {
+                let to_payee = escrow.amount * *payee_share;
+                let to_holder = escrow.amount - to_payee;
+                if !to_payee.is_zero() {
+                    let payee = payout_address(deps.storage, &invoice.payee_address)?;
+                    response = response.add_message(transfer_msg(&escrow.denom, &payee, to_payee)?);
+                }
+                //// only the holder's share was earned, so only it is charged the protocol fee ////
+                let denom = denom_key(&escrow.denom);
+                let fee = charge_payment_fee(deps.storage, &invoice.from, &denom, to_holder)?;
+                let to_holder = to_holder - fee;
+                if !to_holder.is_zero() {
+                    let holder = payout_address(deps.storage, &holder)?;
+                    response =
+                        response.add_message(transfer_msg(&escrow.denom, &holder, to_holder)?);
+                }
+                escrow.amount = Uint128::zero();
+                ESCROW.save(deps.storage, &invoice.id, &escrow)?;
+            }
+            invoice.status = Status::Settled;
+            invoice.remarks = Some(format!("Dispute {} settled by split", dispute_id));
+        }
+    }
+
+    invoice.dispute_id = None;
+    INVOICE.save(deps.storage, invoice.id, &invoice)?;
+
+    dispute.status = DisputeStatus::Resolved;
+    dispute.ruling = Some(ruling);
+    dispute.closed_at = Some(env.block.time);
+    DISPUTE.save(deps.storage, dispute_id, &dispute)?;
+
+    Ok(response
+        .add_attribute("method", "resolve_dispute")
+        .add_attribute("dispute_id", dispute_id.to_string())
+        .add_attribute("invoice_id", invoice.id.to_string()))
+}
+
+/// lifts the freeze on the invoice when the arbitrator did not rule in time, callable by anyone
+pub fn expire_dispute(
+    deps: DepsMut,
+    env: Env,
+    _info: MessageInfo,
+    dispute_id: u64,
+) -> Result<Response, ContractError> {
+    let mut dispute = DISPUTE.load(deps.storage, dispute_id)?;
+    if dispute.status != DisputeStatus::Open {
+        return Err(StdError::generic_err("Dispute is closed").into());
+    }
+    if env.block.time <= dispute.ruling_deadline {
+        return Err(StdError::generic_err("Ruling deadline not reached").into());
+    }
+
+    dispute.status = DisputeStatus::Expired;
+    dispute.closed_at = Some(env.block.time);
+    DISPUTE.save(deps.storage, dispute_id, &dispute)?;
+
+    INVOICE.update(
+        deps.storage,
+        dispute.invoice_id,
+        |invoice| -> Result<_, ContractError> {
+            let mut invoice = invoice.ok_or_else(|| StdError::not_found("Invoice"))?;
+            invoice.dispute_id = None;
+            Ok(invoice)
+        },
+    )?;
+
+    Ok(Response::new()
+        .add_attribute("method", "expire_dispute")
+        .add_attribute("dispute_id", dispute_id.to_string())
+        .add_attribute("invoice_id", dispute.invoice_id.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::execute;
+    use crate::msg::ExecuteMsg;
+    use crate::query::get_open_disputes;
+    use crate::testing::*;
+    use cosmwasm_std::testing::{mock_env, mock_info};
+    use cosmwasm_std::{coins, Addr, BankMsg, CosmosMsg};
+
+    /// escrowed invoice, accepted and half paid, with an arbitrator configured
+    fn setup_dispute() -> (MockDeps, u64) {
+        let release_at = cw_utils::Expiration::AtTime(mock_env().block.time.plus_seconds(1000));
+        setup_dispute_with(Some(SettlementMode::Escrow { release_at }))
+    }
+
+    fn setup_dispute_with(settlement: Option<SettlementMode>) -> (MockDeps, u64) {
+        let (mut deps, invoice_id) = setup_with_invoice(settlement);
+        let msgs = vec![
+            (
+                ADMIN,
+                ExecuteMsg::SetArbitration {
+                    arbitrator: Arbitrator::Address(Addr::unchecked("arbitrator")),
+                    evidence_days: 7,
+                    ruling_days: 7,
+                },
+                vec![],
+            ),
+            (PAYEE, ExecuteMsg::AcceptInvoice { invoice_id }, vec![]),
+            (
+                PAYEE,
+                ExecuteMsg::PayInvoice { invoice_id },
+                coins(500, "uatom"),
+            ),
+        ];
+        for (sender, msg, funds) in msgs {
+            execute(deps.as_mut(), mock_env(), mock_info(sender, &funds), msg).unwrap();
+        }
+        (deps, invoice_id)
+    }
+
+    #[test]
+    fn dispute_freezes_invoice_until_ruling() {
+        let (mut deps, invoice_id) = setup_dispute();
+
+        let err = open_dispute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("stranger", &[]),
+            invoice_id,
+            "ipfs://claim".to_string(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        open_dispute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[]),
+            invoice_id,
+            "ipfs://claim".to_string(),
+        )
+        .unwrap();
+        let dispute_id = DISPUTE_ID.load(&deps.storage).unwrap();
+
+        //// payments and escrow releases are frozen ////
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &coins(100, "uatom")),
+            ExecuteMsg::PayInvoice { invoice_id },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            StdError::generic_err("Invoice is under dispute").into()
+        );
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[]),
+            ExecuteMsg::ConfirmDelivery { invoice_id },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            StdError::generic_err("Invoice is under dispute").into()
+        );
+        mock_nft_owner(&mut deps, ISSUER);
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            ExecuteMsg::CreateCreditNote {
+                invoice_id,
+                amount: Uint128::new(100),
+                reason: "goodwill".to_string(),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            StdError::generic_err("Invoice is under dispute").into()
+        );
+
+        //// both sides can add evidence until the deadline ////
+        submit_evidence(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            dispute_id,
+            "ipfs://delivery-note".to_string(),
+        )
+        .unwrap();
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(8 * SECONDS_PER_DAY);
+        let err = submit_evidence(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(PAYEE, &[]),
+            dispute_id,
+            "ipfs://late".to_string(),
+        )
+        .unwrap_err();
+        assert_eq!(err, StdError::generic_err("Evidence period is over").into());
+
+        //// only the arbitrator rules ////
+        let ruling = Ruling::Split {
+            payee_share: Decimal::percent(40),
+        };
+        let err = resolve_dispute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(ISSUER, &[]),
+            dispute_id,
+            ruling.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        let res = resolve_dispute(
+            deps.as_mut(),
+            env,
+            mock_info("arbitrator", &[]),
+            dispute_id,
+            ruling,
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages
+                .into_iter()
+                .map(|msg| msg.msg)
+                .collect::<Vec<_>>(),
+            vec![
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: PAYEE.to_string(),
+                    amount: coins(200, "uatom"),
+                }),
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: ISSUER.to_string(),
+                    amount: coins(300, "uatom"),
+                }),
+            ]
+        );
+
+        let invoice = INVOICE.load(&deps.storage, invoice_id).unwrap();
+        assert_eq!(invoice.status, Status::Settled);
+        assert_eq!(invoice.dispute_id, None);
+        let dispute = DISPUTE.load(&deps.storage, dispute_id).unwrap();
+        assert_eq!(dispute.status, DisputeStatus::Resolved);
+        assert_eq!(dispute.evidence.len(), 2);
+    }
+
+    #[test]
+    fn unresolved_dispute_expires() {
+        let (mut deps, invoice_id) = setup_dispute();
+        open_dispute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            invoice_id,
+            "ipfs://claim".to_string(),
+        )
+        .unwrap();
+        let dispute_id = DISPUTE_ID.load(&deps.storage).unwrap();
+
+        let err = expire_dispute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            dispute_id,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            StdError::generic_err("Ruling deadline not reached").into()
+        );
+
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(15 * SECONDS_PER_DAY);
+        expire_dispute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            dispute_id,
+        )
+        .unwrap();
+
+        //// the arbitrator can no longer rule and payments resume ////
+        let err = resolve_dispute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("arbitrator", &[]),
+            dispute_id,
+            Ruling::Refund {},
+        )
+        .unwrap_err();
+        assert_eq!(err, StdError::generic_err("Dispute is closed").into());
+        execute(
+            deps.as_mut(),
+            env,
+            mock_info(PAYEE, &coins(100, "uatom")),
+            ExecuteMsg::PayInvoice { invoice_id },
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn direct_invoice_disputes_are_only_released() {
+        let (mut deps, invoice_id) = setup_dispute_with(None);
+        open_dispute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[]),
+            invoice_id,
+            "ipfs://claim".to_string(),
+        )
+        .unwrap();
+        let dispute_id = DISPUTE_ID.load(&deps.storage).unwrap();
+
+        //// the payments were forwarded already, a refund would only close the invoice ////
+        for ruling in [
+            Ruling::Refund {},
+            Ruling::Split {
+                payee_share: Decimal::percent(40),
+            },
+        ] {
+            let err = resolve_dispute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("arbitrator", &[]),
+                dispute_id,
+                ruling,
+            )
+            .unwrap_err();
+            assert_eq!(
+                err,
+                StdError::generic_err("Nothing is held for a direct invoice").into()
+            );
+        }
+        let disputes =
+            get_open_disputes(deps.as_ref(), Addr::unchecked(PAYEE), None, None).unwrap();
+        assert_eq!(disputes.len(), 1);
+        let disputes = get_open_disputes(
+            deps.as_ref(),
+            Addr::unchecked(ISSUER),
+            Some(dispute_id),
+            None,
+        )
+        .unwrap();
+        assert!(disputes.is_empty());
+
+        resolve_dispute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("arbitrator", &[]),
+            dispute_id,
+            Ruling::Release {},
+        )
+        .unwrap();
+        let invoice = INVOICE.load(&deps.storage, invoice_id).unwrap();
+        assert_eq!(invoice.status, Status::PartiallyPaid);
+        let disputes =
+            get_open_disputes(deps.as_ref(), Addr::unchecked(ISSUER), None, None).unwrap();
+        assert!(disputes.is_empty());
+    }
+
+    #[test]
+    fn paid_escrow_is_disputed_and_not_voided() {
+        let (mut deps, invoice_id) = setup_dispute();
+        //// the rest is paid within the discount window ////
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &coins(480, "uatom")),
+            ExecuteMsg::PayInvoice { invoice_id },
+        )
+        .unwrap();
+        open_dispute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[]),
+            invoice_id,
+            "ipfs://claim".to_string(),
+        )
+        .unwrap();
+        let dispute_id = DISPUTE_ID.load(&deps.storage).unwrap();
+
+        let void = ExecuteMsg::VoidInvoice {
+            invoice_id,
+            reason: "duplicate".to_string(),
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info(ADMIN, &[]), void).unwrap_err();
+        assert_eq!(
+            err,
+            StdError::generic_err("Invoice is under dispute").into()
+        );
+
+        let res = resolve_dispute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("arbitrator", &[]),
+            dispute_id,
+            Ruling::Refund {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: PAYEE.to_string(),
+                amount: coins(980, "uatom"),
+            })
+        );
+        let invoice = INVOICE.load(&deps.storage, invoice_id).unwrap();
+        assert_eq!(invoice.status, Status::Refunded);
+    }
+}
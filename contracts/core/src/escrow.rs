@@ -0,0 +1,401 @@
+use crate::fee::charge_payment_fee;
+use crate::helpers::{denom_key, query_nft_owner, receivable_holder, transfer_msg};
+use crate::invoice::burn_invoice_nft_msg;
+use crate::org::payout_address;
+use crate::state::*;
+use cosmwasm_std::{
+    Addr, CosmosMsg, DepsMut, Env, MessageInfo, Response, StdError, StdResult, Storage, Uint128,
+};
+use cw20::Denom;
+
+use crate::error::ContractError;
+
+/// holds a payment in escrow for the invoice until it is released or refunded
+pub fn escrow_payment(
+    storage: &mut dyn Storage,
+    invoice_id: u64,
+    denom: &Denom,
+    amount: Uint128,
+) -> StdResult<()> {
+    ESCROW.update(storage, &invoice_id, |escrow| -> StdResult<_> {
+        let mut escrow = escrow.unwrap_or(EscrowBalance {
+            denom: denom.clone(),
+            amount: Uint128::zero(),
+            delivery_confirmed: false,
+        });
+        escrow.amount += amount;
+        Ok(escrow)
+    })?;
+    Ok(())
+}
+
+/// returns true while payments for the invoice are held and its delivery is not yet confirmed
+pub fn holds_escrow(storage: &dyn Storage, invoice_id: u64) -> StdResult<bool> {
+    Ok(ESCROW
+        .may_load(storage, &invoice_id)?
+        .map(|escrow| !escrow.amount.is_zero() && !escrow.delivery_confirmed)
+        .unwrap_or_default())
+}
+
+/// empties the escrow of the invoice and returns the message paying it out to the recipient,
+/// or to its payout address for orgs. Releases pass the issuer, who bears the protocol fee,
+/// refunds pass `None` and return the full amount
+pub fn take_escrow(
+    storage: &mut dyn Storage,
+    invoice_id: u64,
+    recipient: &Addr,
+    fee_payer: Option<&Addr>,
+) -> StdResult<Option<CosmosMsg>> {
+    let mut escrow = match ESCROW.may_load(storage, &invoice_id)? {
+        Some(escrow) if !escrow.amount.is_zero() => escrow,
+        _ => return Ok(None),
+    };
+    let fee = match fee_payer {
+        Some(issuer) => {
+            charge_payment_fee(storage, issuer, &denom_key(&escrow.denom), escrow.amount)?
+        }
+        None => Uint128::zero(),
+    };
+    let recipient = payout_address(storage, recipient)?;
+    let msg = transfer_msg(&escrow.denom, &recipient, escrow.amount - fee)?;
+    escrow.amount = Uint128::zero();
+    ESCROW.save(storage, &invoice_id, &escrow)?;
+    Ok(Some(msg))
+}
+
+pub fn confirm_delivery(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    invoice_id: u64,
+) -> Result<Response, ContractError> {
+    let invoice = INVOICE.load(deps.storage, invoice_id)?;
+    invoice.ensure_not_disputed()?;
+
+    //// only the payee can confirm the delivery ////
+    if invoice.payee_address != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if invoice.settlement == SettlementMode::Direct {
+        return Err(StdError::generic_err("Invoice is not settled through escrow").into());
+    }
+
+    let config = CONFIG.load(deps.storage)?;
+    let mut escrow = match ESCROW.may_load(deps.storage, &invoice_id)? {
+        Some(escrow) => escrow,
+        None => {
+            let asset = config
+                .accepted_assets
+                .iter()
+                .find(|asset| asset.matches(&invoice.receivable.denom))
+                .ok_or_else(|| StdError::generic_err("Asset not accepted"))?;
+            EscrowBalance {
+                denom: asset.denom.clone(),
+                amount: Uint128::zero(),
+                delivery_confirmed: false,
+            }
+        }
+    };
+    if escrow.delivery_confirmed {
+        return Err(StdError::generic_err("Delivery already confirmed").into());
+    }
+    escrow.delivery_confirmed = true;
+    ESCROW.save(deps.storage, &invoice_id, &escrow)?;
+
+    //// release whatever was paid so far, later payments go straight to the holder ////
+    let recipient = receivable_holder(&deps.querier, &env, &config, &invoice)?;
+    let mut response = Response::new();
+    if let Some(msg) = take_escrow(deps.storage, invoice_id, &recipient, Some(&invoice.from))? {
+        response = response.add_message(msg);
+    }
+
+    Ok(response
+        .add_attribute("method", "confirm_delivery")
+        .add_attribute("invoice_id", invoice_id.to_string())
+        .add_attribute("released", escrow.amount.to_string()))
+}
+
+pub fn release_escrow(
+    deps: DepsMut,
+    env: Env,
+    _info: MessageInfo,
+    invoice_id: u64,
+) -> Result<Response, ContractError> {
+    let invoice = INVOICE.load(deps.storage, invoice_id)?;
+    invoice.ensure_not_disputed()?;
+
+    //// anyone can release the funds once the release deadline has passed ////
+    match invoice.settlement {
+        SettlementMode::Escrow { release_at } if release_at.is_expired(&env.block) => {}
+        SettlementMode::Escrow { .. } => {
+            return Err(StdError::generic_err("Escrow release deadline not reached").into())
+        }
+        SettlementMode::Direct => {
+            return Err(StdError::generic_err("Invoice is not settled through escrow").into())
+        }
+    }
+
+    let config = CONFIG.load(deps.storage)?;
+    let recipient = receivable_holder(&deps.querier, &env, &config, &invoice)?;
+    let msg = take_escrow(deps.storage, invoice_id, &recipient, Some(&invoice.from))?
+        .ok_or_else(|| StdError::generic_err("Nothing to release"))?;
+
+    Ok(Response::new()
+        .add_message(msg)
+        .add_attribute("method", "release_escrow")
+        .add_attribute("invoice_id", invoice_id.to_string()))
+}
+
+pub fn refund_escrow(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    invoice_id: u64,
+) -> Result<Response, ContractError> {
+    let mut invoice = INVOICE.load(deps.storage, invoice_id)?;
+    invoice.ensure_not_disputed()?;
+    //// a fully paid invoice can be refunded as long as its payment is still held ////
+    let held = invoice.status == Status::Paid && holds_escrow(deps.storage, invoice_id)?;
+    if invoice.status.is_closed() && !held {
+        return Err(StdError::generic_err("Invoice is closed").into());
+    }
+
+    //// the receivable holder or an admin can hand the escrowed funds back to the payee ////
+    let config = CONFIG.load(deps.storage)?;
+    let holder = receivable_holder(&deps.querier, &env, &config, &invoice)?;
+    let admin_list = ADMIN_LIST.load(deps.storage)?;
+    if holder != info.sender && !admin_list.is_admin(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let msg = take_escrow(deps.storage, invoice_id, &invoice.payee_address, None)?
+        .ok_or_else(|| StdError::generic_err("Nothing to refund"))?;
+
+    invoice.status = Status::Refunded;
+    INVOICE.save(deps.storage, invoice_id, &invoice)?;
+
+    //// the refund ends the receivable, the nft is burnt if the contract still holds it ////
+    let mut response = Response::new().add_message(msg);
+    if query_nft_owner(&deps.querier, &config, invoice_id)? == env.contract.address {
+        response = response.add_message(burn_invoice_nft_msg(&config, invoice_id)?);
+    }
+
+    Ok(response
+        .add_attribute("method", "refund_escrow")
+        .add_attribute("invoice_id", invoice_id.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::invoice::{accept_invoice, pay_invoice};
+    use crate::testing::*;
+    use cosmwasm_std::testing::{mock_env, mock_info};
+    use cosmwasm_std::{coin, BankMsg, SubMsg};
+    use cw_utils::Expiration;
+
+    fn setup_escrow() -> (MockDeps, u64) {
+        let release_at = mock_env().block.time.plus_seconds(60 * SECONDS_PER_DAY);
+        let (mut deps, invoice_id) = setup_with_invoice(Some(SettlementMode::Escrow {
+            release_at: Expiration::AtTime(release_at),
+        }));
+        accept_invoice(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[]),
+            invoice_id,
+            None,
+        )
+        .unwrap();
+        (deps, invoice_id)
+    }
+
+    #[test]
+    fn payments_are_held_until_delivery() {
+        let (mut deps, invoice_id) = setup_escrow();
+
+        let res = pay_invoice(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[coin(400, "uatom")]),
+            invoice_id,
+            None,
+        )
+        .unwrap();
+        assert!(res.messages.is_empty());
+        let escrow = ESCROW.load(&deps.storage, &invoice_id).unwrap();
+        assert_eq!(escrow.amount, Uint128::new(400));
+
+        //// funds cannot be released before the deadline ////
+        let err = release_escrow(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            invoice_id,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            StdError::generic_err("Escrow release deadline not reached").into()
+        );
+
+        let err = confirm_delivery(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            invoice_id,
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        let res =
+            confirm_delivery(deps.as_mut(), mock_env(), mock_info(PAYEE, &[]), invoice_id).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: ISSUER.to_string(),
+                amount: vec![coin(400, "uatom")],
+            })]
+        );
+
+        //// once delivered, payments go straight to the issuer ////
+        let res = pay_invoice(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[coin(100, "uatom")]),
+            invoice_id,
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: ISSUER.to_string(),
+                amount: vec![coin(100, "uatom")],
+            })]
+        );
+    }
+
+    #[test]
+    fn escrow_is_released_after_deadline_or_refunded() {
+        let (mut deps, invoice_id) = setup_escrow();
+        pay_invoice(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[coin(400, "uatom")]),
+            invoice_id,
+            None,
+        )
+        .unwrap();
+
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(61 * SECONDS_PER_DAY);
+        let res = release_escrow(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            invoice_id,
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), 1);
+
+        let err =
+            refund_escrow(deps.as_mut(), env, mock_info(ISSUER, &[]), invoice_id).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Nothing to refund").into());
+    }
+
+    #[test]
+    fn receivable_holder_refunds_escrow() {
+        let (mut deps, invoice_id) = setup_escrow();
+        pay_invoice(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[coin(400, "uatom")]),
+            invoice_id,
+            None,
+        )
+        .unwrap();
+
+        let err = refund_escrow(deps.as_mut(), mock_env(), mock_info(PAYEE, &[]), invoice_id)
+            .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        //// once the nft is sold, the issuer can no longer refund the buyer's funds ////
+        mock_nft_owner(&mut deps, "financier");
+        let err = refund_escrow(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            invoice_id,
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        let res = refund_escrow(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("financier", &[]),
+            invoice_id,
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: PAYEE.to_string(),
+                amount: vec![coin(400, "uatom")],
+            })]
+        );
+        let invoice = INVOICE.load(&deps.storage, invoice_id).unwrap();
+        assert_eq!(invoice.status, Status::Refunded);
+
+        let err = refund_escrow(deps.as_mut(), mock_env(), mock_info(ADMIN, &[]), invoice_id)
+            .unwrap_err();
+        assert_eq!(err, StdError::generic_err("Invoice is closed").into());
+    }
+
+    #[test]
+    fn fully_paid_escrow_is_refunded_until_delivery() {
+        let (mut deps, invoice_id) = setup_escrow();
+        //// paid in full within the discount window ////
+        pay_invoice(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[coin(980, "uatom")]),
+            invoice_id,
+            None,
+        )
+        .unwrap();
+        let invoice = INVOICE.load(&deps.storage, invoice_id).unwrap();
+        assert_eq!(invoice.status, Status::Paid);
+
+        //// the holder has not approved the burn, the admin refund still goes through ////
+        mock_nft_owner(&mut deps, ISSUER);
+        let res =
+            refund_escrow(deps.as_mut(), mock_env(), mock_info(ADMIN, &[]), invoice_id).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: PAYEE.to_string(),
+                amount: vec![coin(980, "uatom")],
+            })]
+        );
+        let invoice = INVOICE.load(&deps.storage, invoice_id).unwrap();
+        assert_eq!(invoice.status, Status::Refunded);
+
+        //// a confirmed delivery settles the invoice for good ////
+        let (mut deps, invoice_id) = setup_escrow();
+        pay_invoice(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[coin(980, "uatom")]),
+            invoice_id,
+            None,
+        )
+        .unwrap();
+        confirm_delivery(deps.as_mut(), mock_env(), mock_info(PAYEE, &[]), invoice_id).unwrap();
+        let err = refund_escrow(deps.as_mut(), mock_env(), mock_info(ADMIN, &[]), invoice_id)
+            .unwrap_err();
+        assert_eq!(err, StdError::generic_err("Invoice is closed").into());
+    }
+}
@@ -0,0 +1,424 @@
+use crate::contract::map_validate;
+use crate::helpers::{denom_key, transfer_msg};
+use crate::state::*;
+use cosmwasm_std::{
+    Addr, Coin, DepsMut, Empty, Env, MessageInfo, Response, StdError, StdResult, Storage, Uint128,
+};
+use cw20::Denom;
+
+use crate::error::ContractError;
+
+pub fn set_fee_config(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    payment_fee_bps: u16,
+    creation_fee: Option<Coin>,
+    caps: Vec<Coin>,
+) -> Result<Response, ContractError> {
+    let admin_list = ADMIN_LIST.load(deps.storage)?;
+    if !admin_list.is_admin(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if payment_fee_bps > MAX_FEE_BPS {
+        return Err(StdError::generic_err("Payment fee cannot exceed 100%").into());
+    }
+    let creation_fee = creation_fee.filter(|fee| !fee.amount.is_zero());
+    FEE_CONFIG.save(
+        deps.storage,
+        &FeeConfig {
+            payment_fee_bps,
+            creation_fee,
+            caps,
+        },
+    )?;
+
+    Ok(Response::new()
+        .add_attribute("method", "set_fee_config")
+        .add_attribute("payment_fee_bps", payment_fee_bps.to_string()))
+}
+
+pub fn add_fee_exemptions(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    addresses: Vec<String>,
+) -> Result<Response, ContractError> {
+    let admin_list = ADMIN_LIST.load(deps.storage)?;
+    if !admin_list.is_admin(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    for address in map_validate(deps.api, &addresses)? {
+        FEE_EXEMPT.save(deps.storage, &address, &Empty {})?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "add_fee_exemptions")
+        .add_attribute("addresses", addresses.join(",")))
+}
+
+pub fn remove_fee_exemptions(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    addresses: Vec<String>,
+) -> Result<Response, ContractError> {
+    let admin_list = ADMIN_LIST.load(deps.storage)?;
+    if !admin_list.is_admin(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    for address in map_validate(deps.api, &addresses)? {
+        FEE_EXEMPT.remove(deps.storage, &address);
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "remove_fee_exemptions")
+        .add_attribute("addresses", addresses.join(",")))
+}
+
+pub fn withdraw_fees(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    denom: Denom,
+    amount: Option<Uint128>,
+    recipient: Option<String>,
+) -> Result<Response, ContractError> {
+    let admin_list = ADMIN_LIST.load(deps.storage)?;
+    if !admin_list.is_admin(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    //// withdraws the whole balance unless an amount is given ////
+    let key = denom_key(&denom);
+    let balance = TREASURY.may_load(deps.storage, &key)?.unwrap_or_default();
+    let amount = amount.unwrap_or(balance);
+    if amount.is_zero() {
+        return Err(StdError::generic_err("Nothing to withdraw").into());
+    }
+    if amount > balance {
+        return Err(StdError::generic_err("Amount exceeds collected fees").into());
+    }
+    TREASURY.save(deps.storage, &key, &(balance - amount))?;
+
+    let recipient = match recipient {
+        Some(recipient) => deps.api.addr_validate(&recipient)?,
+        None => info.sender,
+    };
+
+    Ok(Response::new()
+        .add_message(transfer_msg(&denom, &recipient, amount)?)
+        .add_attribute("method", "withdraw_fees")
+        .add_attribute("denom", key)
+        .add_attribute("amount", amount.to_string())
+        .add_attribute("recipient", recipient))
+}
+
+fn credit_treasury(storage: &mut dyn Storage, denom: &str, amount: Uint128) -> StdResult<()> {
+    TREASURY.update(storage, denom, |balance| -> StdResult<_> {
+        Ok(balance.unwrap_or_default() + amount)
+    })?;
+    Ok(())
+}
+
+/// checks the funds sent cover the creation fee of `count` invoices exactly and collects it,
+/// a `None` count is refused while a fee is due as it cannot be prepaid
+pub fn charge_creation_fee(
+    storage: &mut dyn Storage,
+    info: &MessageInfo,
+    count: Option<u64>,
+) -> StdResult<Option<Coin>> {
+    let fee = FEE_CONFIG
+        .may_load(storage)?
+        .and_then(|config| config.creation_fee)
+        .filter(|_| !FEE_EXEMPT.has(storage, &info.sender));
+
+    let fee = match (fee, count) {
+        (Some(fee), Some(count)) => Coin {
+            denom: fee.denom,
+            amount: fee.amount.checked_mul(Uint128::from(count))?,
+        },
+        (Some(_), None) => {
+            return Err(StdError::generic_err(
+                "Invoice count required to prepay the creation fee",
+            ))
+        }
+        (None, _) if info.funds.is_empty() => return Ok(None),
+        (None, _) => return Err(StdError::generic_err("Funds not accepted")),
+    };
+    if info.funds != [fee.clone()] {
+        return Err(StdError::generic_err(format!(
+            "Creation fee of {} required",
+            fee
+        )));
+    }
+
+    credit_treasury(storage, &fee.denom, fee.amount)?;
+    Ok(Some(fee))
+}
+
+/// collects the protocol fee on a payment to the invoice, returns the fee kept
+pub fn charge_payment_fee(
+    storage: &mut dyn Storage,
+    issuer: &Addr,
+    denom: &str,
+    amount: Uint128,
+) -> StdResult<Uint128> {
+    //// the issuer bears the fee, so exempt issuers receive the full payment ////
+    if FEE_EXEMPT.has(storage, issuer) {
+        return Ok(Uint128::zero());
+    }
+    let fee = FEE_CONFIG
+        .may_load(storage)?
+        .unwrap_or_default()
+        .payment_fee(denom, amount);
+
+    if !fee.is_zero() {
+        credit_treasury(storage, denom, fee)?;
+    }
+    Ok(fee)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::execute;
+    use crate::msg::ExecuteMsg;
+    use crate::query::{get_asset_fees, get_treasury};
+    use crate::testing::*;
+    use cosmwasm_std::testing::{mock_env, mock_info};
+    use cosmwasm_std::{coin, coins, BankMsg, CosmosMsg};
+    use cw_utils::Expiration;
+
+    fn set_fees(deps: &mut MockDeps, payment_fee_bps: u16, creation_fee: Option<Coin>) {
+        let msg = ExecuteMsg::SetFeeConfig {
+            payment_fee_bps,
+            creation_fee,
+            caps: coins(8, "uatom"),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(ADMIN, &[]), msg).unwrap();
+    }
+
+    fn exempt(deps: &mut MockDeps, address: &str) {
+        let msg = ExecuteMsg::AddFeeExemptions {
+            addresses: vec![address.to_string()],
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(ADMIN, &[]), msg).unwrap();
+    }
+
+    fn forwarded(res: &Response) -> CosmosMsg {
+        res.messages[0].msg.clone()
+    }
+
+    #[test]
+    fn payment_fees_are_capped_collected_and_withdrawn() {
+        let mut deps = setup();
+        set_fees(&mut deps, 200, None);
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            create_invoice_msg(),
+        )
+        .unwrap();
+        let invoice_id = INVOICE_ID.load(&deps.storage).unwrap() - 1;
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[]),
+            ExecuteMsg::AcceptInvoice { invoice_id },
+        )
+        .unwrap();
+
+        //// 2% of 500 is capped at 8 ////
+        let pay = ExecuteMsg::PayInvoice { invoice_id };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &coins(500, "uatom")),
+            pay.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            forwarded(&res),
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: ISSUER.to_string(),
+                amount: coins(492, "uatom"),
+            })
+        );
+        assert!(res
+            .attributes
+            .iter()
+            .any(|attr| attr.key == "protocol_fee" && attr.value == "8"));
+        let invoice = INVOICE.load(&deps.storage, invoice_id).unwrap();
+        assert_eq!(invoice.amount_paid.amount, Uint128::new(500));
+        let records = INVOICE_PAYMENTS.load(&deps.storage, &invoice_id).unwrap();
+        assert_eq!(records[0].net(), Uint128::new(492));
+
+        //// exempt issuers receive the full payment ////
+        exempt(&mut deps, ISSUER);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &coins(500, "uatom")),
+            pay,
+        )
+        .unwrap();
+        assert_eq!(
+            forwarded(&res),
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: ISSUER.to_string(),
+                amount: coins(500, "uatom"),
+            })
+        );
+        assert_eq!(
+            get_treasury(deps.as_ref(), None, None).unwrap(),
+            coins(8, "uatom")
+        );
+
+        let withdraw = ExecuteMsg::WithdrawFees {
+            denom: Denom::Native("uatom".to_string()),
+            amount: None,
+            recipient: Some("treasury".to_string()),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            withdraw.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        let res = execute(deps.as_mut(), mock_env(), mock_info(ADMIN, &[]), withdraw).unwrap();
+        assert_eq!(
+            forwarded(&res),
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: coins(8, "uatom"),
+            })
+        );
+        let fees = get_asset_fees(deps.as_ref(), "uatom".to_string()).unwrap();
+        assert_eq!(fees.collected, Uint128::zero());
+        assert_eq!(fees.cap, Some(Uint128::new(8)));
+    }
+
+    fn escrowed_payment() -> (MockDeps, u64) {
+        let release_at = mock_env().block.time.plus_seconds(60 * SECONDS_PER_DAY);
+        let (mut deps, invoice_id) = setup_with_invoice(Some(SettlementMode::Escrow {
+            release_at: Expiration::AtTime(release_at),
+        }));
+        set_fees(&mut deps, 200, None);
+        mock_nft_owner(&mut deps, ISSUER);
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[]),
+            ExecuteMsg::AcceptInvoice { invoice_id },
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &coins(500, "uatom")),
+            ExecuteMsg::PayInvoice { invoice_id },
+        )
+        .unwrap();
+        (deps, invoice_id)
+    }
+
+    #[test]
+    fn escrowed_payments_are_charged_on_release() {
+        //// a refund hands back the full payment ////
+        let (mut deps, invoice_id) = escrowed_payment();
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ADMIN, &[]),
+            ExecuteMsg::RefundEscrow { invoice_id },
+        )
+        .unwrap();
+        assert_eq!(
+            forwarded(&res),
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: PAYEE.to_string(),
+                amount: coins(500, "uatom"),
+            })
+        );
+        assert!(get_treasury(deps.as_ref(), None, None).unwrap().is_empty());
+
+        //// the fee is taken when the funds reach the issuer ////
+        let (mut deps, invoice_id) = escrowed_payment();
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[]),
+            ExecuteMsg::ConfirmDelivery { invoice_id },
+        )
+        .unwrap();
+        assert_eq!(
+            forwarded(&res),
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: ISSUER.to_string(),
+                amount: coins(492, "uatom"),
+            })
+        );
+        assert_eq!(
+            get_treasury(deps.as_ref(), None, None).unwrap(),
+            coins(8, "uatom")
+        );
+    }
+
+    #[test]
+    fn creation_fee_is_required_unless_exempt() {
+        let mut deps = setup();
+        set_fees(&mut deps, 0, Some(coin(10, "uatom")));
+
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            create_invoice_msg(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Std(StdError::generic_err("Creation fee of 10uatom required"))
+        );
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &coins(10, "uatom")),
+            create_invoice_msg(),
+        )
+        .unwrap();
+        assert!(res
+            .attributes
+            .iter()
+            .any(|attr| attr.key == "creation_fee" && attr.value == "10uatom"));
+        let fees = get_asset_fees(deps.as_ref(), "uatom".to_string()).unwrap();
+        assert_eq!(fees.collected, Uint128::new(10));
+
+        exempt(&mut deps, ISSUER);
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &coins(10, "uatom")),
+            create_invoice_msg(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Std(StdError::generic_err("Funds not accepted"))
+        );
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            create_invoice_msg(),
+        )
+        .unwrap();
+    }
+}
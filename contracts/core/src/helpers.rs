@@ -0,0 +1,80 @@
+use crate::state::{Config, Invoice};
+use cosmwasm_std::{
+    to_binary, Addr, BankMsg, Binary, Coin, CosmosMsg, Env, QuerierWrapper, StdResult, Uint128,
+    WasmMsg,
+};
+use cw20::{Cw20ExecuteMsg, Denom};
+use cw721::{Cw721QueryMsg, OwnerOfResponse};
+use sha2::{Digest, Sha256};
+
+/// builds the message that moves `amount` of a native or cw20 asset held by the contract
+pub fn transfer_msg(denom: &Denom, recipient: &Addr, amount: Uint128) -> StdResult<CosmosMsg> {
+    let msg = match denom {
+        Denom::Native(denom) => CosmosMsg::Bank(BankMsg::Send {
+            to_address: recipient.to_string(),
+            amount: vec![Coin {
+                denom: denom.clone(),
+                amount,
+            }],
+        }),
+        Denom::Cw20(address) => CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: address.to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: recipient.to_string(),
+                amount,
+            })?,
+            funds: vec![],
+        }),
+    };
+    Ok(msg)
+}
+
+/// returns the string used as `Coin.denom` for invoices in this asset
+pub fn denom_key(denom: &Denom) -> String {
+    match denom {
+        Denom::Native(denom) => denom.clone(),
+        Denom::Cw20(address) => address.to_string(),
+    }
+}
+
+/// returns the current owner of the invoice NFT
+pub fn query_nft_owner(
+    querier: &QuerierWrapper,
+    config: &Config,
+    invoice_id: u64,
+) -> StdResult<Addr> {
+    let res: OwnerOfResponse = querier.query_wasm_smart(
+        config.nft_address.to_string(),
+        &Cw721QueryMsg::OwnerOf {
+            token_id: invoice_id.to_string(),
+            include_expired: None,
+        },
+    )?;
+    Ok(Addr::unchecked(res.owner))
+}
+
+/// returns who is entitled to payments on the invoice: the holder of its NFT,
+/// or the issuer while the NFT is still held by this contract
+pub fn receivable_holder(
+    querier: &QuerierWrapper,
+    env: &Env,
+    config: &Config,
+    invoice: &Invoice,
+) -> StdResult<Addr> {
+    let owner = query_nft_owner(querier, config, invoice.id)?;
+    if owner == env.contract.address {
+        Ok(invoice.from.clone())
+    } else {
+        Ok(owner)
+    }
+}
+
+/// commitment to a PII value, as stored by private profiles, the salt is length prefixed so
+/// no other split of the same bytes into salt and value matches
+pub fn pii_commitment(salt: &str, value: &str) -> Binary {
+    let mut hasher = Sha256::new();
+    hasher.update((salt.len() as u64).to_be_bytes());
+    hasher.update(salt.as_bytes());
+    hasher.update(value.as_bytes());
+    Binary(hasher.finalize().to_vec())
+}
@@ -1,9 +1,19 @@
-use crate::msg::{InstantiateMsg, QueryMsg};
+use crate::approval::hold_for_approval;
+use crate::credit_note;
+use crate::delegation::{payment_exposure, spend_allowance};
+use crate::escrow::{escrow_payment, take_escrow};
+use crate::fee::{charge_creation_fee, charge_payment_fee};
+use crate::helpers::{denom_key, query_nft_owner, receivable_holder, transfer_msg};
+use crate::kyc::ensure_kyc;
+use crate::msg::{InstantiateMsg, QueryMsg, ReceiveMsg};
+use crate::org::{has_org_role, payout_address};
+use crate::profile::ensure_not_blocked;
 use crate::state::*;
 use cosmwasm_std::{
-    entry_point, to_binary, Addr, Api, BankMsg, Binary, Coin, CosmosMsg, Deps, DepsMut, Empty, Env,
-    MessageInfo, Response, StdError, Uint128, WasmMsg,
+    entry_point, from_binary, to_binary, Addr, Binary, Coin, CosmosMsg, Decimal, Deps, DepsMut,
+    Empty, Env, MessageInfo, Response, StdError, StdResult, Timestamp, Uint128, WasmMsg,
 };
+use cw20::{Cw20ReceiveMsg, Denom};
 use cw721_base::msg::{ExecuteMsg, MintMsg};
 
 use crate::error::ContractError;
@@ -12,19 +22,94 @@ pub fn create_invoice(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    payee_address: Addr,
-    receivable: Coin,
-    amount_paid: Coin,
-    service_type: ServiceType,
-    doc_uri: String,
+    params: InvoiceParams,
+    acting: Option<Addr>,
 ) -> Result<Response, ContractError> {
-    //// do not accept funds ////
-    if !info.funds.is_empty() {
-        return Err(StdError::generic_err("Funds not accepted").into());
+    //// no funds are accepted besides the creation fee ////
+    let creation_fee = charge_creation_fee(deps.storage, &info, Some(1))?;
+
+    let mut response = issue_invoice(deps, env, info.sender, params, acting)?;
+    if let Some(fee) = creation_fee {
+        response = response.add_attribute("creation_fee", fee.to_string());
+    }
+    Ok(response)
+}
+
+/// issues the invoice for `issuer` and mints its nft, fees are charged by the callers
+pub fn issue_invoice(
+    deps: DepsMut,
+    env: Env,
+    issuer: Addr,
+    params: InvoiceParams,
+    created_by: Option<Addr>,
+) -> Result<Response, ContractError> {
+    let InvoiceParams {
+        payee_address,
+        receivable,
+        amount_paid,
+        service_type,
+        doc_uri,
+        due_date,
+        payment_terms,
+        settlement,
+        installments,
+        line_items,
+    } = params;
+
+    //// due date must be in the future ////
+    if due_date <= env.block.time {
+        return Err(StdError::generic_err("Due date must be in the future").into());
+    }
+
+    //// fall back to the terms and asset the issuer set for this contact ////
+    let contact = CONTACTS.may_load(deps.storage, (&issuer, &payee_address))?;
+    let payment_terms = match payment_terms {
+        Some(terms) => Some(terms),
+        None => contact
+            .as_ref()
+            .and_then(|contact| contact.default_terms.clone()),
+    };
+    if let Some(terms) = &payment_terms {
+        validate_payment_terms(terms, env.block.time, due_date)?;
+    }
+
+    let mut receivable = receivable;
+    let mut amount_paid = amount_paid;
+    if receivable.denom.is_empty() {
+        if let Some(denom) = contact.and_then(|contact| contact.default_asset) {
+            receivable.denom = denom_key(&denom);
+            if amount_paid.denom.is_empty() {
+                amount_paid.denom = receivable.denom.clone();
+            }
+        }
+    }
+
+    //// the receivable is the total of the line items, taxes included ////
+    let line_items = line_items.unwrap_or_default();
+    let summary = TaxSummary::from_line_items(&line_items);
+    if !line_items.is_empty() {
+        validate_line_items(&line_items)?;
+        if receivable.amount.is_zero() {
+            receivable.amount = summary.total;
+        } else if receivable.amount != summary.total {
+            return Err(StdError::generic_err("Receivable does not match line items").into());
+        }
+    }
+
+    let installments = installments.unwrap_or_default();
+    if !installments.is_empty() {
+        validate_installments(&installments, env.block.time, due_date, receivable.amount)?;
+    }
+
+    let settlement = settlement.unwrap_or_default();
+    if let SettlementMode::Escrow { release_at } = settlement {
+        if release_at.is_expired(&env.block) {
+            return Err(StdError::generic_err("Escrow release already expired").into());
+        }
     }
 
     //// Address cannot be sender////
-    if info.sender == payee_address {
+    if issuer == payee_address {
         return Err(StdError::generic_err("Receiver and Sender cannot be same").into());
     }
 
@@ -39,32 +124,48 @@ pub fn create_invoice(
 
     let config = CONFIG.load(deps.storage)?;
  
-    if !config.accepted_assets.iter().any(|asset| asset.denom == receivable.denom) {
+    if !config
+        .accepted_assets
+        .iter()
+        .any(|asset| asset.matches(&receivable.denom))
+    {
         return Err(StdError::generic_err("Asset not accepted").into());
     }
 
         // Load and validate sender's contact info
-        let mut sender_contact_info = CONTACT_INFO.may_load(deps.storage, &info.sender)?
+    let sender_contact_info = CONTACT_INFO
+        .may_load(deps.storage, &issuer)?
         .ok_or_else(|| StdError::generic_err("Profile does not exist"))?;
 
- 
-    if sender_contact_info.kyc_status != KYCStatus::Approved {
-        return Err(StdError::generic_err("Creator KYC not verified").into());
-    }
+    sender_contact_info.ensure_active()?;
+
+    let kyc_policy = KYC_POLICY.may_load(deps.storage)?.unwrap_or_default();
+    ensure_kyc(
+        &sender_contact_info,
+        &env.block,
+        &kyc_policy.issuer,
+        "Creator",
+    )?;
+
+    ensure_not_blocked(deps.storage, &issuer, &payee_address)?;
 
     //// if address doesnt exists in contact _info.contact throw error
-    let contacts = sender_contact_info.contacts.clone();
-    if !contacts.contains(&payee_address) {
+    if !CONTACTS.has(deps.storage, (&issuer, &payee_address)) {
         return Err(StdError::generic_err("Receiver not in contact list").into());
     }
     
     //// Check if counter party is verified or not
-    let mut receiver_contact_info = CONTACT_INFO.may_load(deps.storage, &payee_address)?
-    .ok_or_else(|| StdError::generic_err("CounterParty Profile does not exist"))?;
+    let receiver_contact_info = CONTACT_INFO
+        .may_load(deps.storage, &payee_address)?
+        .ok_or_else(|| StdError::generic_err("CounterParty Profile does not exist"))?;
 
-    if receiver_contact_info.kyc_status == KYCStatus::Unverified {
-        return Err(StdError::generic_err("CounterParty KYC not verified").into());
-    }
+    receiver_contact_info.ensure_active()?;
+    ensure_kyc(
+        &receiver_contact_info,
+        &env.block,
+        &kyc_policy.payer,
+        "CounterParty",
+    )?;
 
     let due_amount=Coin {
         denom: receivable.denom.clone(),
@@ -74,7 +175,7 @@ pub fn create_invoice(
     let invoice_id = get_invoice_id(deps.as_ref());
     let invoice = Invoice {
         id: invoice_id,
-        from: info.sender.clone(),
+        from: issuer.clone(),
         payee_address: payee_address.clone(),
         nft_id: invoice_id ,
         doc_uri: doc_uri.clone(),
@@ -86,25 +187,37 @@ pub fn create_invoice(
         },
         service_type: service_type.clone(),
         status: Status::Raised,
+        remarks: None,
+        due_date: Some(due_date),
+        issued_at: Some(env.block.time),
+        accepted_at: None,
+        paid_at: None,
+        payment_terms,
+        discount_applied: Uint128::zero(),
+        penalty_paid: Uint128::zero(),
+        settlement,
+        installments,
+        line_items: line_items.clone(),
+        credited: Uint128::zero(),
+        dispute_id: None,
+        created_by,
+        accepted_by: None,
     };
 
-    INVOICE.save(deps.storage, &invoice_id, &invoice)?;
-
-    sender_contact_info.generated_invoices.push(invoice_id);
-    CONTACT_INFO.save(deps.storage, &info.sender, &sender_contact_info)?;
-
-    ///// updated assigned invoice list
-    receiver_contact_info.assigned_invoices.push(invoice_id);
-    CONTACT_INFO.save(deps.storage, &payee_address, &receiver_contact_info)?;
+    INVOICE.save(deps.storage, invoice_id, &invoice)?;
 
     let metadata = Metadata {
         invoice_id: invoice_id,
-        from: info.sender.clone(),
+        from: issuer.clone(),
         payee_address: payee_address.clone(),
         receivable: receivable.clone(),
         uri: doc_uri.clone(),
+        line_items,
+        subtotal: summary.subtotal,
+        tax_total: summary.tax_total,
     };
 
+    //// the contract holds the nft until the payee accepts the invoice ////
     let mint_msg = MintMsg {
         token_id: invoice_id.to_string(),
         owner: env.contract.address.to_string(),
@@ -129,9 +242,10 @@ pub fn create_invoice(
 
 pub fn pay_invoice(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     invoice_id: u64,
+    acting: Option<Addr>,
 ) -> Result<Response, ContractError> {
     // Validate that exactly one type of token is sent
     let funds = match info.funds.as_slice() {
@@ -139,89 +253,251 @@ pub fn pay_invoice(
         _ => return Err(StdError::generic_err("Accepts only one token").into()),
     };
 
+    let payer = Payer {
+        address: info.sender,
+        member: acting,
+    };
+    execute_payment(
+        deps,
+        env,
+        payer,
+        Denom::Native(funds.denom.clone()),
+        funds.amount,
+        invoice_id,
+        false,
+    )
+}
 
-    //// iterate config accepted asset to check if receivable denom is accepted ////
+pub fn receive_cw20(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    wrapper: Cw20ReceiveMsg,
+) -> Result<Response, ContractError> {
+    //// the cw20 contract is the sender, the payer is wrapped inside ////
+    let payer = deps.api.addr_validate(&wrapper.sender)?;
+    match from_binary(&wrapper.msg)? {
+        ReceiveMsg::PayInvoice { invoice_id } => execute_payment(
+            deps,
+            env,
+            Payer {
+                address: payer,
+                member: None,
+            },
+            Denom::Cw20(info.sender),
+            wrapper.amount,
+            invoice_id,
+            false,
+        ),
+        ReceiveMsg::RefundCreditNote { credit_note_id } => credit_note::execute_refund(
+            deps,
+            env,
+            payer,
+            Denom::Cw20(info.sender),
+            wrapper.amount,
+            credit_note_id,
+        ),
+    }
+}
+
+/// who pays an invoice, `member` is the org member or delegate sending the payment for `address`
+pub(crate) struct Payer {
+    pub address: Addr,
+    pub member: Option<Addr>,
+}
 
+pub(crate) fn execute_payment(
+    deps: DepsMut,
+    env: Env,
+    payer: Payer,
+    payment_denom: Denom,
+    amount: Uint128,
+    invoice_id: u64,
+    approved: bool,
+) -> Result<Response, ContractError> {
+    //// iterate config accepted asset to check if receivable denom is accepted ////
     let config = CONFIG.load(deps.storage)?;
-    if !config.accepted_assets.iter().any(|asset| asset.denom == funds.denom) {
+    if !config
+        .accepted_assets
+        .iter()
+        .any(|asset| asset.denom == payment_denom)
+    {
         return Err(StdError::generic_err("Token not accepted").into());
     }
 
-    let mut invoice = INVOICE.load(deps.storage, &invoice_id)?;
+    let mut invoice = INVOICE.load(deps.storage, invoice_id)?;
 
-    if invoice.payee_address != info.sender {
-        return Err(StdError::generic_err("Receiver and Sender cannot be same").into());
+    //// members with the payer role pay directly for their org, e.g. with cw20 sends ////
+    let Payer {
+        address: payer,
+        member,
+    } = payer;
+    let (payer, member) = if invoice.payee_address == payer {
+        (payer, member)
+    } else if has_org_role(
+        deps.as_ref(),
+        &invoice.payee_address,
+        &payer,
+        &OrgRole::Payer,
+    )? {
+        (invoice.payee_address.clone(), Some(payer))
+    } else if DELEGATIONS.has(deps.storage, (&invoice.payee_address, &payer)) {
+        //// delegates pay from their own funds against the payee's allowance ////
+        let payment = Coin {
+            denom: denom_key(&payment_denom),
+            amount,
+        };
+        let spend = if approved {
+            None
+        } else {
+            payment_exposure(&invoice, &payer, payment)
+        };
+        spend_allowance(
+            deps.storage,
+            &env.block,
+            &invoice.payee_address,
+            &payer,
+            spend.as_ref(),
+        )?;
+        (invoice.payee_address.clone(), Some(payer))
+    } else {
+        return Err(StdError::generic_err("Only payee can pay the invoice").into());
+    };
+
+    let kyc_policy = KYC_POLICY.may_load(deps.storage)?.unwrap_or_default();
+    if kyc_policy.payer != KycRequirement::None {
+        let payer_contact_info = CONTACT_INFO
+            .may_load(deps.storage, &payer)?
+            .ok_or_else(|| StdError::generic_err("Payer Profile does not exist"))?;
+        ensure_kyc(&payer_contact_info, &env.block, &kyc_policy.payer, "Payer")?;
     }
 
     // Check the invoice status
     match invoice.status {
         Status::Raised => return Err(StdError::generic_err("Invoice not yet accepted").into()),
         Status::Paid => return Err(StdError::generic_err("Invoice already paid").into()),
+        Status::Rejected
+        | Status::Cancelled
+        | Status::Voided
+        | Status::Refunded
+        | Status::Settled => return Err(StdError::generic_err("Invoice is closed").into()),
         _ => (),
     }
+    invoice.ensure_not_disputed()?;
 
-    let amount = info.funds[0].amount;
-    let denom = info.funds[0].denom.clone();
-
-    let amount_paid = invoice.amount_paid.amount.clone();
-    let receivable = invoice.receivable.amount.clone();
+    let denom = denom_key(&payment_denom);
 
-    if amount_paid + amount > receivable {
-        return Err(StdError::generic_err("Amount paid exceeds receivable").into());
+    if denom != invoice.receivable.denom {
+        return Err(StdError::generic_err("Denom does not match receivable").into());
     }
 
-    let mut response: Response<Empty> =
-        Response::new().add_attribute("invoice_id", invoice_id.to_string());
-    if amount_paid + amount == receivable {
-        invoice.amount_paid.amount = invoice.amount_paid.amount + info.funds[0].amount.clone();
-        invoice.status = Status::Paid;
-        invoice.due_amount.amount = Uint128::zero();
-        INVOICE.save(deps.storage, &invoice_id, &invoice)?;
+    //// effective amount due at this block, including discount and late fee ////
+    let due = invoice.amount_due(&env.block);
+    if amount > due.total.amount {
+        return Err(StdError::generic_err("Amount paid exceeds amount due").into());
+    }
 
-        //// transfer nft to owner ////
-        let msg: ExecuteMsg<Empty, Empty> = ExecuteMsg::TransferNft {
-            recipient: invoice.from.to_string(),
-            token_id: invoice_id.to_string(),
+    //// high value payments are held until the payee's multisig approves them ////
+    if !approved {
+        let action = ApprovalAction::Pay {
+            denom: payment_denom.clone(),
+            amount,
         };
+        let requested_by = member.clone().unwrap_or_else(|| payer.clone());
+        if let Some(response) =
+            hold_for_approval(deps.storage, &env, &invoice, action, requested_by)?
+        {
+            return Ok(response);
+        }
+    }
 
-        let message: CosmosMsg<Empty> = CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: config.nft_address.into_string(),
-            msg: to_binary(&msg)?,
-            funds: vec![],
-        });
+    //// payments settle the late fee first, then the principal ////
+    let penalty = amount.min(due.penalty);
+    let mut principal = amount - penalty;
+    let mut discount = Uint128::zero();
+    if amount == due.total.amount {
+        //// discount is only granted when the invoice is settled in full ////
+        principal = due.principal - due.discount;
+        discount = due.discount;
+    }
 
+    invoice.amount_paid.amount += principal;
+    invoice.discount_applied += discount;
+    invoice.penalty_paid += penalty;
+    invoice.apply_to_installments(principal + discount);
 
-        response = response.add_message(message);
+    let mut response: Response<Empty> =
+        Response::new().add_attribute("invoice_id", invoice_id.to_string());
+    if amount == due.total.amount {
+        invoice.status = Status::Paid;
+        invoice.due_amount.amount = Uint128::zero();
+        invoice.paid_at = Some(env.block.time);
+        INVOICE.save(deps.storage, invoice_id, &invoice)?;
     } else {
-        invoice.amount_paid.amount = invoice.amount_paid.amount + info.funds[0].amount.clone();
-        invoice.due_amount.amount = receivable - (amount_paid + amount);
+        invoice.due_amount.amount = due.principal - principal;
         invoice.status = Status::PartiallyPaid;
-        INVOICE.save(deps.storage, &invoice_id, &invoice)?;
+        INVOICE.save(deps.storage, invoice_id, &invoice)?;
     }
 
-    let bank_msg: CosmosMsg<Empty> = CosmosMsg::Bank(BankMsg::Send {
-        to_address: invoice.from.to_string(),
-        amount: vec![Coin {
-            denom: denom.clone(),
-            amount: amount,
-        }],
-    });
+    //// forward the payment to the receivable holder unless it is held in escrow ////
+    let delivery_confirmed = ESCROW
+        .may_load(deps.storage, &invoice_id)?
+        .map(|escrow| escrow.delivery_confirmed)
+        .unwrap_or_default();
+    let (recipient, fee) = if invoice.settlement != SettlementMode::Direct && !delivery_confirmed {
+        //// the protocol fee waits for the release, refunds return the full payment ////
+        escrow_payment(deps.storage, invoice_id, &payment_denom, amount)?;
+        response = response.add_attribute("escrowed", amount.to_string());
+        (None, Uint128::zero())
+    } else {
+        let fee = charge_payment_fee(deps.storage, &invoice.from, &denom, amount)?;
+        let holder = receivable_holder(&deps.querier, &env, &config, &invoice)?;
+        let recipient = payout_address(deps.storage, &holder)?;
+        response = response
+            .add_message(transfer_msg(&payment_denom, &recipient, amount - fee)?)
+            .add_attribute("recipient", recipient.clone());
+        (Some(recipient), fee)
+    };
 
-    response = response.add_message(bank_msg);
+    //// record how the payment was applied ////
+    let record = PaymentRecord {
+        payer,
+        amount: Coin {
+            denom: denom.clone(),
+            amount,
+        },
+        principal,
+        discount,
+        penalty,
+        paid_at: env.block.time,
+        recipient,
+        member,
+        protocol_fee: fee,
+    };
+    INVOICE_PAYMENTS.update(deps.storage, &invoice_id, |records| -> StdResult<_> {
+        let mut records = records.unwrap_or_default();
+        records.push(record);
+        Ok(records)
+    })?;
 
     Ok(response
         .add_attribute("method", "pay_invoice")
         .add_attribute("amount", amount.to_string())
-        .add_attribute("denom", denom))
+        .add_attribute("denom", denom)
+        .add_attribute("principal", principal.to_string())
+        .add_attribute("discount", discount.to_string())
+        .add_attribute("penalty", penalty.to_string())
+        .add_attribute("protocol_fee", fee.to_string()))
 }
 
 pub fn accept_invoice(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     invoice_id: u64,
+    acting: Option<Addr>,
 ) -> Result<Response, ContractError> {
-    let mut invoice = INVOICE.load(deps.storage, &invoice_id)?;
+    let invoice = INVOICE.load(deps.storage, invoice_id)?;
 
     if invoice.payee_address != info.sender {
         return Err(ContractError::Unauthorized {});
@@ -231,10 +507,845 @@ pub fn accept_invoice(
         return Err(StdError::generic_err("Invoice already accepted").into());
     }
 
+    //// high value invoices wait for the payee's multisig ////
+    let requested_by = acting.clone().unwrap_or(info.sender);
+    if let Some(response) = hold_for_approval(
+        deps.storage,
+        &env,
+        &invoice,
+        ApprovalAction::Accept {},
+        requested_by,
+    )? {
+        return Ok(response);
+    }
+
+    complete_acceptance(deps, env, invoice, acting)
+}
+
+pub(crate) fn complete_acceptance(
+    deps: DepsMut,
+    env: Env,
+    mut invoice: Invoice,
+    accepted_by: Option<Addr>,
+) -> Result<Response, ContractError> {
+    let invoice_id = invoice.id;
+    if invoice.status != Status::Raised {
+        return Err(StdError::generic_err("Invoice already accepted").into());
+    }
+
     invoice.status = Status::Accepted;
-    INVOICE.save(deps.storage, &invoice_id, &invoice)?;
+    invoice.accepted_at = Some(env.block.time);
+    invoice.accepted_by = accepted_by;
+    INVOICE.save(deps.storage, invoice_id, &invoice)?;
+
+    //// once accepted, the issuer holds the nft as title to the receivable ////
+    let config = CONFIG.load(deps.storage)?;
+    let msg: ExecuteMsg<Empty, Empty> = ExecuteMsg::TransferNft {
+        recipient: payout_address(deps.storage, &invoice.from)?.to_string(),
+        token_id: invoice_id.to_string(),
+    };
+    let message: CosmosMsg = CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: config.nft_address.into_string(),
+        msg: to_binary(&msg)?,
+        funds: vec![],
+    });
 
     Ok(Response::new()
+        .add_message(message)
         .add_attribute("method", "accept_invoice")
         .add_attribute("invoice_id", invoice_id.to_string()))
 }
+
+pub fn reject_invoice(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    invoice_id: u64,
+    reason: String,
+) -> Result<Response, ContractError> {
+    let mut invoice = INVOICE.load(deps.storage, invoice_id)?;
+
+    //// only the payee can reject an invoice ////
+    if invoice.payee_address != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if invoice.status != Status::Raised {
+        return Err(StdError::generic_err("Only raised invoices can be rejected").into());
+    }
+
+    invoice.status = Status::Rejected;
+    invoice.remarks = Some(reason.clone());
+    INVOICE.save(deps.storage, invoice_id, &invoice)?;
+
+    let config = CONFIG.load(deps.storage)?;
+    Ok(Response::new()
+        .add_message(burn_invoice_nft_msg(&config, invoice_id)?)
+        .add_attribute("method", "reject_invoice")
+        .add_attribute("invoice_id", invoice_id.to_string())
+        .add_attribute("reason", reason))
+}
+
+pub fn cancel_invoice(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    invoice_id: u64,
+) -> Result<Response, ContractError> {
+    let mut invoice = INVOICE.load(deps.storage, invoice_id)?;
+
+    //// only the issuer can cancel an invoice ////
+    if invoice.from != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if invoice.status != Status::Raised {
+        return Err(StdError::generic_err("Invoice already accepted").into());
+    }
+
+    invoice.status = Status::Cancelled;
+    INVOICE.save(deps.storage, invoice_id, &invoice)?;
+
+    let config = CONFIG.load(deps.storage)?;
+    Ok(Response::new()
+        .add_message(burn_invoice_nft_msg(&config, invoice_id)?)
+        .add_attribute("method", "cancel_invoice")
+        .add_attribute("invoice_id", invoice_id.to_string()))
+}
+
+pub fn void_invoice(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    invoice_id: u64,
+    reason: String,
+) -> Result<Response, ContractError> {
+    //// only admins can void an invoice ////
+    let admin_list = ADMIN_LIST.load(deps.storage)?;
+    if !admin_list.is_admin(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut invoice = INVOICE.load(deps.storage, invoice_id)?;
+    //// a disputed invoice is left to the arbitrator ////
+    invoice.ensure_not_disputed()?;
+    if invoice.status.is_closed() {
+        return Err(StdError::generic_err("Invoice is closed").into());
+    }
+
+    invoice.status = Status::Voided;
+    invoice.remarks = Some(reason.clone());
+    INVOICE.save(deps.storage, invoice_id, &invoice)?;
+
+    //// hand back anything still held in escrow ////
+    let mut response = Response::new();
+    if let Some(msg) = take_escrow(deps.storage, invoice_id, &invoice.payee_address, None)? {
+        response = response.add_message(msg);
+    }
+
+    //// the nft can only be burnt while the contract still holds it ////
+    let config = CONFIG.load(deps.storage)?;
+    if query_nft_owner(&deps.querier, &config, invoice_id)? == env.contract.address {
+        response = response.add_message(burn_invoice_nft_msg(&config, invoice_id)?);
+    }
+
+    Ok(response
+        .add_attribute("method", "void_invoice")
+        .add_attribute("invoice_id", invoice_id.to_string())
+        .add_attribute("reason", reason))
+}
+
+pub(crate) fn validate_payment_terms(
+    terms: &PaymentTerms,
+    issued_at: Timestamp,
+    due_date: Timestamp,
+) -> Result<(), ContractError> {
+    if let Some(rate) = terms.discount_rate {
+        if rate >= Decimal::one() {
+            return Err(StdError::generic_err("Discount rate must be less than 1").into());
+        }
+        //// discount window has to close before the invoice is due ////
+        if issued_at.plus_seconds(terms.discount_days * SECONDS_PER_DAY) > due_date {
+            return Err(StdError::generic_err("Discount window exceeds due date").into());
+        }
+    }
+    if let Some(LateFee::Rate(rate)) = terms.late_fee {
+        if rate > Decimal::one() {
+            return Err(StdError::generic_err("Late fee rate cannot exceed 1").into());
+        }
+    }
+    Ok(())
+}
+
+fn validate_line_items(line_items: &[LineItem]) -> Result<(), ContractError> {
+    for item in line_items {
+        if item.description.is_empty() {
+            return Err(StdError::generic_err("Line item description is required").into());
+        }
+        if item.quantity.is_zero() || item.net_amount().is_zero() {
+            return Err(StdError::generic_err("Line item amount cannot be zero").into());
+        }
+        if item.tax_rate > Decimal::one() {
+            return Err(StdError::generic_err("Tax rate cannot exceed 1").into());
+        }
+    }
+    Ok(())
+}
+
+fn validate_installments(
+    installments: &[Installment],
+    issued_at: Timestamp,
+    due_date: Timestamp,
+    receivable: Uint128,
+) -> Result<(), ContractError> {
+    let mut previous = issued_at;
+    let mut total = Uint128::zero();
+    for installment in installments {
+        if installment.amount.is_zero() || !installment.paid.is_zero() {
+            return Err(StdError::generic_err("Invalid installment amount").into());
+        }
+        //// installments fall due one after another, no later than the invoice ////
+        if installment.due_date <= previous || installment.due_date > due_date {
+            return Err(StdError::generic_err("Invalid installment due date").into());
+        }
+        previous = installment.due_date;
+        total += installment.amount;
+    }
+    if total != receivable {
+        return Err(StdError::generic_err("Installments must add up to the receivable").into());
+    }
+    Ok(())
+}
+
+pub(crate) fn burn_invoice_nft_msg(config: &Config, invoice_id: u64) -> StdResult<CosmosMsg> {
+    let msg: ExecuteMsg<Empty, Empty> = ExecuteMsg::Burn {
+        token_id: invoice_id.to_string(),
+    };
+
+    Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: config.nft_address.to_string(),
+        msg: to_binary(&msg)?,
+        funds: vec![],
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::execute;
+    use crate::msg::ExecuteMsg as CoreExecuteMsg;
+    use crate::query::{
+        get_installments, get_invoices, get_pending_invoices, get_tax_summary, RequestResponse,
+    };
+    use crate::testing::*;
+    use cosmwasm_std::coin;
+    use cosmwasm_std::testing::{mock_env, mock_info};
+
+    #[test]
+    fn payee_rejects_raised_invoice() {
+        let (mut deps, invoice_id) = setup_with_invoice(None);
+
+        let err = reject_invoice(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            invoice_id,
+            "wrong amount".to_string(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        let res = reject_invoice(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[]),
+            invoice_id,
+            "wrong amount".to_string(),
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), 1);
+
+        let invoice = INVOICE.load(&deps.storage, invoice_id).unwrap();
+        assert_eq!(invoice.status, Status::Rejected);
+        assert_eq!(invoice.remarks, Some("wrong amount".to_string()));
+
+        //// rejected invoices can no longer be paid ////
+        let err = pay_invoice(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[coin(1000, "uatom")]),
+            invoice_id,
+            None,
+        )
+        .unwrap_err();
+        assert_eq!(err, StdError::generic_err("Invoice is closed").into());
+
+        let receivables =
+            crate::query::get_total_receivables(deps.as_ref(), Addr::unchecked(ISSUER), None, None)
+                .unwrap()
+                .totals;
+        assert!(receivables.is_empty());
+    }
+
+    #[test]
+    fn issuer_cancels_only_before_acceptance() {
+        let (mut deps, invoice_id) = setup_with_invoice(None);
+
+        accept_invoice(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[]),
+            invoice_id,
+            None,
+        )
+        .unwrap();
+        let err = cancel_invoice(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            invoice_id,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            StdError::generic_err("Invoice already accepted").into()
+        );
+
+        //// admins can still void an accepted invoice ////
+        let err = void_invoice(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            invoice_id,
+            "fraud".to_string(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        void_invoice(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ADMIN, &[]),
+            invoice_id,
+            "fraud".to_string(),
+        )
+        .unwrap();
+        let invoice = INVOICE.load(&deps.storage, invoice_id).unwrap();
+        assert_eq!(invoice.status, Status::Voided);
+
+        let payables =
+            crate::query::get_total_payables(deps.as_ref(), Addr::unchecked(PAYEE), None, None)
+                .unwrap()
+                .totals;
+        assert!(payables.is_empty());
+    }
+
+    #[test]
+    fn overdue_invoices_are_aged() {
+        let (mut deps, invoice_id) = setup_with_invoice(None);
+        accept_invoice(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[]),
+            invoice_id,
+            None,
+        )
+        .unwrap();
+        pay_invoice(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[coin(400, "uatom")]),
+            invoice_id,
+            None,
+        )
+        .unwrap();
+
+        let issuer = Addr::unchecked(ISSUER);
+        let report =
+            crate::query::get_aging_report(deps.as_ref(), mock_env(), issuer, None, None).unwrap();
+        assert_eq!(report.receivables.current, vec![coin(600, "uatom")]);
+
+        //// 75 days after issuing, the invoice is 45 days past due ////
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(75 * SECONDS_PER_DAY);
+        let invoice = crate::query::get_invoice(deps.as_ref(), env.clone(), invoice_id).unwrap();
+        assert_eq!(invoice.status, Status::Overdue);
+
+        let payee = Addr::unchecked(PAYEE);
+        let report = crate::query::get_aging_report(deps.as_ref(), env, payee, None, None).unwrap();
+        assert!(report.payables.current.is_empty());
+        assert_eq!(report.payables.days_31_60, vec![coin(600, "uatom")]);
+        assert_eq!(report.last_invoice_id, None);
+    }
+
+    #[test]
+    fn totals_are_summed_page_by_page() {
+        let (mut deps, _) = setup_with_invoice(None);
+        let mut ids = vec![];
+        for _ in 0..2 {
+            execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(ISSUER, &[]),
+                create_invoice_msg(),
+            )
+            .unwrap();
+            ids.push(INVOICE_ID.load(&deps.storage).unwrap() - 1);
+        }
+
+        let issuer = Addr::unchecked(ISSUER);
+        let page =
+            crate::query::get_total_receivables(deps.as_ref(), issuer.clone(), None, Some(2))
+                .unwrap();
+        assert_eq!(page.totals, vec![coin(2000, "uatom")]);
+        assert_eq!(page.last_invoice_id, Some(ids[0]));
+
+        let page = crate::query::get_total_receivables(
+            deps.as_ref(),
+            issuer,
+            page.last_invoice_id,
+            Some(2),
+        )
+        .unwrap();
+        assert_eq!(page.totals, vec![coin(1000, "uatom")]);
+        assert_eq!(page.last_invoice_id, None);
+    }
+
+    #[test]
+    fn early_payment_earns_discount() {
+        let (mut deps, invoice_id) = setup_with_invoice(None);
+        accept_invoice(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[]),
+            invoice_id,
+            None,
+        )
+        .unwrap();
+
+        //// 2% off when settled within 10 days ////
+        let due = crate::query::get_amount_due(deps.as_ref(), mock_env(), invoice_id).unwrap();
+        assert_eq!(due.discount, Uint128::new(20));
+        assert_eq!(due.total, coin(980, "uatom"));
+
+        let err = pay_invoice(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[coin(1000, "uatom")]),
+            invoice_id,
+            None,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            StdError::generic_err("Amount paid exceeds amount due").into()
+        );
+
+        pay_invoice(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[coin(980, "uatom")]),
+            invoice_id,
+            None,
+        )
+        .unwrap();
+        let invoice = INVOICE.load(&deps.storage, invoice_id).unwrap();
+        assert_eq!(invoice.status, Status::Paid);
+        assert_eq!(invoice.discount_applied, Uint128::new(20));
+
+        let records = crate::query::get_invoice_payments(deps.as_ref(), invoice_id).unwrap();
+        assert_eq!(records[0].principal, Uint128::new(980));
+        assert_eq!(records[0].discount, Uint128::new(20));
+    }
+
+    #[test]
+    fn late_payment_pays_penalty_first() {
+        let (mut deps, invoice_id) = setup_with_invoice(None);
+        accept_invoice(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[]),
+            invoice_id,
+            None,
+        )
+        .unwrap();
+
+        //// still within the grace period ////
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(33 * SECONDS_PER_DAY);
+        let due = crate::query::get_amount_due(deps.as_ref(), env.clone(), invoice_id).unwrap();
+        assert_eq!(due.total, coin(1000, "uatom"));
+
+        //// 5% late fee after the grace period ////
+        env.block.time = env.block.time.plus_seconds(3 * SECONDS_PER_DAY);
+        let due = crate::query::get_amount_due(deps.as_ref(), env.clone(), invoice_id).unwrap();
+        assert_eq!(due.penalty, Uint128::new(50));
+        assert_eq!(due.total, coin(1050, "uatom"));
+
+        let res = pay_invoice(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(PAYEE, &[coin(100, "uatom")]),
+            invoice_id,
+            None,
+        )
+        .unwrap();
+        assert!(res
+            .attributes
+            .iter()
+            .any(|attr| attr.key == "penalty" && attr.value == "50"));
+
+        let due = crate::query::get_amount_due(deps.as_ref(), env, invoice_id).unwrap();
+        assert_eq!(due.penalty, Uint128::zero());
+        assert_eq!(due.total, coin(950, "uatom"));
+    }
+
+    #[test]
+    fn pay_invoice_with_cw20() {
+        let (mut deps, _) = setup_with_invoice(None);
+        let mut config = CONFIG.load(&deps.storage).unwrap();
+        config.accepted_assets.push(Asset {
+            name: "token".to_string(),
+            denom: Denom::Cw20(Addr::unchecked("token")),
+            decimal: 6,
+            uri: None,
+        });
+        CONFIG.save(&mut deps.storage, &config).unwrap();
+
+        let msg = CoreExecuteMsg::CreateInvoice {
+            payee_address: Addr::unchecked(PAYEE),
+            receivable: coin(500, "token"),
+            amount_paid: coin(0, "token"),
+            service_type: ServiceType::Goods,
+            doc_uri: "ipfs://invoice".to_string(),
+            due_date: mock_env().block.time.plus_seconds(30 * SECONDS_PER_DAY),
+            payment_terms: None,
+            settlement: None,
+            installments: None,
+            line_items: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(ISSUER, &[]), msg).unwrap();
+        let invoice_id = INVOICE_ID.load(&deps.storage).unwrap() - 1;
+        accept_invoice(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[]),
+            invoice_id,
+            None,
+        )
+        .unwrap();
+
+        let wrapper = Cw20ReceiveMsg {
+            sender: PAYEE.to_string(),
+            amount: Uint128::new(500),
+            msg: to_binary(&ReceiveMsg::PayInvoice { invoice_id }).unwrap(),
+        };
+
+        //// tokens from an unknown cw20 contract are refused ////
+        let err = receive_cw20(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("fake", &[]),
+            wrapper.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, StdError::generic_err("Token not accepted").into());
+
+        let res =
+            receive_cw20(deps.as_mut(), mock_env(), mock_info("token", &[]), wrapper).unwrap();
+        let transfer = transfer_msg(
+            &Denom::Cw20(Addr::unchecked("token")),
+            &Addr::unchecked(ISSUER),
+            Uint128::new(500),
+        )
+        .unwrap();
+        assert_eq!(res.messages.last().unwrap().msg, transfer);
+
+        let invoice = INVOICE.load(&deps.storage, invoice_id).unwrap();
+        assert_eq!(invoice.status, Status::Paid);
+    }
+
+    #[test]
+    fn payments_follow_the_invoice_nft() {
+        let (mut deps, invoice_id) = setup_with_invoice(None);
+
+        //// accepting hands the nft from the contract to the issuer ////
+        let res = accept_invoice(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[]),
+            invoice_id,
+            None,
+        )
+        .unwrap();
+        let transfer: ExecuteMsg<Empty, Empty> = ExecuteMsg::TransferNft {
+            recipient: ISSUER.to_string(),
+            token_id: invoice_id.to_string(),
+        };
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "nft".to_string(),
+                msg: to_binary(&transfer).unwrap(),
+                funds: vec![],
+            })
+        );
+
+        //// the issuer sold the receivable to a financier ////
+        mock_nft_owner(&mut deps, "financier");
+        let res = pay_invoice(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[coin(500, "uatom")]),
+            invoice_id,
+            None,
+        )
+        .unwrap();
+        let payment = transfer_msg(
+            &Denom::Native("uatom".to_string()),
+            &Addr::unchecked("financier"),
+            Uint128::new(500),
+        )
+        .unwrap();
+        assert_eq!(res.messages[0].msg, payment);
+    }
+
+    #[test]
+    fn installments_are_paid_in_order() {
+        let mut deps = setup();
+        let day = |days: u64| mock_env().block.time.plus_seconds(days * SECONDS_PER_DAY);
+        let installment = |amount: u128, due_date: Timestamp| Installment {
+            amount: Uint128::new(amount),
+            due_date,
+            paid: Uint128::zero(),
+        };
+        let create = |installments: Vec<Installment>| CoreExecuteMsg::CreateInvoice {
+            payee_address: Addr::unchecked(PAYEE),
+            receivable: coin(1000, "uatom"),
+            amount_paid: coin(0, "uatom"),
+            service_type: ServiceType::Service,
+            doc_uri: "ipfs://invoice".to_string(),
+            due_date: day(30),
+            payment_terms: None,
+            settlement: None,
+            installments: Some(installments),
+            line_items: None,
+        };
+
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            create(vec![installment(300, day(10)), installment(600, day(30))]),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            StdError::generic_err("Installments must add up to the receivable").into()
+        );
+
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            create(vec![
+                installment(300, day(10)),
+                installment(300, day(20)),
+                installment(400, day(30)),
+            ]),
+        )
+        .unwrap();
+        let invoice_id = INVOICE_ID.load(&deps.storage).unwrap() - 1;
+        accept_invoice(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[]),
+            invoice_id,
+            None,
+        )
+        .unwrap();
+        pay_invoice(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[coin(400, "uatom")]),
+            invoice_id,
+            None,
+        )
+        .unwrap();
+
+        //// the second installment is short by 100 once its due date passes ////
+        let mut env = mock_env();
+        env.block.time = day(21);
+        let schedule = get_installments(deps.as_ref(), env, invoice_id).unwrap();
+        let flags: Vec<_> = schedule
+            .iter()
+            .map(|i| (i.paid.u128(), i.is_paid, i.is_overdue))
+            .collect();
+        assert_eq!(
+            flags,
+            vec![(300, true, false), (100, false, true), (0, false, false)]
+        );
+    }
+
+    #[test]
+    fn receivable_is_derived_from_line_items() {
+        let mut deps = setup();
+        let item =
+            |description: &str, quantity: u64, unit_price: u128, tax_percent: u64| LineItem {
+                description: description.to_string(),
+                quantity: Decimal::from_ratio(quantity, 1u64),
+                unit_price: Uint128::new(unit_price),
+                tax_rate: Decimal::percent(tax_percent),
+                tax_jurisdiction: Some("DE".to_string()),
+            };
+        let line_items = vec![
+            item("consulting", 10, 50, 19),
+            item("travel", 1, 200, 19),
+            item("books", 2, 100, 7),
+        ];
+        let create = |receivable: u128| CoreExecuteMsg::CreateInvoice {
+            payee_address: Addr::unchecked(PAYEE),
+            receivable: coin(receivable, "uatom"),
+            amount_paid: coin(0, "uatom"),
+            service_type: ServiceType::Service,
+            doc_uri: "ipfs://invoice".to_string(),
+            due_date: mock_env().block.time.plus_seconds(30 * SECONDS_PER_DAY),
+            payment_terms: None,
+            settlement: None,
+            installments: None,
+            line_items: Some(line_items.clone()),
+        };
+
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            create(900),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            StdError::generic_err("Receivable does not match line items").into()
+        );
+
+        //// 700 at 19% and 200 at 7% ////
+        let res = execute(deps.as_mut(), mock_env(), mock_info(ISSUER, &[]), create(0)).unwrap();
+        let invoice_id = INVOICE_ID.load(&deps.storage).unwrap() - 1;
+        let invoice = INVOICE.load(&deps.storage, invoice_id).unwrap();
+        assert_eq!(invoice.receivable, coin(1047, "uatom"));
+
+        let mint: ExecuteMsg<Metadata, Empty> = match &res.messages[0].msg {
+            CosmosMsg::Wasm(WasmMsg::Execute { msg, .. }) => from_binary(msg).unwrap(),
+            _ => panic!("expected a mint message"),
+        };
+        match mint {
+            ExecuteMsg::Mint(mint) => {
+                assert_eq!(mint.extension.line_items, line_items);
+                assert_eq!(mint.extension.subtotal, Uint128::new(900));
+                assert_eq!(mint.extension.tax_total, Uint128::new(147));
+            }
+            _ => panic!("expected a mint message"),
+        }
+
+        let summary = get_tax_summary(deps.as_ref(), invoice_id).unwrap();
+        let taxes: Vec<_> = summary
+            .taxes
+            .iter()
+            .map(|line| (line.tax_rate, line.taxable.u128(), line.tax.u128()))
+            .collect();
+        assert_eq!(
+            taxes,
+            vec![
+                (Decimal::percent(19), 700, 133),
+                (Decimal::percent(7), 200, 14)
+            ]
+        );
+    }
+
+    #[test]
+    fn pending_invoices_are_paged_and_filtered() {
+        let (mut deps, first) = setup_with_invoice(None);
+        let create = CoreExecuteMsg::CreateInvoice {
+            payee_address: Addr::unchecked(PAYEE),
+            receivable: coin(500, "uatom"),
+            amount_paid: coin(0, "uatom"),
+            service_type: ServiceType::Service,
+            doc_uri: "ipfs://invoice".to_string(),
+            due_date: mock_env().block.time.plus_seconds(30 * SECONDS_PER_DAY),
+            payment_terms: None,
+            settlement: None,
+            installments: None,
+            line_items: None,
+        };
+        let mut ids = vec![first];
+        for _ in 0..2 {
+            execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(ISSUER, &[]),
+                create.clone(),
+            )
+            .unwrap();
+            ids.push(INVOICE_ID.load(&deps.storage).unwrap() - 1);
+        }
+        let (first, second, third) = (ids[0], ids[1], ids[2]);
+        accept_invoice(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[]),
+            second,
+            None,
+        )
+        .unwrap();
+
+        let page_ids = |res: RequestResponse| -> (Vec<u64>, Vec<u64>) {
+            (
+                res.sent_invoices.iter().map(|r| r.invoice.id).collect(),
+                res.received_invoices.iter().map(|r| r.invoice.id).collect(),
+            )
+        };
+        let page = get_pending_invoices(
+            deps.as_ref(),
+            mock_env(),
+            Addr::unchecked(ISSUER),
+            None,
+            None,
+            Some(2),
+        )
+        .unwrap();
+        assert_eq!(page_ids(page), (vec![first, second], vec![]));
+        let page = get_pending_invoices(
+            deps.as_ref(),
+            mock_env(),
+            Addr::unchecked(PAYEE),
+            None,
+            Some(second),
+            Some(2),
+        )
+        .unwrap();
+        assert_eq!(page_ids(page), (vec![], vec![third]));
+
+        //// only the accepted invoice ////
+        let page = get_pending_invoices(
+            deps.as_ref(),
+            mock_env(),
+            Addr::unchecked(PAYEE),
+            Some(Status::Accepted),
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(page_ids(page), (vec![], vec![second]));
+
+        let filter = InvoiceFilter {
+            status: Some(Status::Raised),
+            denom: Some("uatom".to_string()),
+            ..InvoiceFilter::default()
+        };
+        let invoices = get_invoices(deps.as_ref(), mock_env(), filter, Some(first), None).unwrap();
+        assert_eq!(
+            invoices.iter().map(|i| i.id).collect::<Vec<_>>(),
+            vec![third]
+        );
+    }
+}
@@ -0,0 +1,268 @@
+use crate::state::*;
+use cosmwasm_std::{Addr, BlockInfo, DepsMut, Env, MessageInfo, Response, StdError, Timestamp};
+
+use crate::error::ContractError;
+
+pub fn update_kyc(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    address: Addr,
+    status: KYCStatus,
+    expires_at: Option<Timestamp>,
+    attestation: Option<KycAttestation>,
+) -> Result<Response, ContractError> {
+    //// do not accept funds ////
+    if !info.funds.is_empty() {
+        return Err(StdError::generic_err("Funds not accepted").into());
+    }
+
+    if !KYC_PROVIDERS.has(deps.storage, &info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut contact_info = CONTACT_INFO
+        .may_load(deps.storage, &address)?
+        .ok_or_else(|| StdError::generic_err("Profile does not exist"))?;
+    if contact_info.profile_status == ProfileStatus::Erased {
+        return Err(StdError::generic_err("Profile is erased").into());
+    }
+
+    let current = contact_info.current_kyc_status(&env.block);
+    if !current.can_transition_to(&status) {
+        return Err(StdError::generic_err("Invalid KYC status transition").into());
+    }
+
+    //// only approvals expire ////
+    if let Some(expires_at) = expires_at {
+        if status != KYCStatus::Approved {
+            return Err(StdError::generic_err("Only approvals can expire").into());
+        }
+        if expires_at <= env.block.time {
+            return Err(StdError::generic_err("KYC expiry must be in the future").into());
+        }
+    }
+    if attestation
+        .as_ref()
+        .is_some_and(|attestation| attestation.hash.is_empty())
+    {
+        return Err(StdError::generic_err("Attestation hash cannot be empty").into());
+    }
+
+    let mut response = Response::new()
+        .add_attribute("method", "update_kyc")
+        .add_attribute("provider", info.sender.as_str())
+        .add_attribute("address", address.as_str())
+        .add_attribute("from_status", current.key())
+        .add_attribute("status", status.key());
+    if let Some(expires_at) = expires_at {
+        response = response.add_attribute("expires_at", expires_at.seconds().to_string());
+    }
+    if let Some(attestation) = &attestation {
+        response = response.add_attribute("attestation_hash", attestation.hash.clone());
+    }
+
+    contact_info.kyc_status = status;
+    contact_info.kyc_record = Some(KycRecord {
+        provider: info.sender,
+        updated_at: env.block.time,
+        expires_at,
+        attestation,
+    });
+    CONTACT_INFO.save(deps.storage, &address, &contact_info)?;
+
+    Ok(response)
+}
+
+/// errors with "`party` KYC not verified" unless the profile meets `requirement`
+pub fn ensure_kyc(
+    contact_info: &ContactInfo,
+    block: &BlockInfo,
+    requirement: &KycRequirement,
+    party: &str,
+) -> Result<(), ContractError> {
+    if !requirement.is_met_by(&contact_info.current_kyc_status(block)) {
+        return Err(StdError::generic_err(format!("{} KYC not verified", party)).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::execute;
+    use crate::invoice::{accept_invoice, pay_invoice};
+    use crate::msg::ExecuteMsg;
+    use crate::query::get_kyc_status;
+    use crate::testing::*;
+    use cosmwasm_std::coins;
+    use cosmwasm_std::testing::{mock_env, mock_info};
+
+    #[test]
+    fn kyc_lifecycle_is_driven_by_providers() {
+        let mut deps = setup();
+        let issuer = Addr::unchecked(ISSUER);
+
+        //// only registered providers change the status ////
+        let err = update_kyc(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            issuer.clone(),
+            KYCStatus::Rejected,
+            None,
+            None,
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        let err = update_kyc(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(KYC_PROVIDER, &[]),
+            issuer.clone(),
+            KYCStatus::InProcess,
+            None,
+            None,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Std(StdError::generic_err("Invalid KYC status transition"))
+        );
+
+        //// approval renewed with an expiry and an attestation ////
+        let expires_at = mock_env().block.time.plus_seconds(10 * SECONDS_PER_DAY);
+        let attestation = KycAttestation {
+            hash: "8c2f1a".to_string(),
+            uri: Some("ipfs://attestation".to_string()),
+        };
+        let res = update_kyc(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(KYC_PROVIDER, &[]),
+            issuer.clone(),
+            KYCStatus::Approved,
+            Some(expires_at),
+            Some(attestation.clone()),
+        )
+        .unwrap();
+        assert!(res
+            .attributes
+            .iter()
+            .any(|attr| attr.key == "attestation_hash" && attr.value == "8c2f1a"));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            create_invoice_msg(),
+        )
+        .unwrap();
+
+        //// an expired approval has to be verified again ////
+        let mut env = mock_env();
+        env.block.time = expires_at;
+        let status = get_kyc_status(deps.as_ref(), env.clone(), issuer.clone()).unwrap();
+        assert_eq!(status.status, KYCStatus::ReVerify);
+        assert_eq!(status.record.unwrap().attestation, Some(attestation));
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(ISSUER, &[]),
+            create_invoice_msg(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Std(StdError::generic_err("Creator KYC not verified"))
+        );
+
+        update_kyc(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(KYC_PROVIDER, &[]),
+            issuer,
+            KYCStatus::Approved,
+            None,
+            None,
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            env,
+            mock_info(ISSUER, &[]),
+            create_invoice_msg(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn kyc_policy_applies_to_each_side() {
+        let (mut deps, invoice_id) = setup_with_invoice(None);
+        accept_invoice(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[]),
+            invoice_id,
+            None,
+        )
+        .unwrap();
+
+        //// the payer only needs verification to have started by default ////
+        update_kyc(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(KYC_PROVIDER, &[]),
+            Addr::unchecked(PAYEE),
+            KYCStatus::ReVerify,
+            None,
+            None,
+        )
+        .unwrap();
+        let policy = KycPolicy {
+            issuer: KycRequirement::Approved,
+            payer: KycRequirement::Approved,
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ADMIN, &[]),
+            ExecuteMsg::SetKycPolicy { policy },
+        )
+        .unwrap();
+
+        let err = pay_invoice(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &coins(500, "uatom")),
+            invoice_id,
+            None,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Std(StdError::generic_err("Payer KYC not verified"))
+        );
+
+        let policy = KycPolicy {
+            issuer: KycRequirement::Approved,
+            payer: KycRequirement::None,
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ADMIN, &[]),
+            ExecuteMsg::SetKycPolicy { policy },
+        )
+        .unwrap();
+        pay_invoice(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &coins(500, "uatom")),
+            invoice_id,
+            None,
+        )
+        .unwrap();
+    }
+}
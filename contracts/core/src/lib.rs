@@ -18,11 +18,27 @@ For more information on this contract, please check out the
 [README](https://github.com/CosmWasm/cw-plus/blob/main/contracts/cw1-whitelist/README.md).
 */
 
+pub mod admin;
+pub mod approval;
+pub mod contact;
 pub mod contract;
+pub mod credit_note;
+pub mod delegation;
+pub mod dispute;
 pub mod error;
+pub mod escrow;
+pub mod fee;
+pub mod helpers;
 pub mod invoice;
+pub mod kyc;
+pub mod migration;
 pub mod msg;
+pub mod org;
 pub mod profile;
 pub mod query;
+pub mod recurring;
 pub mod state;
 pub use crate::error::ContractError;
+
+#[cfg(any(test, feature = "test-utils"))]
+pub mod testing;
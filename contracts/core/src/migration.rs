@@ -0,0 +1,696 @@
+use crate::state::*;
+use cosmwasm_std::{
+    from_binary, Addr, Binary, Empty, Order, Response, StdError, StdResult, Storage,
+};
+use cw2::ensure_from_older_version;
+use cw20::Denom;
+use cw_storage_plus::{Bound, Map};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+use crate::error::ContractError;
+
+/// records migrated by one run of a step, `next` is set if the batch ended before the step did
+struct StepResult {
+    migrated: u64,
+    next: Option<Vec<u8>>,
+}
+
+/// migrates at most `limit` records after the given key
+type StepFn = fn(&mut dyn Storage, Option<Vec<u8>>, usize) -> StdResult<StepResult>;
+
+/// state migration run on stores written by a version older than `version`
+struct MigrationStep {
+    version: &'static str,
+    name: &'static str,
+    run: StepFn,
+    /// number of records the step visits, for dry runs
+    count: fn(&dyn Storage) -> StdResult<u64>,
+}
+
+/// steps run in this order, later steps can rely on the layout written by earlier ones
+const MIGRATIONS: &[MigrationStep] = &[
+    MigrationStep {
+        version: "1.2.0",
+        name: "asset_denoms",
+        run: migrate_asset_denoms,
+        count: count_asset_denoms,
+    },
+    MigrationStep {
+        version: "1.2.0",
+        name: "admin_list",
+        run: seed_admin_list,
+        count: count_admin_list,
+    },
+    MigrationStep {
+        version: "1.2.0",
+        name: "invoice_indexes",
+        run: index_invoices,
+        count: count_invoices,
+    },
+    MigrationStep {
+        version: "1.2.0",
+        name: "profile_contact_lists",
+        run: move_profile_contact_lists,
+        count: count_profiles,
+    },
+    MigrationStep {
+        version: "1.2.0",
+        name: "profile_invoice_lists",
+        run: drop_profile_invoice_lists,
+        count: count_profiles,
+    },
+    MigrationStep {
+        version: "1.2.0",
+        name: "kyc_reverify",
+        run: reverify_unrecorded_kyc,
+        count: count_profiles,
+    },
+];
+
+pub fn migrate(
+    storage: &mut dyn Storage,
+    name: &str,
+    version: &str,
+    msg: MigrateMsg,
+) -> Result<Response, ContractError> {
+    if msg.batch_size == Some(0) {
+        return Err(StdError::generic_err("Batch size cannot be zero").into());
+    }
+    if msg.dry_run {
+        return dry_run(storage, name, version);
+    }
+
+    //// a resumed migration already bumped the stored version, so it remembers where it started ////
+    let pending = PENDING_MIGRATION.may_load(storage)?;
+    let stored = ensure_from_older_version(storage, name, version)?;
+    let (from, mut step, mut start_after) = match pending {
+        Some(pending) => {
+            let step = MIGRATIONS
+                .iter()
+                .position(|migration| migration.name == pending.step)
+                .ok_or_else(|| StdError::generic_err("Unknown migration step"))?;
+            (
+                parse_version(&pending.from_version)?,
+                step,
+                pending.start_after.map(|key| key.0),
+            )
+        }
+        None => (stored, 0, None),
+    };
+
+    let mut budget = msg.batch_size.map_or(usize::MAX, |size| size as usize);
+    let mut response = Response::new()
+        .add_attribute("method", "migrate")
+        .add_attribute("from_version", from.to_string())
+        .add_attribute("to_version", version);
+    while step < MIGRATIONS.len() {
+        let migration = &MIGRATIONS[step];
+        if from >= parse_version(migration.version)? {
+            step += 1;
+            continue;
+        }
+        if budget == 0 {
+            break;
+        }
+
+        let result = (migration.run)(storage, start_after.take(), budget)?;
+        budget = budget.saturating_sub(result.migrated as usize);
+        response = response.add_attribute(migration.name, result.migrated.to_string());
+        match result.next {
+            Some(key) => {
+                start_after = Some(key);
+                break;
+            }
+            None => step += 1,
+        }
+    }
+
+    let complete = step == MIGRATIONS.len();
+    if complete {
+        PENDING_MIGRATION.remove(storage);
+    } else {
+        let pending = PendingMigration {
+            from_version: from.to_string(),
+            step: MIGRATIONS[step].name.to_string(),
+            start_after: start_after.map(Binary),
+        };
+        PENDING_MIGRATION.save(storage, &pending)?;
+    }
+    Ok(response.add_attribute("complete", complete.to_string()))
+}
+
+/// same checks as a migration, but only reports what the pending steps would visit
+fn dry_run(storage: &dyn Storage, name: &str, version: &str) -> Result<Response, ContractError> {
+    let stored = cw2::get_contract_version(storage)?;
+    if stored.contract != name {
+        let msg = format!("Cannot migrate from {} to {}", stored.contract, name);
+        return Err(StdError::generic_err(msg).into());
+    }
+    if parse_version(&stored.version)? > parse_version(version)? {
+        let msg = format!(
+            "Cannot migrate from newer version ({}) to older ({})",
+            stored.version, version
+        );
+        return Err(StdError::generic_err(msg).into());
+    }
+
+    let from = match PENDING_MIGRATION.may_load(storage)? {
+        Some(pending) => pending.from_version,
+        None => stored.version,
+    };
+    let from = parse_version(&from)?;
+
+    let mut response = Response::new()
+        .add_attribute("method", "migrate")
+        .add_attribute("dry_run", "true")
+        .add_attribute("from_version", from.to_string())
+        .add_attribute("to_version", version);
+    for migration in MIGRATIONS.iter() {
+        if from < parse_version(migration.version)? {
+            response =
+                response.add_attribute(migration.name, (migration.count)(storage)?.to_string());
+        }
+    }
+    Ok(response)
+}
+
+fn parse_version(version: &str) -> StdResult<Version> {
+    version
+        .parse()
+        .map_err(|err| StdError::generic_err(format!("Semver: {}", err)))
+}
+
+/// re-saves the records of a page, returning where the next batch starts
+fn page_result<T>(
+    mut records: Vec<(Vec<u8>, T)>,
+    limit: usize,
+    mut save: impl FnMut(&T) -> StdResult<()>,
+) -> StdResult<StepResult> {
+    let more = records.len() > limit;
+    records.truncate(limit);
+    for (_, record) in records.iter() {
+        save(record)?;
+    }
+    Ok(StepResult {
+        migrated: records.len() as u64,
+        next: if more {
+            records.last().map(|(key, _)| key.clone())
+        } else {
+            None
+        },
+    })
+}
+
+/// 1.1.x stored accepted assets with a plain native denom
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct LegacyAsset {
+    name: String,
+    denom: String,
+    decimal: u64,
+    uri: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct LegacyConfig {
+    nft_address: Addr,
+    owner: Addr,
+    accepted_assets: Vec<LegacyAsset>,
+}
+
+fn legacy_config(storage: &dyn Storage) -> StdResult<Option<LegacyConfig>> {
+    let raw = match storage.get(CONFIG.as_slice()) {
+        Some(raw) => Binary(raw),
+        None => return Ok(None),
+    };
+    if from_binary::<Config>(&raw).is_ok() {
+        return Ok(None);
+    }
+    from_binary(&raw).map(Some)
+}
+
+fn migrate_asset_denoms(
+    storage: &mut dyn Storage,
+    _start_after: Option<Vec<u8>>,
+    _limit: usize,
+) -> StdResult<StepResult> {
+    let mut migrated = 0;
+    if let Some(legacy) = legacy_config(storage)? {
+        let config = Config {
+            nft_address: legacy.nft_address,
+            owner: legacy.owner,
+            accepted_assets: legacy
+                .accepted_assets
+                .into_iter()
+                .map(|asset| Asset {
+                    name: asset.name,
+                    denom: Denom::Native(asset.denom),
+                    decimal: asset.decimal,
+                    uri: asset.uri,
+                })
+                .collect(),
+        };
+        CONFIG.save(storage, &config)?;
+        migrated = 1;
+    }
+    Ok(StepResult {
+        migrated,
+        next: None,
+    })
+}
+
+fn count_asset_denoms(storage: &dyn Storage) -> StdResult<u64> {
+    Ok(legacy_config(storage)?.is_some() as u64)
+}
+
+/// 1.1.x had no admin list, the config owner becomes the first admin
+fn seed_admin_list(
+    storage: &mut dyn Storage,
+    _start_after: Option<Vec<u8>>,
+    _limit: usize,
+) -> StdResult<StepResult> {
+    let mut migrated = 0;
+    if count_admin_list(storage)? == 1 {
+        let config = CONFIG.load(storage)?;
+        let admin_list = AdminList {
+            admins: vec![config.owner],
+            mutable: true,
+        };
+        ADMIN_LIST.save(storage, &admin_list)?;
+        migrated = 1;
+    }
+    Ok(StepResult {
+        migrated,
+        next: None,
+    })
+}
+
+fn count_admin_list(storage: &dyn Storage) -> StdResult<u64> {
+    let seed = !ADMIN_LIST.exists(storage) && CONFIG.exists(storage);
+    Ok(seed as u64)
+}
+
+/// invoices move to an indexed map, saving through it writes their index entries
+fn index_invoices(
+    storage: &mut dyn Storage,
+    start_after: Option<Vec<u8>>,
+    limit: usize,
+) -> StdResult<StepResult> {
+    let records = INVOICE
+        .range_raw(
+            storage,
+            start_after.map(Bound::ExclusiveRaw),
+            None,
+            Order::Ascending,
+        )
+        .take(limit.saturating_add(1))
+        .collect::<StdResult<Vec<_>>>()?;
+    page_result(records, limit, |invoice| {
+        INVOICE.save(storage, invoice.id, invoice)
+    })
+}
+
+fn count_invoices(storage: &dyn Storage) -> StdResult<u64> {
+    Ok(INVOICE
+        .keys_raw(storage, None, None, Order::Ascending)
+        .count() as u64)
+}
+
+/// contact and request lists 1.1.x kept inside profiles
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct LegacyContactLists {
+    owner: Addr,
+    #[serde(default)]
+    sent_requests: Vec<Addr>,
+    #[serde(default)]
+    received_requests: Vec<Addr>,
+    #[serde(default)]
+    contacts: Vec<Addr>,
+}
+
+const LEGACY_CONTACT_LISTS: Map<&Addr, LegacyContactLists> = Map::new("contact_info");
+
+/// contacts and requests move to keyed maps, before the profiles are saved without the lists
+fn move_profile_contact_lists(
+    storage: &mut dyn Storage,
+    start_after: Option<Vec<u8>>,
+    limit: usize,
+) -> StdResult<StepResult> {
+    let records = LEGACY_CONTACT_LISTS
+        .range_raw(
+            storage,
+            start_after.map(Bound::ExclusiveRaw),
+            None,
+            Order::Ascending,
+        )
+        .take(limit.saturating_add(1))
+        .collect::<StdResult<Vec<_>>>()?;
+    page_result(records, limit, |lists| {
+        let owner = &lists.owner;
+        for contact in lists.contacts.iter() {
+            if !CONTACTS.has(storage, (owner, contact)) {
+                CONTACTS.save(storage, (owner, contact), &Contact::new(contact.clone()))?;
+            }
+        }
+        for recipient in lists.sent_requests.iter() {
+            SENT_REQUESTS.save(storage, (owner, recipient), &Empty {})?;
+            RECEIVED_REQUESTS.save(storage, (recipient, owner), &Empty {})?;
+        }
+        for requester in lists.received_requests.iter() {
+            RECEIVED_REQUESTS.save(storage, (owner, requester), &Empty {})?;
+            SENT_REQUESTS.save(storage, (requester, owner), &Empty {})?;
+        }
+        Ok(())
+    })
+}
+
+/// profiles lose the invoice lists the indexes replace, unknown fields are dropped on save
+fn drop_profile_invoice_lists(
+    storage: &mut dyn Storage,
+    start_after: Option<Vec<u8>>,
+    limit: usize,
+) -> StdResult<StepResult> {
+    let records = CONTACT_INFO
+        .range_raw(
+            storage,
+            start_after.map(Bound::ExclusiveRaw),
+            None,
+            Order::Ascending,
+        )
+        .take(limit.saturating_add(1))
+        .collect::<StdResult<Vec<_>>>()?;
+    page_result(records, limit, |contact_info| {
+        CONTACT_INFO.save(storage, &contact_info.owner, contact_info)
+    })
+}
+
+/// 1.1.x approvals carry no provider or expiry, so they have to be verified again
+fn reverify_unrecorded_kyc(
+    storage: &mut dyn Storage,
+    start_after: Option<Vec<u8>>,
+    limit: usize,
+) -> StdResult<StepResult> {
+    let records = CONTACT_INFO
+        .range_raw(
+            storage,
+            start_after.map(Bound::ExclusiveRaw),
+            None,
+            Order::Ascending,
+        )
+        .take(limit.saturating_add(1))
+        .collect::<StdResult<Vec<_>>>()?;
+    page_result(records, limit, |contact_info| {
+        if contact_info.kyc_status != KYCStatus::Approved || contact_info.kyc_record.is_some() {
+            return Ok(());
+        }
+        let contact_info = ContactInfo {
+            kyc_status: KYCStatus::ReVerify,
+            ..contact_info.clone()
+        };
+        CONTACT_INFO.save(storage, &contact_info.owner, &contact_info)
+    })
+}
+
+fn count_profiles(storage: &dyn Storage) -> StdResult<u64> {
+    Ok(CONTACT_INFO
+        .keys_raw(storage, None, None, Order::Ascending)
+        .count() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::{execute, instantiate, migrate as core_migrate, query};
+    use crate::msg::QueryMsg;
+    use crate::query::{ContactResponse, KycStatusResponse};
+    use crate::testing::create_profile_msg;
+    use cosmwasm_std::{Deps, DepsMut, Empty, Env, MessageInfo, StdResult};
+    use cw_multi_test::{App, AppResponse, ContractWrapper, Executor};
+    use cw_storage_plus::Map;
+
+    const OWNER: &str = "owner";
+
+    /// stand-in for a core 1.1.2 deployment, instantiated with state in the 1.1.x layout
+    fn legacy_instantiate(
+        deps: DepsMut,
+        _env: Env,
+        _info: MessageInfo,
+        invoice_count: u64,
+    ) -> StdResult<Response> {
+        cw2::set_contract_version(deps.storage, "core", "1.1.2")?;
+        deps.storage.set(
+            CONFIG.as_slice(),
+            br#"{"nft_address":"nft","owner":"owner","accepted_assets":[{"name":"atom","denom":"uatom","decimal":6,"uri":null}]}"#,
+        );
+
+        let invoices: Map<u64, Empty> = Map::new("invoice");
+        let mut ids = vec![];
+        for n in 0..invoice_count {
+            let id = 1_000_001 + 2 * n;
+            let invoice = format!(
+                r#"{{"id":{id},"from":"issuer","payee_address":"payee","nft_id":{id},"doc_uri":"ipfs://invoice","due_amount":{{"denom":"uatom","amount":"1000"}},"receivable":{{"denom":"uatom","amount":"1000"}},"amount_paid":{{"denom":"uatom","amount":"0"}},"service_type":"service","status":"raised"}}"#
+            );
+            deps.storage.set(&invoices.key(id), invoice.as_bytes());
+            ids.push(id.to_string());
+        }
+        INVOICE_ID.save(deps.storage, &(1_000_000 + 2 * invoice_count))?;
+
+        let ids = ids.join(",");
+        for (owner, other, assigned, generated) in [
+            ("issuer", "payee", "", ids.as_str()),
+            ("payee", "issuer", ids.as_str(), ""),
+        ] {
+            let profile = format!(
+                r#"{{"name":"{owner}","company_name":"company","address":"street","phone_number":"0000000000","owner":"{owner}","email_id":"name@example.com","sent_requests":[],"received_requests":[],"contacts":["{other}"],"kyc_status":"approved","assigned_invoices":[{assigned}],"generated_invoices":[{generated}]}}"#
+            );
+            deps.storage.set(
+                &CONTACT_INFO.key(&Addr::unchecked(owner)),
+                profile.as_bytes(),
+            );
+        }
+        Ok(Response::default())
+    }
+
+    fn legacy_execute(_: DepsMut, _: Env, _: MessageInfo, _: Empty) -> StdResult<Response> {
+        Err(StdError::generic_err("not supported"))
+    }
+
+    fn legacy_query(_: Deps, _: Env, _: Empty) -> StdResult<Binary> {
+        Err(StdError::generic_err("not supported"))
+    }
+
+    /// deploys the legacy contract and stores the current core code
+    fn setup_legacy(invoice_count: u64) -> (App, Addr, u64) {
+        let mut app = App::default();
+        let legacy = ContractWrapper::new(legacy_execute, legacy_instantiate, legacy_query);
+        let legacy_code_id = app.store_code(Box::new(legacy));
+        let core = ContractWrapper::new(execute, instantiate, query).with_migrate(core_migrate);
+        let core_code_id = app.store_code(Box::new(core));
+
+        let addr = app
+            .instantiate_contract(
+                legacy_code_id,
+                Addr::unchecked(OWNER),
+                &invoice_count,
+                &[],
+                "core",
+                Some(OWNER.to_string()),
+            )
+            .unwrap();
+        (app, addr, core_code_id)
+    }
+
+    fn migrate_msg(dry_run: bool, batch_size: Option<u32>) -> MigrateMsg {
+        MigrateMsg {
+            dry_run,
+            batch_size,
+        }
+    }
+
+    fn attribute(res: &AppResponse, key: &str) -> Option<String> {
+        res.events
+            .iter()
+            .flat_map(|event| event.attributes.iter())
+            .find(|attr| attr.key == key)
+            .map(|attr| attr.value.clone())
+    }
+
+    #[test]
+    fn upgrades_from_1_1_data() {
+        let (mut app, addr, code_id) = setup_legacy(2);
+        let res = app
+            .migrate_contract(
+                Addr::unchecked(OWNER),
+                addr.clone(),
+                &migrate_msg(false, None),
+                code_id,
+            )
+            .unwrap();
+        assert_eq!(attribute(&res, "from_version").unwrap(), "1.1.2");
+        assert_eq!(attribute(&res, "invoice_indexes").unwrap(), "2");
+        assert_eq!(attribute(&res, "profile_contact_lists").unwrap(), "2");
+        assert_eq!(attribute(&res, "complete").unwrap(), "true");
+
+        let version = cw2::query_contract_info(&app.wrap(), addr.to_string()).unwrap();
+        assert_eq!(version.version, env!("CARGO_PKG_VERSION"));
+
+        //// assets, admins and invoice indexes are usable by the new code ////
+        let config: Config = app
+            .wrap()
+            .query_wasm_smart(&addr, &QueryMsg::GetConfig {})
+            .unwrap();
+        assert_eq!(
+            config.accepted_assets[0].denom,
+            Denom::Native("uatom".to_string())
+        );
+        let admin_list: AdminList = app
+            .wrap()
+            .query_wasm_smart(&addr, &QueryMsg::GetAdminList {})
+            .unwrap();
+        assert_eq!(admin_list.admins, vec![Addr::unchecked(OWNER)]);
+        let kyc: KycStatusResponse = app
+            .wrap()
+            .query_wasm_smart(
+                &addr,
+                &QueryMsg::GetKycStatus {
+                    address: Addr::unchecked("issuer"),
+                },
+            )
+            .unwrap();
+        assert_eq!(kyc.status, KYCStatus::ReVerify);
+
+        let invoices: Vec<Invoice> = app
+            .wrap()
+            .query_wasm_smart(
+                &addr,
+                &QueryMsg::GetInvoices {
+                    filter: InvoiceFilter {
+                        payee: Some(Addr::unchecked("payee")),
+                        ..InvoiceFilter::default()
+                    },
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            invoices
+                .iter()
+                .map(|invoice| invoice.id)
+                .collect::<Vec<_>>(),
+            vec![1_000_001, 1_000_003]
+        );
+
+        //// migrating again to the same version is a no-op ////
+        let res = app
+            .migrate_contract(
+                Addr::unchecked(OWNER),
+                addr,
+                &migrate_msg(false, None),
+                code_id,
+            )
+            .unwrap();
+        assert_eq!(attribute(&res, "invoice_indexes"), None);
+        assert_eq!(attribute(&res, "complete").unwrap(), "true");
+    }
+
+    #[test]
+    fn batched_migration_resumes_and_blocks_execution() {
+        let (mut app, addr, code_id) = setup_legacy(3);
+
+        //// config and admin list, then two of the three invoices ////
+        let res = app
+            .migrate_contract(
+                Addr::unchecked(OWNER),
+                addr.clone(),
+                &migrate_msg(false, Some(4)),
+                code_id,
+            )
+            .unwrap();
+        assert_eq!(attribute(&res, "invoice_indexes").unwrap(), "2");
+        assert_eq!(attribute(&res, "complete").unwrap(), "false");
+
+        let pending: Option<PendingMigration> = app
+            .wrap()
+            .query_wasm_smart(&addr, &QueryMsg::GetPendingMigration {})
+            .unwrap();
+        let pending = pending.unwrap();
+        assert_eq!(pending.from_version, "1.1.2");
+        assert_eq!(pending.step, "invoice_indexes");
+
+        let err = app
+            .execute_contract(
+                Addr::unchecked("carol"),
+                addr.clone(),
+                &create_profile_msg(),
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.root_cause().to_string(),
+            "Generic error: Migration in progress"
+        );
+
+        //// the last invoice, then both profiles three times ////
+        let res = app
+            .migrate_contract(
+                Addr::unchecked(OWNER),
+                addr.clone(),
+                &migrate_msg(false, Some(7)),
+                code_id,
+            )
+            .unwrap();
+        assert_eq!(attribute(&res, "from_version").unwrap(), "1.1.2");
+        assert_eq!(attribute(&res, "invoice_indexes").unwrap(), "1");
+        assert_eq!(attribute(&res, "profile_contact_lists").unwrap(), "2");
+        assert_eq!(attribute(&res, "profile_invoice_lists").unwrap(), "2");
+        assert_eq!(attribute(&res, "kyc_reverify").unwrap(), "2");
+        assert_eq!(attribute(&res, "complete").unwrap(), "true");
+
+        app.execute_contract(
+            Addr::unchecked("carol"),
+            addr.clone(),
+            &create_profile_msg(),
+            &[],
+        )
+        .unwrap();
+        let contacts: Vec<ContactResponse> = app
+            .wrap()
+            .query_wasm_smart(
+                &addr,
+                &QueryMsg::GetAllContacts {
+                    address: Addr::unchecked("issuer"),
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap();
+        assert_eq!(contacts[0].address, Addr::unchecked("payee"));
+    }
+
+    #[test]
+    fn dry_run_only_reports_counts() {
+        let (mut app, addr, code_id) = setup_legacy(2);
+        let res = app
+            .migrate_contract(
+                Addr::unchecked(OWNER),
+                addr.clone(),
+                &migrate_msg(true, None),
+                code_id,
+            )
+            .unwrap();
+        assert_eq!(attribute(&res, "asset_denoms").unwrap(), "1");
+        assert_eq!(attribute(&res, "admin_list").unwrap(), "1");
+        assert_eq!(attribute(&res, "invoice_indexes").unwrap(), "2");
+        assert_eq!(attribute(&res, "profile_contact_lists").unwrap(), "2");
+        assert_eq!(attribute(&res, "profile_invoice_lists").unwrap(), "2");
+        assert_eq!(attribute(&res, "kyc_reverify").unwrap(), "2");
+
+        let version = cw2::query_contract_info(&app.wrap(), addr.to_string()).unwrap();
+        assert_eq!(version.version, "1.1.2");
+        let admin_list: StdResult<AdminList> = app
+            .wrap()
+            .query_wasm_smart(&addr, &QueryMsg::GetAdminList {});
+        assert!(admin_list.is_err());
+    }
+}
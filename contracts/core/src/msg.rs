@@ -1,7 +1,10 @@
 use crate::state::*;
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::{Addr, Decimal};
-use cosmwasm_std::{Coin, CosmosMsg, Empty};
+use cosmwasm_std::{Addr, Binary};
+use cosmwasm_std::{Coin, CosmosMsg, Timestamp, Uint128};
+use cw20::{Cw20ReceiveMsg, Denom};
+use cw4::MemberChangedHookMsg;
+use cw_utils::Expiration;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::fmt;
@@ -13,7 +16,7 @@ pub struct InstantiateMsg {
     pub mutable: bool,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 
 pub enum ExecuteMsg {
@@ -37,8 +40,141 @@ pub enum ExecuteMsg {
         amount_paid: Coin,
         service_type: ServiceType,
         doc_uri: String,
+        due_date: Timestamp,
+        payment_terms: Option<PaymentTerms>,
+        settlement: Option<SettlementMode>,
+        /// splits the receivable into amounts due on successive dates
+        installments: Option<Vec<Installment>>,
+        /// itemised body of the invoice, the receivable must equal their total including tax
+        /// or be zero to derive it from them
+        line_items: Option<Vec<LineItem>>,
     },
 
+    RejectRequest {
+        address: Addr,
+    },
+    CancelRequest {
+        address: Addr,
+    },
+    RemoveContact {
+        address: Addr,
+    },
+    BlockAddress {
+        address: Addr,
+    },
+    UnblockAddress {
+        address: Addr,
+    },
+    /// `None` clears the alias
+    SetContactAlias {
+        address: Addr,
+        alias: Option<String>,
+    },
+    TagContact {
+        address: Addr,
+        tag: Option<ContactTag>,
+    },
+    SetContactDefaults {
+        address: Addr,
+        default_asset: Option<Denom>,
+        default_terms: Option<PaymentTerms>,
+    },
+    UpdateProfile {
+        update: ProfileUpdate,
+    },
+    DeactivateProfile {},
+    /// admin only, wipes the PII of a deactivated profile
+    EraseProfile {
+        address: Addr,
+    },
+    /// profile storing only commitments to its PII, see `QueryMsg::VerifyPii`
+    CreatePrivateProfile {
+        company_name: String,
+        commitments: PiiCommitments,
+        encrypted_pii: Option<Binary>,
+        viewing_key: Option<Binary>,
+    },
+    SetViewingKey {
+        viewing_key: Binary,
+    },
+    SharePii {
+        contact: Addr,
+        encrypted_pii: Binary,
+    },
+    /// organisation profile keyed by a cw4 group, only callable by the group admin, `payout`
+    /// receives what is owed to the org
+    CreateOrgProfile {
+        group: String,
+        name: String,
+        email_id: String,
+        phone_number: String,
+        company_name: String,
+        address: String,
+        payout: String,
+    },
+    /// only callable by the group admin
+    SetOrgPayout {
+        org: Addr,
+        payout: String,
+    },
+    SetOrgRoles {
+        org: Addr,
+        member: String,
+        roles: Vec<OrgRole>,
+    },
+    /// sends `msg` as the org, the sender needs the org role the message requires
+    ExecuteAs {
+        org: Addr,
+        msg: Box<ExecuteMsg>,
+    },
+    /// sent by org groups when their membership changes
+    MemberChangedHook(MemberChangedHookMsg),
+    /// invoices to the sender above a threshold need approval of `multisig`, `None` removes the policy
+    SetApprovalPolicy {
+        multisig: Option<String>,
+        thresholds: Vec<Coin>,
+    },
+    /// sent by the multisig once the approval proposal passed
+    ApproveInvoice {
+        invoice_id: u64,
+    },
+    RejectApproval {
+        invoice_id: u64,
+    },
+    /// lets `delegate` accept, pay or create invoices for the sender, replacing any earlier grant.
+    /// The delegate pays with its own funds, `spend_limits` cap the receivables it accepts and
+    /// the payments it makes on invoices it did not accept
+    SetDelegation {
+        delegate: String,
+        permissions: DelegatePermissions,
+        spend_limits: Vec<Coin>,
+        expires: Option<Expiration>,
+    },
+    RevokeDelegation {
+        delegate: Addr,
+    },
+    /// sends `msg` as the owner, acceptances and payments are taken off the delegate's spend limits
+    ExecuteAsDelegate {
+        owner: Addr,
+        msg: Box<ExecuteMsg>,
+    },
+    SetFeeConfig {
+        payment_fee_bps: u16,
+        creation_fee: Option<Coin>,
+        caps: Vec<Coin>,
+    },
+    AddFeeExemptions {
+        addresses: Vec<String>,
+    },
+    RemoveFeeExemptions {
+        addresses: Vec<String>,
+    },
+    /// sends collected fees to `recipient`, or the sender, the whole balance if `amount` is unset
+    WithdrawFees {
+        denom: Denom,
+        amount: Option<Uint128>,
+        recipient: Option<String>,
+    },
     SetConfig {
         nft_address: Addr,
         owner: Addr,
@@ -50,20 +186,301 @@ pub enum ExecuteMsg {
     PayInvoice {
         invoice_id: u64,
     },
+    RejectInvoice {
+        invoice_id: u64,
+        reason: String,
+    },
+    CancelInvoice {
+        invoice_id: u64,
+    },
+    VoidInvoice {
+        invoice_id: u64,
+        reason: String,
+    },
+    AddAdmins {
+        admins: Vec<String>,
+    },
+    RemoveAdmins {
+        admins: Vec<String>,
+    },
+    Freeze {},
+    Receive(Cw20ReceiveMsg),
+    ConfirmDelivery {
+        invoice_id: u64,
+    },
+    ReleaseEscrow {
+        invoice_id: u64,
+    },
+    /// by the receivable holder or an admin, burns the invoice nft so a holder other than this
+    /// contract has to approve it for the token first
+    RefundEscrow {
+        invoice_id: u64,
+    },
+    CreateRecurring {
+        template: RecurringTemplate,
+    },
+    CancelRecurring {
+        recurring_id: u64,
+    },
+    /// issues the next invoice of a template once it is due, callable by anyone
+    TriggerRecurring {
+        recurring_id: u64,
+    },
+    /// by the receivable holder, the issuer until the invoice nft is sold
+    CreateCreditNote {
+        invoice_id: u64,
+        amount: Uint128,
+        reason: String,
+    },
+    AcknowledgeCreditNote {
+        credit_note_id: u64,
+    },
+    CancelCreditNote {
+        credit_note_id: u64,
+    },
+    /// pays the refundable balance of a credit note to the payee
+    RefundCreditNote {
+        credit_note_id: u64,
+    },
+    AddKycProviders {
+        providers: Vec<String>,
+    },
+    RemoveKycProviders {
+        providers: Vec<String>,
+    },
+    SetKycPolicy {
+        policy: KycPolicy,
+    },
+    /// moves a profile through the KYC lifecycle, only callable by KYC providers
+    UpdateKyc {
+        address: Addr,
+        status: KYCStatus,
+        expires_at: Option<Timestamp>,
+        attestation: Option<KycAttestation>,
+    },
+    SetArbitration {
+        arbitrator: Arbitrator,
+        evidence_days: u64,
+        ruling_days: u64,
+    },
+    OpenDispute {
+        invoice_id: u64,
+        evidence_uri: String,
+    },
+    SubmitEvidence {
+        dispute_id: u64,
+        evidence_uri: String,
+    },
+    /// settles the escrow of a disputed invoice, sent by the arbitrator or its multisig
+    ResolveDispute {
+        dispute_id: u64,
+        ruling: Ruling,
+    },
+    /// unfreezes the invoice once the ruling deadline has passed, callable by anyone
+    ExpireDispute {
+        dispute_id: u64,
+    },
+}
+
+/// hook messages embedded in a cw20 `Send`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiveMsg {
+    PayInvoice { invoice_id: u64 },
+    RefundCreditNote { credit_note_id: u64 },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
-    GetInvoice { invoice_id: u64 },
+    GetInvoice {
+        invoice_id: u64,
+    },
+    GetInvoices {
+        filter: InvoiceFilter,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
     GetConfig {},
     GetLatestInvoiceId {},
-    GetContactInfo { address: Addr },
-    GetPendingInvoices { address: Addr },
-    GetExecutedInvoices { address: Addr },
-    GetTotalReceivables { address: Addr },
-    GetTotalPayables { address: Addr },
-    GetPendingContactRequests { address: Addr },
-    GetSentContactRequests { address: Addr },
-    GetAllContacts { address: Addr },
+    GetContactInfo {
+        address: Addr,
+    },
+    GetPendingInvoices {
+        address: Addr,
+        status: Option<Status>,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    GetExecutedInvoices {
+        address: Addr,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// totals of a page of the address's invoices, summed by the caller until
+    /// `last_invoice_id` is `None`
+    GetTotalReceivables {
+        address: Addr,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    GetTotalPayables {
+        address: Addr,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    GetPendingContactRequests {
+        address: Addr,
+        start_after: Option<Addr>,
+        limit: Option<u32>,
+    },
+    GetSentContactRequests {
+        address: Addr,
+        start_after: Option<Addr>,
+        limit: Option<u32>,
+    },
+    GetAllContacts {
+        address: Addr,
+        start_after: Option<Addr>,
+        limit: Option<u32>,
+    },
+    GetAdminList {},
+    GetAgingReport {
+        address: Addr,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    GetAmountDue {
+        invoice_id: u64,
+    },
+    GetInvoicePayments {
+        invoice_id: u64,
+    },
+    GetEscrow {
+        invoice_id: u64,
+    },
+    GetInstallments {
+        invoice_id: u64,
+    },
+    GetTaxSummary {
+        invoice_id: u64,
+    },
+    GetRecurring {
+        recurring_id: u64,
+    },
+    /// pages through the templates the address issues and pays
+    GetRecurringInvoices {
+        address: Addr,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    GetCreditNote {
+        credit_note_id: u64,
+    },
+    GetInvoiceCreditNotes {
+        invoice_id: u64,
+    },
+    GetPendingMigration {},
+    /// the owner's contacts with their address book entries, `Both` contacts match either tag
+    GetContacts {
+        owner: Addr,
+        tag: Option<ContactTag>,
+        start_after: Option<Addr>,
+        limit: Option<u32>,
+    },
+    ResolveContactAlias {
+        owner: Addr,
+        alias: String,
+    },
+    GetOrg {
+        org: Addr,
+    },
+    /// group members of the org with their roles
+    GetOrgMembers {
+        org: Addr,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    GetFeeConfig {},
+    /// fee settings and collected balance of a single asset
+    GetAssetFees {
+        denom: String,
+    },
+    /// collected balances of every asset
+    GetTreasury {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    GetFeeExemptions {
+        start_after: Option<Addr>,
+        limit: Option<u32>,
+    },
+    GetDelegation {
+        owner: Addr,
+        delegate: Addr,
+    },
+    GetDelegations {
+        owner: Addr,
+        start_after: Option<Addr>,
+        limit: Option<u32>,
+    },
+    /// cw1 style check whether `sender` may run `msg`, a wasm execute on core, as `owner`
+    CanExecute {
+        owner: Addr,
+        sender: String,
+        msg: CosmosMsg,
+    },
+    GetApprovalPolicy {
+        address: Addr,
+    },
+    GetPendingApproval {
+        invoice_id: u64,
+    },
+    /// outstanding approvals, optionally only those waiting for `approver`
+    GetPendingApprovals {
+        approver: Option<Addr>,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// cw3 proposal for the payee's multisig approving the held action
+    GetApprovalProposal {
+        invoice_id: u64,
+    },
+    GetBlockedAddresses {
+        address: Addr,
+        start_after: Option<Addr>,
+        limit: Option<u32>,
+    },
+    GetSharedPii {
+        owner: Addr,
+        viewer: Addr,
+    },
+    /// checks a claimed PII value against the profile, `salt` is ignored for public profiles
+    VerifyPii {
+        address: Addr,
+        field: PiiField,
+        value: String,
+        salt: String,
+    },
+    GetKycProviders {
+        start_after: Option<Addr>,
+        limit: Option<u32>,
+    },
+    GetKycPolicy {},
+    GetKycStatus {
+        address: Addr,
+    },
+    GetArbitration {},
+    GetDispute {
+        dispute_id: u64,
+    },
+    GetOpenDisputes {
+        address: Addr,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    GetRulingProposal {
+        dispute_id: u64,
+        ruling: Ruling,
+    },
 }
@@ -0,0 +1,562 @@
+use crate::contract::dispatch;
+use crate::msg::ExecuteMsg;
+use crate::state::*;
+use cosmwasm_std::{
+    Addr, Deps, DepsMut, Env, MessageInfo, QuerierWrapper, Response, StdError, StdResult, Storage,
+};
+use cw4::{Cw4Contract, MemberChangedHookMsg};
+
+use crate::error::ContractError;
+
+/// errors unless `sender` administers the cw4 group at `org`
+fn ensure_group_admin(querier: &QuerierWrapper, org: &Addr, sender: &Addr) -> StdResult<()> {
+    let admin = Cw4Contract::new(org.clone()).admin(querier)?;
+    if admin.as_deref() != Some(sender.as_str()) {
+        return Err(StdError::generic_err(
+            "Only the group admin can manage the org",
+        ));
+    }
+    Ok(())
+}
+
+/// creates the profile of an org, keyed by its cw4 group, on behalf of the group admin
+pub fn create_org_profile(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    params: OrgProfileParams,
+) -> Result<Response, ContractError> {
+    let OrgProfileParams {
+        group,
+        name,
+        email_id,
+        phone_number,
+        company_name,
+        address,
+        payout,
+    } = params;
+
+    //// do not accept funds ////
+    if !info.funds.is_empty() {
+        return Err(StdError::generic_err("Funds not accepted").into());
+    }
+
+    let org = deps.api.addr_validate(&group)?;
+    ensure_group_admin(&deps.querier, &org, &info.sender)?;
+    let payout = deps.api.addr_validate(&payout)?;
+
+    ///// only create profile if not already created /////
+    if CONTACT_INFO.has(deps.storage, &org) {
+        return Err(StdError::generic_err("Profile already exist").into());
+    }
+
+    let contact_info = ContactInfo {
+        name,
+        company_name,
+        address,
+        phone_number,
+        owner: org.clone(),
+        email_id,
+        kyc_status: KYCStatus::Unverified,
+        kyc_record: None,
+        private_pii: None,
+        viewing_key: None,
+        profile_status: ProfileStatus::Active,
+    };
+    CONTACT_INFO.save(deps.storage, &org, &contact_info)?;
+    ORGS.save(deps.storage, &org, &OrgProfile { payout })?;
+
+    Ok(Response::new()
+        .add_attribute("method", "create_org_profile")
+        .add_attribute("sender", info.sender)
+        .add_attribute("org", org))
+}
+
+/// changes where the org is paid, only for the group admin
+pub fn set_org_payout(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    org: Addr,
+    payout: String,
+) -> Result<Response, ContractError> {
+    if !ORGS.has(deps.storage, &org) {
+        return Err(StdError::generic_err("Org does not exist").into());
+    }
+    ensure_group_admin(&deps.querier, &org, &info.sender)?;
+
+    let profile = OrgProfile {
+        payout: deps.api.addr_validate(&payout)?,
+    };
+    ORGS.save(deps.storage, &org, &profile)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "set_org_payout")
+        .add_attribute("org", org)
+        .add_attribute("payout", profile.payout))
+}
+
+/// sets the roles of a group member, an empty list revokes all of them
+pub fn set_org_roles(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    org: Addr,
+    member: String,
+    roles: Vec<OrgRole>,
+) -> Result<Response, ContractError> {
+    if !ORGS.has(deps.storage, &org) {
+        return Err(StdError::generic_err("Org does not exist").into());
+    }
+    ensure_group_admin(&deps.querier, &org, &info.sender)?;
+
+    let member = deps.api.addr_validate(&member)?;
+    if roles.is_empty() {
+        ORG_ROLES.remove(deps.storage, (&org, &member));
+    } else {
+        let weight = Cw4Contract::new(org.clone()).is_member(&deps.querier, &member, None)?;
+        if weight.is_none() {
+            return Err(StdError::generic_err("Not a group member").into());
+        }
+        ORG_ROLES.save(deps.storage, (&org, &member), &roles)?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "set_org_roles")
+        .add_attribute("org", org)
+        .add_attribute("member", member))
+}
+
+/// drops the roles of members the group removed, sent by the group contract itself
+pub fn member_changed_hook(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    msg: MemberChangedHookMsg,
+) -> Result<Response, ContractError> {
+    if !ORGS.has(deps.storage, &info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut revoked = vec![];
+    for diff in msg.diffs {
+        if diff.new.is_none() {
+            let member = deps.api.addr_validate(&diff.key)?;
+            ORG_ROLES.remove(deps.storage, (&info.sender, &member));
+            revoked.push(diff.key);
+        }
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "member_changed_hook")
+        .add_attribute("org", info.sender)
+        .add_attribute("revoked", revoked.join(",")))
+}
+
+/// address funds and nfts owed to `address` are sent to, the payout address for orgs
+pub fn payout_address(storage: &dyn Storage, address: &Addr) -> StdResult<Addr> {
+    let payout = ORGS
+        .may_load(storage, address)?
+        .map_or_else(|| address.clone(), |org| org.payout);
+    Ok(payout)
+}
+
+/// true while `member` holds `role` and is still in the org's group
+pub fn has_org_role(deps: Deps, org: &Addr, member: &Addr, role: &OrgRole) -> StdResult<bool> {
+    let roles = ORG_ROLES
+        .may_load(deps.storage, (org, member))?
+        .unwrap_or_default();
+    if !roles.contains(role) {
+        return Ok(false);
+    }
+    let weight = Cw4Contract::new(org.clone()).is_member(&deps.querier, member, None)?;
+    Ok(weight.is_some())
+}
+
+/// role a member needs to send `msg` for an org, `None` if orgs cannot send it
+fn required_role(msg: &ExecuteMsg) -> Option<OrgRole> {
+    match msg {
+        ExecuteMsg::CreateInvoice { .. }
+        | ExecuteMsg::CancelInvoice { .. }
+        | ExecuteMsg::CreateRecurring { .. }
+        | ExecuteMsg::CancelRecurring { .. }
+        | ExecuteMsg::CreateCreditNote { .. }
+        | ExecuteMsg::CancelCreditNote { .. } => Some(OrgRole::Issuer),
+        ExecuteMsg::AcceptInvoice { .. }
+        | ExecuteMsg::RejectInvoice { .. }
+        | ExecuteMsg::ConfirmDelivery { .. }
+        | ExecuteMsg::AcknowledgeCreditNote { .. }
+        | ExecuteMsg::CreateRequest { .. }
+        | ExecuteMsg::AcceptRequest { .. }
+        | ExecuteMsg::RejectRequest { .. }
+        | ExecuteMsg::CancelRequest { .. }
+        | ExecuteMsg::RemoveContact { .. }
+        | ExecuteMsg::SetContactAlias { .. }
+        | ExecuteMsg::TagContact { .. }
+        | ExecuteMsg::SetContactDefaults { .. }
+        | ExecuteMsg::RejectApproval { .. } => Some(OrgRole::Approver),
+        ExecuteMsg::PayInvoice { .. } => Some(OrgRole::Payer),
+        _ => None,
+    }
+}
+
+/// runs `msg` with the org as sender, recording the member as the one acting
+pub fn execute_as(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    org: Addr,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    let role = required_role(&msg)
+        .ok_or_else(|| StdError::generic_err("Message cannot be sent for an org"))?;
+    if !has_org_role(deps.as_ref(), &org, &info.sender, &role)? {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let org_info = MessageInfo {
+        sender: org,
+        funds: info.funds,
+    };
+    let response = dispatch(deps, env, org_info, msg, Some(info.sender.clone()))?;
+
+    Ok(response.add_attribute("acting_member", info.sender))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::execute;
+    use crate::helpers::transfer_msg;
+    use crate::query::{get_org, get_org_members};
+    use crate::testing::*;
+    use cosmwasm_std::testing::{mock_env, mock_info, MOCK_CONTRACT_ADDR};
+    use cosmwasm_std::{
+        coins, from_binary, to_binary, Binary, ContractResult, CosmosMsg, Empty, SystemResult,
+        Uint128, WasmMsg, WasmQuery,
+    };
+    use cw20::Denom;
+    use cw4::{member_key, AdminResponse, Cw4QueryMsg, Member, MemberDiff, MemberListResponse};
+    use cw721::{Cw721QueryMsg, OwnerOfResponse};
+    use cw721_base::msg::ExecuteMsg as Cw721ExecuteMsg;
+
+    const GROUP: &str = "group";
+    const CLERK: &str = "clerk";
+    const TREASURY: &str = "treasury";
+
+    /// mocks a cw4 group administered by the admin with the clerk as its only member
+    fn mock_group(deps: &mut MockDeps) {
+        deps.querier.update_wasm(|query| {
+            let res = match query {
+                WasmQuery::Raw { contract_addr, key } if contract_addr == GROUP => {
+                    if *key == Binary(member_key(CLERK)) {
+                        to_binary(&1u64).unwrap()
+                    } else {
+                        Binary::default()
+                    }
+                }
+                WasmQuery::Smart { contract_addr, msg } if contract_addr == GROUP => {
+                    match from_binary(msg) {
+                        Ok(Cw4QueryMsg::Admin {}) => to_binary(&AdminResponse {
+                            admin: Some(ADMIN.to_string()),
+                        })
+                        .unwrap(),
+                        Ok(Cw4QueryMsg::ListMembers { .. }) => to_binary(&MemberListResponse {
+                            members: vec![Member {
+                                addr: CLERK.to_string(),
+                                weight: 1,
+                            }],
+                        })
+                        .unwrap(),
+                        _ => return unsupported_query(query),
+                    }
+                }
+                WasmQuery::Smart { msg, .. } => match from_binary(msg) {
+                    Ok(Cw721QueryMsg::OwnerOf { .. }) => to_binary(&OwnerOfResponse {
+                        owner: MOCK_CONTRACT_ADDR.to_string(),
+                        approvals: vec![],
+                    })
+                    .unwrap(),
+                    _ => return unsupported_query(query),
+                },
+                _ => return unsupported_query(query),
+            };
+            SystemResult::Ok(ContractResult::Ok(res))
+        });
+    }
+
+    /// core with a KYC approved org, connected to the payee, whose clerk holds `roles`
+    fn setup_org(roles: Vec<OrgRole>) -> MockDeps {
+        let mut deps = setup();
+        mock_group(&mut deps);
+        let org = Addr::unchecked(GROUP);
+        let msgs = vec![
+            (
+                ADMIN,
+                ExecuteMsg::CreateOrgProfile {
+                    group: GROUP.to_string(),
+                    name: "name".to_string(),
+                    email_id: "ap@example.com".to_string(),
+                    phone_number: "0000000000".to_string(),
+                    company_name: "org".to_string(),
+                    address: "street".to_string(),
+                    payout: TREASURY.to_string(),
+                },
+            ),
+            (
+                ADMIN,
+                ExecuteMsg::SetOrgRoles {
+                    org: org.clone(),
+                    member: CLERK.to_string(),
+                    roles,
+                },
+            ),
+            (
+                KYC_PROVIDER,
+                ExecuteMsg::UpdateKyc {
+                    address: org.clone(),
+                    status: KYCStatus::InProcess,
+                    expires_at: None,
+                    attestation: None,
+                },
+            ),
+            (
+                KYC_PROVIDER,
+                ExecuteMsg::UpdateKyc {
+                    address: org.clone(),
+                    status: KYCStatus::Approved,
+                    expires_at: None,
+                    attestation: None,
+                },
+            ),
+            (
+                PAYEE,
+                ExecuteMsg::CreateRequest {
+                    address: org.clone(),
+                },
+            ),
+        ];
+        for (sender, msg) in msgs {
+            execute(deps.as_mut(), mock_env(), mock_info(sender, &[]), msg).unwrap();
+        }
+        deps
+    }
+
+    fn execute_as_org(deps: &mut MockDeps, msg: ExecuteMsg) -> Result<Response, ContractError> {
+        let msg = ExecuteMsg::ExecuteAs {
+            org: Addr::unchecked(GROUP),
+            msg: Box::new(msg),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(CLERK, &[]), msg)
+    }
+
+    #[test]
+    fn members_act_for_the_org_by_role() {
+        let mut deps = setup_org(vec![OrgRole::Issuer, OrgRole::Approver, OrgRole::Payer]);
+        let org = Addr::unchecked(GROUP);
+
+        //// only the group admin manages roles, and only for members ////
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(CLERK, &[]),
+            ExecuteMsg::SetOrgRoles {
+                org: org.clone(),
+                member: CLERK.to_string(),
+                roles: vec![OrgRole::Viewer],
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Std(StdError::generic_err(
+                "Only the group admin can manage the org"
+            ))
+        );
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ADMIN, &[]),
+            ExecuteMsg::SetOrgRoles {
+                org: org.clone(),
+                member: "stranger".to_string(),
+                roles: vec![OrgRole::Viewer],
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Std(StdError::generic_err("Not a group member"))
+        );
+
+        execute_as_org(
+            &mut deps,
+            ExecuteMsg::AcceptRequest {
+                address: Addr::unchecked(PAYEE),
+            },
+        )
+        .unwrap();
+
+        //// invoices raised by the org record the clerk ////
+        execute_as_org(&mut deps, create_invoice_msg()).unwrap();
+        let invoice_id = INVOICE_ID.load(&deps.storage).unwrap() - 1;
+        let invoice = INVOICE.load(&deps.storage, invoice_id).unwrap();
+        assert_eq!(invoice.from, org);
+        assert_eq!(invoice.created_by, Some(Addr::unchecked(CLERK)));
+
+        //// and so do invoices the org accepts and pays ////
+        let mut msg = create_invoice_msg();
+        if let ExecuteMsg::CreateInvoice { payee_address, .. } = &mut msg {
+            *payee_address = org.clone();
+        }
+        execute(deps.as_mut(), mock_env(), mock_info(PAYEE, &[]), msg).unwrap();
+        let invoice_id = INVOICE_ID.load(&deps.storage).unwrap() - 1;
+        execute_as_org(&mut deps, ExecuteMsg::AcceptInvoice { invoice_id }).unwrap();
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(CLERK, &coins(400, "uatom")),
+            ExecuteMsg::PayInvoice { invoice_id },
+        )
+        .unwrap();
+
+        let invoice = INVOICE.load(&deps.storage, invoice_id).unwrap();
+        assert_eq!(invoice.accepted_by, Some(Addr::unchecked(CLERK)));
+        let records = INVOICE_PAYMENTS.load(&deps.storage, &invoice_id).unwrap();
+        assert_eq!(records[0].payer, org);
+        assert_eq!(records[0].member, Some(Addr::unchecked(CLERK)));
+
+        let members = get_org_members(deps.as_ref(), org, None, None).unwrap();
+        assert_eq!(members[0].member, Addr::unchecked(CLERK));
+        assert_eq!(members[0].roles.len(), 3);
+    }
+
+    #[test]
+    fn removed_members_lose_their_roles() {
+        let mut deps = setup_org(vec![OrgRole::Viewer]);
+
+        //// viewers cannot act for the org ////
+        let err = execute_as_org(
+            &mut deps,
+            ExecuteMsg::AcceptRequest {
+                address: Addr::unchecked(PAYEE),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        let hook = ExecuteMsg::MemberChangedHook(MemberChangedHookMsg::one(MemberDiff::new(
+            CLERK,
+            Some(1),
+            None,
+        )));
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(CLERK, &[]),
+            hook.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        execute(deps.as_mut(), mock_env(), mock_info(GROUP, &[]), hook).unwrap();
+
+        let roles = ORG_ROLES
+            .may_load(
+                &deps.storage,
+                (&Addr::unchecked(GROUP), &Addr::unchecked(CLERK)),
+            )
+            .unwrap();
+        assert_eq!(roles, None);
+
+        //// roles left behind by a missed hook are void once the member is gone ////
+        let org = Addr::unchecked(GROUP);
+        let former = Addr::unchecked("former");
+        ORG_ROLES
+            .save(
+                &mut deps.storage,
+                (&org, &former),
+                &vec![OrgRole::Approver, OrgRole::Payer],
+            )
+            .unwrap();
+        let msg = ExecuteMsg::ExecuteAs {
+            org,
+            msg: Box::new(ExecuteMsg::AcceptRequest {
+                address: Addr::unchecked(PAYEE),
+            }),
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("former", &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+    }
+
+    #[test]
+    fn org_is_paid_at_its_payout_address() {
+        let mut deps = setup_org(vec![OrgRole::Issuer, OrgRole::Approver]);
+        let org = Addr::unchecked(GROUP);
+        execute_as_org(
+            &mut deps,
+            ExecuteMsg::AcceptRequest {
+                address: Addr::unchecked(PAYEE),
+            },
+        )
+        .unwrap();
+        execute_as_org(&mut deps, create_invoice_msg()).unwrap();
+        let invoice_id = INVOICE_ID.load(&deps.storage).unwrap() - 1;
+
+        //// the nft and the payments go to the treasury, not the group ////
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[]),
+            ExecuteMsg::AcceptInvoice { invoice_id },
+        )
+        .unwrap();
+        let transfer: Cw721ExecuteMsg<Empty, Empty> = Cw721ExecuteMsg::TransferNft {
+            recipient: TREASURY.to_string(),
+            token_id: invoice_id.to_string(),
+        };
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "nft".to_string(),
+                msg: to_binary(&transfer).unwrap(),
+                funds: vec![],
+            })
+        );
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &coins(400, "uatom")),
+            ExecuteMsg::PayInvoice { invoice_id },
+        )
+        .unwrap();
+        let payment = transfer_msg(
+            &Denom::Native("uatom".to_string()),
+            &Addr::unchecked(TREASURY),
+            Uint128::new(400),
+        )
+        .unwrap();
+        assert_eq!(res.messages[0].msg, payment);
+
+        //// only the group admin moves the payout ////
+        let set_payout = ExecuteMsg::SetOrgPayout {
+            org: org.clone(),
+            payout: "vault".to_string(),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(CLERK, &[]),
+            set_payout.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Std(StdError::generic_err(
+                "Only the group admin can manage the org"
+            ))
+        );
+        execute(deps.as_mut(), mock_env(), mock_info(ADMIN, &[]), set_payout).unwrap();
+        let profile = get_org(deps.as_ref(), org).unwrap();
+        assert_eq!(profile.payout, Addr::unchecked("vault"));
+    }
+}
@@ -1,5 +1,9 @@
+use crate::contact::remove_contact_entry;
 use crate::state::*;
-use cosmwasm_std::{to_binary, Addr, DepsMut, Env, MessageInfo, Response, StdError};
+use cosmwasm_std::{
+    to_binary, Addr, Binary, DepsMut, Empty, Env, MessageInfo, Order, Response, StdError,
+    StdResult, Storage,
+};
 
 use crate::error::ContractError;
 
@@ -16,45 +20,44 @@ pub fn create_request(
 
     //// check if already requested ////
 
-    let mut contact_info = CONTACT_INFO
+    let contact_info = CONTACT_INFO
         .may_load(deps.storage, &info.sender)?
         .ok_or_else(|| StdError::generic_err("Profile does not exist"))?;
 
-    //// iterate over sent request to check if address exist in contact_info.sent_requests ////
-    if contact_info.sent_requests.contains(&address) {
+    contact_info.ensure_active()?;
+
+    if SENT_REQUESTS.has(deps.storage, (&info.sender, &address)) {
         return Err(StdError::generic_err("Already sent request").into());
     }
 
-    //// check if already in my contact list or already existing alias ////
-    if contact_info.sent_requests.contains(&address) {
+    //// check if already in my contact list ////
+    if CONTACTS.has(deps.storage, (&info.sender, &address)) {
         return Err(StdError::generic_err("Already in contact").into());
     }
 
-    //// append new contact to sent_requests ////
-    contact_info.sent_requests.push(address.clone());
-
-    //// save updated contact_info ////
-    CONTACT_INFO.save(deps.storage, &info.sender, &contact_info)?;
-
-    //// create request message ////
-
     //// Loads the requested contact information for the given address from storage.
     //// Returns an error if the contact information cannot be loaded.
 
-    let mut requested_contact_info = CONTACT_INFO
+    let requested_contact_info = CONTACT_INFO
         .may_load(deps.storage, &address)?
-        .ok_or_else(|| StdError::generic_err("Recipient Profile does not exist"))?; // Assuming ContactInfo::new() is defined.
-
-    requested_contact_info
-        .received_requests
-        .push(info.sender.clone());
-
-    CONTACT_INFO.save(deps.storage, &address, &requested_contact_info)?;
+        .ok_or_else(|| StdError::generic_err("Recipient Profile does not exist"))?;
+    requested_contact_info.ensure_active()?;
+    ensure_not_blocked(deps.storage, &info.sender, &address)?;
+
+    //// a request to someone who already asked for us accepts theirs ////
+    let connected = RECEIVED_REQUESTS.has(deps.storage, (&info.sender, &address));
+    if connected {
+        connect(deps.storage, &info.sender, &address)?;
+    } else {
+        SENT_REQUESTS.save(deps.storage, (&info.sender, &address), &Empty {})?;
+        RECEIVED_REQUESTS.save(deps.storage, (&address, &info.sender), &Empty {})?;
+    }
 
     Ok(Response::new()
         .add_attribute("method", "create_request")
         .add_attribute("sender", info.sender)
-        .add_attribute("receiver", address))
+        .add_attribute("receiver", address)
+        .add_attribute("connected", connected.to_string()))
 }
 
 pub fn accept_request(
@@ -69,60 +72,159 @@ pub fn accept_request(
     }
 
     //// check if request exist ////
-    let mut contact_info = CONTACT_INFO
-        .may_load(deps.storage, &info.sender)?
-        .ok_or_else(|| StdError::generic_err("Profile does not exist"))?;
-
-    //// check if request exist ////
-    let mut request_exist = false;
-    let mut index = 0;
-    for contact in contact_info.received_requests.iter() {
-        if contact == address {
-            request_exist = true;
-            break;
-        }
-        index += 1;
+    if !CONTACT_INFO.has(deps.storage, &info.sender) {
+        return Err(StdError::generic_err("Profile does not exist").into());
     }
-    if !request_exist {
+    if !RECEIVED_REQUESTS.has(deps.storage, (&info.sender, &address)) {
         return Err(StdError::generic_err("No request found").into());
     }
+    if !CONTACT_INFO.has(deps.storage, &address) {
+        return Err(StdError::generic_err("Requestor Profile does not exist").into());
+    }
 
-    //// remove request from received_requests ////
-    contact_info.received_requests.remove(index);
+    connect(deps.storage, &info.sender, &address)?;
 
-    //// add contact to contacts ////
-    contact_info.contacts.push(address.clone());
+    Ok(Response::new()
+        .add_attribute("method", "accept_request")
+        .add_attribute("receiver", info.sender))
+}
 
-    //// save updated contact_info ////
-    CONTACT_INFO.save(deps.storage, &info.sender, &contact_info)?;
+/// drops the pending requests between two profiles and adds them to each other's contacts
+fn connect(storage: &mut dyn Storage, left: &Addr, right: &Addr) -> StdResult<()> {
+    disconnect(storage, left, right)?;
+    CONTACTS.save(storage, (left, right), &Contact::new(right.clone()))?;
+    CONTACTS.save(storage, (right, left), &Contact::new(left.clone()))
+}
 
-    //// remove send request of the sender ////
+/// removes any request or contact between two profiles
+fn disconnect(storage: &mut dyn Storage, left: &Addr, right: &Addr) -> StdResult<()> {
+    forget(storage, left, right)?;
+    forget(storage, right, left)
+}
 
-    let mut requested_contact_info = CONTACT_INFO
-        .may_load(deps.storage, &address)?
-        .ok_or_else(|| StdError::generic_err("Requestor Profile does not exist"))?; // Assuming ContactInfo::new() is defined.
+/// drops the request `owner` sent to `address` and its contact entry and alias for `address`
+fn forget(storage: &mut dyn Storage, owner: &Addr, address: &Addr) -> StdResult<()> {
+    SENT_REQUESTS.remove(storage, (owner, address));
+    RECEIVED_REQUESTS.remove(storage, (address, owner));
+    remove_contact_entry(storage, owner, address)
+}
 
+pub fn reject_request(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    address: Addr,
+) -> Result<Response, ContractError> {
+    if !CONTACT_INFO.has(deps.storage, &info.sender) {
+        return Err(StdError::generic_err("Profile does not exist").into());
+    }
+    if !RECEIVED_REQUESTS.has(deps.storage, (&info.sender, &address)) {
+        return Err(StdError::generic_err("No request found").into());
+    }
+    RECEIVED_REQUESTS.remove(deps.storage, (&info.sender, &address));
+    SENT_REQUESTS.remove(deps.storage, (&address, &info.sender));
 
+    Ok(Response::new()
+        .add_attribute("method", "reject_request")
+        .add_attribute("sender", info.sender)
+        .add_attribute("requestor", address))
+}
 
-    let mut index = 0;
-    for contact in requested_contact_info.sent_requests.iter() {
-        if contact == info.sender {
-            break;
-        }
-        index += 1;
+pub fn cancel_request(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    address: Addr,
+) -> Result<Response, ContractError> {
+    if !CONTACT_INFO.has(deps.storage, &info.sender) {
+        return Err(StdError::generic_err("Profile does not exist").into());
+    }
+    if !SENT_REQUESTS.has(deps.storage, (&info.sender, &address)) {
+        return Err(StdError::generic_err("No request found").into());
     }
+    SENT_REQUESTS.remove(deps.storage, (&info.sender, &address));
+    RECEIVED_REQUESTS.remove(deps.storage, (&address, &info.sender));
 
-    requested_contact_info.sent_requests.remove(index);
+    Ok(Response::new()
+        .add_attribute("method", "cancel_request")
+        .add_attribute("sender", info.sender)
+        .add_attribute("receiver", address))
+}
 
-    //// add contact to contacts ////
-    requested_contact_info.contacts.push(info.sender.clone());
+pub fn remove_contact(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    address: Addr,
+) -> Result<Response, ContractError> {
+    if !CONTACT_INFO.has(deps.storage, &info.sender) {
+        return Err(StdError::generic_err("Profile does not exist").into());
+    }
+    if !CONTACTS.has(deps.storage, (&info.sender, &address)) {
+        return Err(StdError::generic_err("Not in contact list").into());
+    }
+    drop_contact(deps.storage, &info.sender, &address)?;
 
-    //// save updated contact_info ////
-    CONTACT_INFO.save(deps.storage, &address, &requested_contact_info)?;
+    Ok(Response::new()
+        .add_attribute("method", "remove_contact")
+        .add_attribute("sender", info.sender)
+        .add_attribute("contact", address))
+}
+
+/// removes requests, contacts, address book entries and shared PII between `owner` and `address`
+fn drop_contact(storage: &mut dyn Storage, owner: &Addr, address: &Addr) -> StdResult<()> {
+    disconnect(storage, owner, address)?;
+    SHARED_PII.remove(storage, (owner, address));
+    SHARED_PII.remove(storage, (address, owner));
+    Ok(())
+}
+
+/// blocks contact requests and invoices between the sender and `address`
+pub fn block_address(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    address: Addr,
+) -> Result<Response, ContractError> {
+    if info.sender == address {
+        return Err(ContractError::Unauthorized {});
+    }
+    if !CONTACT_INFO.has(deps.storage, &info.sender) {
+        return Err(StdError::generic_err("Profile does not exist").into());
+    }
+
+    drop_contact(deps.storage, &info.sender, &address)?;
+    BLOCKED.save(deps.storage, (&info.sender, &address), &Empty {})?;
 
     Ok(Response::new()
-        .add_attribute("method", "accept_request")
-        .add_attribute("receiver", info.sender))
+        .add_attribute("method", "block_address")
+        .add_attribute("sender", info.sender)
+        .add_attribute("blocked", address))
+}
+
+pub fn unblock_address(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    address: Addr,
+) -> Result<Response, ContractError> {
+    if !BLOCKED.has(deps.storage, (&info.sender, &address)) {
+        return Err(StdError::generic_err("Address is not blocked").into());
+    }
+    BLOCKED.remove(deps.storage, (&info.sender, &address));
+
+    Ok(Response::new()
+        .add_attribute("method", "unblock_address")
+        .add_attribute("sender", info.sender)
+        .add_attribute("unblocked", address))
+}
+
+/// errors if either address has blocked the other
+pub fn ensure_not_blocked(storage: &dyn Storage, left: &Addr, right: &Addr) -> StdResult<()> {
+    if BLOCKED.has(storage, (left, right)) || BLOCKED.has(storage, (right, left)) {
+        return Err(StdError::generic_err("Address is blocked"));
+    }
+    Ok(())
 }
 
 pub fn create_profile(
@@ -153,13 +255,12 @@ pub fn create_profile(
         phone_number: phone_number.clone(),
         owner: info.sender.clone(),
         email_id: email_id.clone(),
-        sent_requests: vec![],
-        received_requests: vec![],
-        contacts: vec![],
-        ///// default KYC status is set as VERIFIED now to bypass testing /////
-        kyc_status: KYCStatus::Approved,
-        assigned_invoices: vec![],
-        generated_invoices: vec![],
+        //// a KYC provider has to verify the profile ////
+        kyc_status: KYCStatus::Unverified,
+        kyc_record: None,
+        private_pii: None,
+        viewing_key: None,
+        profile_status: ProfileStatus::Active,
     };
 
     CONTACT_INFO.save(deps.storage, &info.sender, &new_contact_info)?;
@@ -168,3 +269,674 @@ pub fn create_profile(
         .add_attribute("method", "create_profile")
         .add_attribute("sender", info.sender))
 }
+
+/// updates the given fields, identity changes send an approved KYC back to `ReVerify`
+pub fn update_profile(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    update: ProfileUpdate,
+) -> Result<Response, ContractError> {
+    //// do not accept funds ////
+    if !info.funds.is_empty() {
+        return Err(StdError::generic_err("Funds not accepted").into());
+    }
+
+    let mut contact_info = CONTACT_INFO
+        .may_load(deps.storage, &info.sender)?
+        .ok_or_else(|| StdError::generic_err("Profile does not exist"))?;
+    contact_info.ensure_active()?;
+
+    let ProfileUpdate {
+        name,
+        email_id,
+        phone_number,
+        company_name,
+        address,
+        commitments,
+        encrypted_pii,
+    } = update;
+
+    let mut identity_changed = false;
+    match contact_info.private_pii.as_mut() {
+        Some(private_pii) => {
+            if name.is_some() || email_id.is_some() || phone_number.is_some() || address.is_some() {
+                return Err(StdError::generic_err("Private profiles only take commitments").into());
+            }
+            if let Some(commitments) = commitments {
+                commitments.validate()?;
+                identity_changed = commitments.name != private_pii.commitments.name
+                    || commitments.address != private_pii.commitments.address;
+                private_pii.commitments = commitments;
+            }
+            if let Some(encrypted_pii) = encrypted_pii {
+                private_pii.encrypted = Some(encrypted_pii);
+            }
+        }
+        None => {
+            if commitments.is_some() || encrypted_pii.is_some() {
+                return Err(
+                    StdError::generic_err("Public profiles do not take commitments").into(),
+                );
+            }
+            if let Some(name) = name {
+                identity_changed |= name != contact_info.name;
+                contact_info.name = name;
+            }
+            if let Some(address) = address {
+                identity_changed |= address != contact_info.address;
+                contact_info.address = address;
+            }
+            if let Some(email_id) = email_id {
+                contact_info.email_id = email_id;
+            }
+            if let Some(phone_number) = phone_number {
+                contact_info.phone_number = phone_number;
+            }
+        }
+    }
+    if let Some(company_name) = company_name {
+        identity_changed |= company_name != contact_info.company_name;
+        contact_info.company_name = company_name;
+    }
+
+    //// the provider has to confirm the new identity ////
+    if identity_changed && contact_info.current_kyc_status(&env.block) == KYCStatus::Approved {
+        contact_info.kyc_status = KYCStatus::ReVerify;
+    }
+    CONTACT_INFO.save(deps.storage, &info.sender, &contact_info)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "update_profile")
+        .add_attribute("sender", info.sender)
+        .add_attribute(
+            "kyc_status",
+            contact_info.current_kyc_status(&env.block).key(),
+        ))
+}
+
+/// stops the profile from taking part in new invoices and contact requests
+pub fn deactivate_profile(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let mut contact_info = CONTACT_INFO
+        .may_load(deps.storage, &info.sender)?
+        .ok_or_else(|| StdError::generic_err("Profile does not exist"))?;
+    contact_info.ensure_active()?;
+
+    contact_info.profile_status = ProfileStatus::Deactivated;
+    CONTACT_INFO.save(deps.storage, &info.sender, &contact_info)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "deactivate_profile")
+        .add_attribute("sender", info.sender))
+}
+
+/// wipes the PII of a deactivated profile, invoices only reference the address and stay intact
+pub fn erase_profile(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    address: Addr,
+) -> Result<Response, ContractError> {
+    let admin_list = ADMIN_LIST.load(deps.storage)?;
+    if !admin_list.is_admin(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut contact_info = CONTACT_INFO
+        .may_load(deps.storage, &address)?
+        .ok_or_else(|| StdError::generic_err("Profile does not exist"))?;
+
+    //// the owner consents by deactivating first ////
+    if contact_info.profile_status != ProfileStatus::Deactivated {
+        return Err(StdError::generic_err("Profile must be deactivated first").into());
+    }
+
+    //// contacts, pending requests and shared copies in both directions ////
+    let mut counterparties = CONTACTS
+        .prefix(&address)
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for requests in [SENT_REQUESTS, RECEIVED_REQUESTS] {
+        for other in requests
+            .prefix(&address)
+            .keys(deps.storage, None, None, Order::Ascending)
+        {
+            counterparties.push(other?);
+        }
+    }
+    for other in counterparties.iter() {
+        drop_contact(deps.storage, &address, other)?;
+    }
+
+    contact_info.name = String::new();
+    contact_info.company_name = String::new();
+    contact_info.address = String::new();
+    contact_info.phone_number = String::new();
+    contact_info.email_id = String::new();
+    contact_info.private_pii = None;
+    contact_info.viewing_key = None;
+    //// attestations reference the erased identity ////
+    contact_info.kyc_status = KYCStatus::Unverified;
+    contact_info.kyc_record = None;
+    contact_info.profile_status = ProfileStatus::Erased;
+    CONTACT_INFO.save(deps.storage, &address, &contact_info)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "erase_profile")
+        .add_attribute("sender", info.sender)
+        .add_attribute("address", address))
+}
+
+/// creates a profile that only stores commitments to its PII, and optionally an encrypted copy
+pub fn create_private_profile(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    company_name: String,
+    commitments: PiiCommitments,
+    encrypted_pii: Option<Binary>,
+    viewing_key: Option<Binary>,
+) -> Result<Response, ContractError> {
+    //// do not accept funds ////
+    if !info.funds.is_empty() {
+        return Err(StdError::generic_err("Funds not accepted").into());
+    }
+
+    ///// only create profile if not already created /////
+    if CONTACT_INFO.has(deps.storage, &info.sender) {
+        return Err(StdError::generic_err("Profile already exist").into());
+    }
+
+    commitments.validate()?;
+
+    let new_contact_info = ContactInfo {
+        name: String::new(),
+        company_name,
+        address: String::new(),
+        phone_number: String::new(),
+        owner: info.sender.clone(),
+        email_id: String::new(),
+        kyc_status: KYCStatus::Unverified,
+        kyc_record: None,
+        private_pii: Some(PrivatePii {
+            commitments,
+            encrypted: encrypted_pii,
+        }),
+        viewing_key,
+        profile_status: ProfileStatus::Active,
+    };
+
+    CONTACT_INFO.save(deps.storage, &info.sender, &new_contact_info)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "create_private_profile")
+        .add_attribute("sender", info.sender))
+}
+
+pub fn set_viewing_key(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    viewing_key: Binary,
+) -> Result<Response, ContractError> {
+    let mut contact_info = CONTACT_INFO
+        .may_load(deps.storage, &info.sender)?
+        .ok_or_else(|| StdError::generic_err("Profile does not exist"))?;
+
+    contact_info.viewing_key = Some(viewing_key);
+    CONTACT_INFO.save(deps.storage, &info.sender, &contact_info)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "set_viewing_key")
+        .add_attribute("sender", info.sender))
+}
+
+/// stores a copy of the sender's PII encrypted to the viewing key of one of its contacts
+pub fn share_pii(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    contact: Addr,
+    encrypted_pii: Binary,
+) -> Result<Response, ContractError> {
+    if !CONTACT_INFO.has(deps.storage, &info.sender) {
+        return Err(StdError::generic_err("Profile does not exist").into());
+    }
+
+    //// PII is only shared once a contact request has been accepted ////
+    if !CONTACTS.has(deps.storage, (&info.sender, &contact)) {
+        return Err(StdError::generic_err("Receiver not in contact list").into());
+    }
+
+    let viewer_info = CONTACT_INFO.load(deps.storage, &contact)?;
+    if viewer_info.viewing_key.is_none() {
+        return Err(StdError::generic_err("Contact has no viewing key").into());
+    }
+
+    SHARED_PII.save(deps.storage, (&info.sender, &contact), &encrypted_pii)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "share_pii")
+        .add_attribute("sender", info.sender)
+        .add_attribute("receiver", contact))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::{execute, query};
+    use crate::helpers::pii_commitment;
+    use crate::msg::{ExecuteMsg, QueryMsg};
+    use crate::testing::*;
+    use cosmwasm_std::from_binary;
+    use cosmwasm_std::testing::{mock_env, mock_info};
+    use cw_storage_plus::Map;
+    use serde::de::DeserializeOwned;
+    use serde::Serialize;
+
+    const CAROL: &str = "carol";
+
+    fn verify(deps: &MockDeps, field: PiiField, value: &str, salt: &str) -> bool {
+        let msg = QueryMsg::VerifyPii {
+            address: Addr::unchecked(CAROL),
+            field,
+            value: value.to_string(),
+            salt: salt.to_string(),
+        };
+        from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn private_profiles_only_store_commitments() {
+        let mut deps = setup();
+        let commitments = PiiCommitments {
+            name: pii_commitment("s1", "Carol"),
+            email_id: pii_commitment("s2", "carol@example.com"),
+            phone_number: pii_commitment("s3", "5550100"),
+            address: pii_commitment("s4", "1 Main St"),
+        };
+        let msg = ExecuteMsg::CreatePrivateProfile {
+            company_name: "carol co".to_string(),
+            commitments,
+            encrypted_pii: Some(Binary::from(b"sealed".to_vec())),
+            viewing_key: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(CAROL, &[]), msg).unwrap();
+
+        let contact_info = CONTACT_INFO
+            .load(&deps.storage, &Addr::unchecked(CAROL))
+            .unwrap();
+        assert_eq!(contact_info.email_id, "");
+        assert!(verify(&deps, PiiField::EmailId, "carol@example.com", "s2"));
+        assert!(!verify(&deps, PiiField::EmailId, "carol@example.com", "s1"));
+        assert!(!verify(&deps, PiiField::PhoneNumber, "5550101", "s3"));
+        assert!(!verify(&deps, PiiField::PhoneNumber, "35550100", ""));
+
+        //// PII is shared with accepted contacts that published a viewing key ////
+        let share = ExecuteMsg::SharePii {
+            contact: Addr::unchecked(ISSUER),
+            encrypted_pii: Binary::from(b"sealed for issuer".to_vec()),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(CAROL, &[]),
+            share.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Std(StdError::generic_err("Receiver not in contact list"))
+        );
+
+        let msgs = vec![
+            (
+                CAROL,
+                ExecuteMsg::CreateRequest {
+                    address: Addr::unchecked(ISSUER),
+                },
+            ),
+            (
+                ISSUER,
+                ExecuteMsg::AcceptRequest {
+                    address: Addr::unchecked(CAROL),
+                },
+            ),
+        ];
+        for (sender, msg) in msgs {
+            execute(deps.as_mut(), mock_env(), mock_info(sender, &[]), msg).unwrap();
+        }
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(CAROL, &[]),
+            share.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Std(StdError::generic_err("Contact has no viewing key"))
+        );
+
+        let msg = ExecuteMsg::SetViewingKey {
+            viewing_key: Binary::from(b"issuer pubkey".to_vec()),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(ISSUER, &[]), msg).unwrap();
+        execute(deps.as_mut(), mock_env(), mock_info(CAROL, &[]), share).unwrap();
+
+        let msg = QueryMsg::GetSharedPii {
+            owner: Addr::unchecked(CAROL),
+            viewer: Addr::unchecked(ISSUER),
+        };
+        let shared: Option<Binary> =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(shared, Some(Binary::from(b"sealed for issuer".to_vec())));
+    }
+
+    #[test]
+    fn identity_updates_require_kyc_again() {
+        let mut deps = setup();
+        let update = ProfileUpdate {
+            phone_number: Some("5550100".to_string()),
+            ..ProfileUpdate::default()
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            ExecuteMsg::UpdateProfile { update },
+        )
+        .unwrap();
+        let contact_info = CONTACT_INFO
+            .load(&deps.storage, &Addr::unchecked(ISSUER))
+            .unwrap();
+        assert_eq!(contact_info.phone_number, "5550100");
+        assert_eq!(contact_info.kyc_status, KYCStatus::Approved);
+
+        let update = ProfileUpdate {
+            address: Some("2 Side St".to_string()),
+            ..ProfileUpdate::default()
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            ExecuteMsg::UpdateProfile { update },
+        )
+        .unwrap();
+        let contact_info = CONTACT_INFO
+            .load(&deps.storage, &Addr::unchecked(ISSUER))
+            .unwrap();
+        assert_eq!(contact_info.address, "2 Side St");
+        assert_eq!(contact_info.kyc_status, KYCStatus::ReVerify);
+    }
+
+    fn linked<T: Serialize + DeserializeOwned>(
+        deps: &MockDeps,
+        map: Map<(&Addr, &Addr), T>,
+        owner: &str,
+        address: &str,
+    ) -> bool {
+        let (owner, address) = (Addr::unchecked(owner), Addr::unchecked(address));
+        map.has(&deps.storage, (&owner, &address))
+    }
+
+    #[test]
+    fn deactivated_profiles_are_erased_by_admins() {
+        let (mut deps, invoice_id) = setup_with_invoice(None);
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(CAROL, &[]),
+            create_profile_msg(),
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(CAROL, &[]),
+            ExecuteMsg::CreateRequest {
+                address: Addr::unchecked(PAYEE),
+            },
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[]),
+            ExecuteMsg::DeactivateProfile {},
+        )
+        .unwrap();
+
+        //// no new invoices, the existing one stays queryable ////
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            create_invoice_msg(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Std(StdError::generic_err("Profile payee is deactivated"))
+        );
+        query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetInvoice { invoice_id },
+        )
+        .unwrap();
+
+        let erase = ExecuteMsg::EraseProfile {
+            address: Addr::unchecked(PAYEE),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[]),
+            erase.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        execute(deps.as_mut(), mock_env(), mock_info(ADMIN, &[]), erase).unwrap();
+
+        let contact_info = CONTACT_INFO
+            .load(&deps.storage, &Addr::unchecked(PAYEE))
+            .unwrap();
+        assert_eq!(contact_info.name, "");
+        assert_eq!(contact_info.email_id, "");
+        assert_eq!(contact_info.kyc_record, None);
+        assert_eq!(contact_info.profile_status, ProfileStatus::Erased);
+        //// counterparties no longer list the erased address ////
+        assert!(!linked(&deps, CONTACTS, ISSUER, PAYEE));
+        assert!(!linked(&deps, SENT_REQUESTS, CAROL, PAYEE));
+        assert!(!linked(&deps, RECEIVED_REQUESTS, PAYEE, CAROL));
+        let invoice = INVOICE.load(&deps.storage, invoice_id).unwrap();
+        assert_eq!(invoice.payee_address, Addr::unchecked(PAYEE));
+    }
+
+    #[test]
+    fn mutual_requests_connect_and_duplicates_are_refused() {
+        let mut deps = setup();
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(CAROL, &[]),
+            create_profile_msg(),
+        )
+        .unwrap();
+
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            ExecuteMsg::CreateRequest {
+                address: Addr::unchecked(PAYEE),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Std(StdError::generic_err("Already in contact"))
+        );
+
+        let msgs = vec![
+            (ISSUER, CAROL),
+            //// carol asking back accepts the issuer's request ////
+            (CAROL, ISSUER),
+        ];
+        for (sender, address) in msgs {
+            let msg = ExecuteMsg::CreateRequest {
+                address: Addr::unchecked(address),
+            };
+            execute(deps.as_mut(), mock_env(), mock_info(sender, &[]), msg).unwrap();
+        }
+        assert!(linked(&deps, CONTACTS, CAROL, ISSUER));
+        assert!(linked(&deps, CONTACTS, ISSUER, CAROL));
+        assert!(!linked(&deps, SENT_REQUESTS, ISSUER, CAROL));
+        assert!(!linked(&deps, RECEIVED_REQUESTS, CAROL, ISSUER));
+
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(CAROL, &[]),
+            ExecuteMsg::RemoveContact {
+                address: Addr::unchecked(ISSUER),
+            },
+        )
+        .unwrap();
+        assert!(!linked(&deps, CONTACTS, ISSUER, CAROL));
+    }
+
+    #[test]
+    fn requests_are_rejected_or_cancelled() {
+        let mut deps = setup();
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(CAROL, &[]),
+            create_profile_msg(),
+        )
+        .unwrap();
+
+        let request = ExecuteMsg::CreateRequest {
+            address: Addr::unchecked(ISSUER),
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(CAROL, &[]),
+            request.clone(),
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            ExecuteMsg::RejectRequest {
+                address: Addr::unchecked(CAROL),
+            },
+        )
+        .unwrap();
+        assert!(!linked(&deps, SENT_REQUESTS, CAROL, ISSUER));
+        assert!(!linked(&deps, RECEIVED_REQUESTS, ISSUER, CAROL));
+
+        //// a rejected request can be sent again, and withdrawn ////
+        execute(deps.as_mut(), mock_env(), mock_info(CAROL, &[]), request).unwrap();
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(CAROL, &[]),
+            ExecuteMsg::CancelRequest {
+                address: Addr::unchecked(ISSUER),
+            },
+        )
+        .unwrap();
+        assert!(!linked(&deps, SENT_REQUESTS, CAROL, ISSUER));
+        assert!(!linked(&deps, RECEIVED_REQUESTS, ISSUER, CAROL));
+
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            ExecuteMsg::AcceptRequest {
+                address: Addr::unchecked(CAROL),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Std(StdError::generic_err("No request found"))
+        );
+    }
+
+    #[test]
+    fn blocked_addresses_cannot_request_or_invoice() {
+        let mut deps = setup();
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[]),
+            ExecuteMsg::BlockAddress {
+                address: Addr::unchecked(ISSUER),
+            },
+        )
+        .unwrap();
+        assert!(!linked(&deps, CONTACTS, ISSUER, PAYEE));
+
+        let blocked = ContractError::Std(StdError::generic_err("Address is blocked"));
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            ExecuteMsg::CreateRequest {
+                address: Addr::unchecked(PAYEE),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err, blocked);
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            create_invoice_msg(),
+        )
+        .unwrap_err();
+        assert_eq!(err, blocked);
+
+        let msg = QueryMsg::GetBlockedAddresses {
+            address: Addr::unchecked(PAYEE),
+            start_after: None,
+            limit: None,
+        };
+        let addresses: Vec<Addr> =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(addresses, vec![Addr::unchecked(ISSUER)]);
+
+        //// unblocking does not restore the contact ////
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[]),
+            ExecuteMsg::UnblockAddress {
+                address: Addr::unchecked(ISSUER),
+            },
+        )
+        .unwrap();
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            create_invoice_msg(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Std(StdError::generic_err("Receiver not in contact list"))
+        );
+    }
+}
@@ -1,19 +1,176 @@
 use crate::state::*;
 use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::{to_binary, Addr, Coin, Deps, StdResult};
+use crate::delegation::check_delegation;
+use crate::helpers::pii_commitment;
+use crate::msg::ExecuteMsg;
+use cosmwasm_std::{
+    from_binary, to_binary, Addr, Binary, Coin, CosmosMsg, Deps, Env, Order, StdError, StdResult,
+    Timestamp, Uint128, WasmMsg,
+};
+use cw1::CanExecuteResponse;
+use cw3::Cw3Contract;
+use cw4::Cw4Contract;
+use cw_storage_plus::{Bound, Map, MultiIndex};
+use cw_utils::Expiration;
 
-pub fn get_invoice(deps: Deps, invoice_id: u64) -> StdResult<Invoice> {
-    let invoice = INVOICE.load(deps.storage, &invoice_id)?;
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 30;
+
+pub fn get_invoice(deps: Deps, env: Env, invoice_id: u64) -> StdResult<Invoice> {
+    let mut invoice = INVOICE.load(deps.storage, invoice_id)?;
+    invoice.due_amount = invoice.amount_due(&env.block).total;
+    invoice.status = invoice.current_status(&env.block);
     Ok(invoice)
 }
 
+pub fn get_amount_due(deps: Deps, env: Env, invoice_id: u64) -> StdResult<AmountDue> {
+    let invoice = INVOICE.load(deps.storage, invoice_id)?;
+    Ok(invoice.amount_due(&env.block))
+}
+
+pub fn get_escrow(deps: Deps, invoice_id: u64) -> StdResult<Option<EscrowBalance>> {
+    let escrow = ESCROW.may_load(deps.storage, &invoice_id)?;
+    Ok(escrow)
+}
+
+pub fn get_invoice_payments(deps: Deps, invoice_id: u64) -> StdResult<Vec<PaymentRecord>> {
+    let records = INVOICE_PAYMENTS
+        .may_load(deps.storage, &invoice_id)?
+        .unwrap_or_default();
+    Ok(records)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct InstallmentResponse {
+    pub amount: Uint128,
+    pub due_date: Timestamp,
+    pub paid: Uint128,
+    pub is_paid: bool,
+    pub is_overdue: bool,
+}
+
+pub fn get_installments(
+    deps: Deps,
+    env: Env,
+    invoice_id: u64,
+) -> StdResult<Vec<InstallmentResponse>> {
+    let invoice = INVOICE.load(deps.storage, invoice_id)?;
+    let installments = invoice
+        .installments
+        .iter()
+        .map(|installment| InstallmentResponse {
+            amount: installment.amount,
+            due_date: installment.due_date,
+            paid: installment.paid,
+            is_paid: installment.is_paid(),
+            //// nothing is overdue on a closed invoice ////
+            is_overdue: !invoice.status.is_closed() && installment.is_overdue(&env.block),
+        })
+        .collect();
+    Ok(installments)
+}
+
+pub fn get_tax_summary(deps: Deps, invoice_id: u64) -> StdResult<TaxSummary> {
+    let invoice = INVOICE.load(deps.storage, invoice_id)?;
+    let mut summary = TaxSummary::from_line_items(&invoice.line_items);
+
+    //// invoices without line items are untaxed ////
+    if invoice.line_items.is_empty() {
+        summary.subtotal = invoice.receivable.amount;
+        summary.total = invoice.receivable.amount;
+    }
+    Ok(summary)
+}
+
+/// next invoice a recurring template will issue
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct RecurringInstance {
+    pub issue_at: Timestamp,
+    /// due date if the invoice is triggered now or at `issue_at`, whichever is later
+    pub due_date: Timestamp,
+    pub receivable: Coin,
+    /// true if `TriggerRecurring` would issue it now
+    pub is_due: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct RecurringResponse {
+    pub recurring: RecurringInvoice,
+    /// `None` once the template will not issue any more invoices
+    pub next_instance: Option<RecurringInstance>,
+}
+
+pub fn get_recurring(deps: Deps, env: Env, recurring_id: u64) -> StdResult<RecurringResponse> {
+    let recurring = RECURRING_INVOICE.load(deps.storage, recurring_id)?;
+    let next_instance = if recurring.is_finished() {
+        None
+    } else {
+        let issue_at = recurring.next_issue_at;
+        Some(RecurringInstance {
+            issue_at,
+            due_date: issue_at
+                .max(env.block.time)
+                .plus_seconds(recurring.payment_days * SECONDS_PER_DAY),
+            receivable: recurring.receivable.clone(),
+            is_due: env.block.time >= issue_at,
+        })
+    };
+    Ok(RecurringResponse {
+        recurring,
+        next_instance,
+    })
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct RecurringListResponse {
+    pub issued: Vec<RecurringInvoice>,
+    pub received: Vec<RecurringInvoice>,
+}
+
+/// returns a page of the recurring templates the address issues and pays
+pub fn get_recurring_invoices(
+    deps: Deps,
+    address: Addr,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<RecurringListResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let page = |index: &MultiIndex<Addr, RecurringInvoice, u64>| {
+        index
+            .prefix(address.clone())
+            .range(
+                deps.storage,
+                start_after.map(Bound::exclusive),
+                None,
+                Order::Ascending,
+            )
+            .take(limit)
+            .map(|item| item.map(|(_, recurring)| recurring))
+            .collect::<StdResult<Vec<_>>>()
+    };
+    Ok(RecurringListResponse {
+        issued: page(&RECURRING_INVOICE.idx.issuer)?,
+        received: page(&RECURRING_INVOICE.idx.payee)?,
+    })
+}
+
 pub fn get_config(deps: Deps) -> StdResult<Config> {
     let config = CONFIG.load(deps.storage)?;
     Ok(config)
 }
 
+pub fn get_admin_list(deps: Deps) -> StdResult<AdminList> {
+    let admin_list = ADMIN_LIST.load(deps.storage)?;
+    Ok(admin_list)
+}
+
 pub fn get_latest_invoice_id(deps: Deps) -> StdResult<u64> {
     let id = INVOICE_ID.load(deps.storage)?;
     Ok(id)
@@ -24,67 +181,189 @@ pub fn get_contact_info(deps: Deps, address: Addr) -> StdResult<ContactInfo> {
     Ok(contact_info)
 }
 
-
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
+pub struct OrgMemberResponse {
+    pub member: Addr,
+    pub weight: u64,
+    pub roles: Vec<OrgRole>,
+}
+
+pub fn get_org(deps: Deps, org: Addr) -> StdResult<OrgProfile> {
+    let org = ORGS.load(deps.storage, &org)?;
+    Ok(org)
+}
+
+pub fn get_org_members(
+    deps: Deps,
+    org: Addr,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<Vec<OrgMemberResponse>> {
+    if !ORGS.has(deps.storage, &org) {
+        return Err(StdError::generic_err("Org does not exist"));
+    }
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+    Cw4Contract::new(org.clone())
+        .list_members(&deps.querier, start_after, Some(limit))?
+        .into_iter()
+        .map(|member| {
+            let addr = Addr::unchecked(member.addr);
+            let roles = ORG_ROLES
+                .may_load(deps.storage, (&org, &addr))?
+                .unwrap_or_default();
+            Ok(OrgMemberResponse {
+                member: addr,
+                weight: member.weight,
+                roles,
+            })
+        })
+        .collect()
+}
+
+pub fn get_blocked_addresses(
+    deps: Deps,
+    address: Addr,
+    start_after: Option<Addr>,
+    limit: Option<u32>,
+) -> StdResult<Vec<Addr>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_ref().map(Bound::exclusive);
+    BLOCKED
+        .prefix(&address)
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect()
+}
+
+pub fn get_shared_pii(deps: Deps, owner: Addr, viewer: Addr) -> StdResult<Option<Binary>> {
+    let shared = SHARED_PII.may_load(deps.storage, (&owner, &viewer))?;
+    Ok(shared)
+}
+
+pub fn verify_pii(
+    deps: Deps,
+    address: Addr,
+    field: PiiField,
+    value: String,
+    salt: String,
+) -> StdResult<bool> {
+    let contact_info = CONTACT_INFO.load(deps.storage, &address)?;
+    let verified = match &contact_info.private_pii {
+        Some(private_pii) => *private_pii.commitments.get(&field) == pii_commitment(&salt, &value),
+        None => contact_info.pii_value(&field) == value,
+    };
+    Ok(verified)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
 pub struct RequestResponse {
     pub sent_invoices: Vec<InvoiceResponse>,
     pub received_invoices: Vec<InvoiceResponse>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct InvoiceResponse {
     pub invoice: Invoice,
     pub contact: ContactResponse,
 }
 
+/// invoices raised by the address, in id order
+fn issued_invoices<'a>(
+    deps: Deps<'a>,
+    address: &Addr,
+    start_after: Option<u64>,
+) -> Box<dyn Iterator<Item = StdResult<Invoice>> + 'a> {
+    let iter = INVOICE.idx.issuer.prefix(address.clone()).range(
+        deps.storage,
+        start_after.map(Bound::exclusive),
+        None,
+        Order::Ascending,
+    );
+    Box::new(iter.map(|item| item.map(|(_, invoice)| invoice)))
+}
+
+/// invoices the address has to pay, in id order
+fn assigned_invoices<'a>(
+    deps: Deps<'a>,
+    address: &Addr,
+    start_after: Option<u64>,
+) -> Box<dyn Iterator<Item = StdResult<Invoice>> + 'a> {
+    let iter = INVOICE.idx.payee.prefix(address.clone()).range(
+        deps.storage,
+        start_after.map(Bound::exclusive),
+        None,
+        Order::Ascending,
+    );
+    Box::new(iter.map(|item| item.map(|(_, invoice)| invoice)))
+}
+
+/// pairs the invoice with the profile of the other party
+fn invoice_response(
+    deps: Deps,
+    invoice: Invoice,
+    counterparty: &Addr,
+) -> StdResult<InvoiceResponse> {
+    let contact_info = CONTACT_INFO.load(deps.storage, counterparty)?;
+    Ok(InvoiceResponse {
+        invoice,
+        contact: ContactResponse {
+            name: contact_info.name,
+            address: contact_info.owner,
+            company_name: contact_info.company_name,
+        },
+    })
+}
+
+/// refreshes the amount due and status of the invoice to this block
+fn with_current_state(mut invoice: Invoice, env: &Env) -> Invoice {
+    invoice.due_amount = invoice.amount_due(&env.block).total;
+    invoice.status = invoice.current_status(&env.block);
+    invoice
+}
+
+/// returns a page of the open invoices the address sent and received, optionally only
+/// those currently in `status`
+pub fn get_pending_invoices(
+    deps: Deps,
+    env: Env,
+    address: Addr,
+    status: Option<Status>,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<RequestResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let is_pending = |invoice: &Invoice| {
+        !invoice.status.is_closed()
+            && status
+                .as_ref()
+                .is_none_or(|status| invoice.current_status(&env.block) == *status)
+    };
 
-pub fn get_pending_invoices(deps: Deps, address: Addr) -> StdResult<RequestResponse> {
-    let contact_info = CONTACT_INFO.may_load(deps.storage, &address)?;
-    if contact_info.is_none() {
-        return Ok(RequestResponse {
-            sent_invoices: vec![],
-            received_invoices: vec![],
-        });
-    }
-    let contact_info = contact_info.unwrap();
     let mut sent_invoices = vec![];
-    let mut received_invoices = vec![];
-    for invoice_id in contact_info.generated_invoices.iter() {
-        let invoice = INVOICE.may_load(deps.storage, &invoice_id)?;
-        if invoice.is_none() {
-            continue;
+    for invoice in issued_invoices(deps, &address, start_after) {
+        let invoice = invoice?;
+        if sent_invoices.len() == limit {
+            break;
         }
-        let invoice = invoice.unwrap();
-        if invoice.status != Status::Paid {
-            let contact_info = CONTACT_INFO.load(deps.storage, &invoice.payee_address)?;
-            sent_invoices.push(InvoiceResponse {
-                invoice: invoice,
-                contact: ContactResponse {
-                    name: contact_info.name,
-                    address: contact_info.owner,
-                    company_name: contact_info.company_name,
-                },
-            });
+        if is_pending(&invoice) {
+            let payee = invoice.payee_address.clone();
+            let invoice = with_current_state(invoice, &env);
+            sent_invoices.push(invoice_response(deps, invoice, &payee)?);
         }
     }
-    for invoice_id in contact_info.assigned_invoices.iter() {
-        let invoice = INVOICE.may_load(deps.storage, &invoice_id)?;
-        if invoice.is_none() {
-            continue;
+    let mut received_invoices = vec![];
+    for invoice in assigned_invoices(deps, &address, start_after) {
+        let invoice = invoice?;
+        if received_invoices.len() == limit {
+            break;
         }
-        let invoice = invoice.unwrap();
-        if invoice.status != Status::Paid {
-            let contact_info = CONTACT_INFO.load(deps.storage, &invoice.from)?;
-            received_invoices.push(InvoiceResponse {
-                invoice: invoice,
-                contact: ContactResponse {
-                    name: contact_info.name,
-                    address: contact_info.owner,
-                    company_name: contact_info.company_name,
-                },
-            });
+        if is_pending(&invoice) {
+            let issuer = invoice.from.clone();
+            let invoice = with_current_state(invoice, &env);
+            received_invoices.push(invoice_response(deps, invoice, &issuer)?);
         }
     }
     Ok(RequestResponse {
@@ -93,51 +372,35 @@ pub fn get_pending_invoices(deps: Deps, address: Addr) -> StdResult<RequestRespo
     })
 }
 
-pub fn get_executed_invoices(deps: Deps, address: Addr) -> StdResult<RequestResponse> {
-    let contact_info = CONTACT_INFO.may_load(deps.storage, &address)?;
-    if contact_info.is_none() {
-        return Ok(RequestResponse {
-            sent_invoices: vec![],
-            received_invoices: vec![],
-        });
-    }
-    let contact_info = contact_info.unwrap();
+/// returns a page of the paid invoices the address sent and received
+pub fn get_executed_invoices(
+    deps: Deps,
+    address: Addr,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<RequestResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+
     let mut sent_invoices = vec![];
-    let mut received_invoices = vec![];
-    for invoice_id in contact_info.generated_invoices.iter() {
-        let invoice = INVOICE.may_load(deps.storage, &invoice_id)?;
-        if invoice.is_none() {
-            continue;
+    for invoice in issued_invoices(deps, &address, start_after) {
+        let invoice = invoice?;
+        if sent_invoices.len() == limit {
+            break;
         }
-        let invoice = invoice.unwrap();
         if invoice.status == Status::Paid {
-            let contact_info = CONTACT_INFO.load(deps.storage, &invoice.payee_address)?;
-            sent_invoices.push(InvoiceResponse {
-                invoice: invoice,
-                contact: ContactResponse {
-                    name: contact_info.name,
-                    address: contact_info.owner,
-                    company_name: contact_info.company_name,
-                },
-            });
+            let payee = invoice.payee_address.clone();
+            sent_invoices.push(invoice_response(deps, invoice, &payee)?);
         }
     }
-    for invoice_id in contact_info.assigned_invoices.iter() {
-        let invoice = INVOICE.may_load(deps.storage, &invoice_id)?;
-        if invoice.is_none() {
-            continue;
+    let mut received_invoices = vec![];
+    for invoice in assigned_invoices(deps, &address, start_after) {
+        let invoice = invoice?;
+        if received_invoices.len() == limit {
+            break;
         }
-        let invoice = invoice.unwrap();
         if invoice.status == Status::Paid {
-            let contact_info = CONTACT_INFO.load(deps.storage, &invoice.from)?;
-            received_invoices.push(InvoiceResponse {
-                invoice: invoice,
-                contact: ContactResponse {
-                    name: contact_info.name,
-                    address: contact_info.owner,
-                    company_name: contact_info.company_name,
-                },
-            });
+            let issuer = invoice.from.clone();
+            received_invoices.push(invoice_response(deps, invoice, &issuer)?);
         }
     }
     Ok(RequestResponse {
@@ -146,75 +409,536 @@ pub fn get_executed_invoices(deps: Deps, address: Addr) -> StdResult<RequestResp
     })
 }
 
+/// returns a page of the invoices matching the filter, read through the most selective index
+pub fn get_invoices(
+    deps: Deps,
+    env: Env,
+    filter: InvoiceFilter,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<Vec<Invoice>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
 
-pub fn get_total_receivables(deps: Deps, address: Addr) -> StdResult<Vec<Coin>> {
-    let contact_info = CONTACT_INFO.may_load(deps.storage, &address)?;
-    if contact_info.is_none() {
-        return Ok(vec![]);
-    }
-    let contact_info = contact_info.unwrap();
-    let mut total_receivables: Vec<Coin> = vec![];
-    for invoice_id in contact_info.generated_invoices.iter() {
-        let invoice = INVOICE.may_load(deps.storage, &invoice_id)?;
-        if invoice.is_none() {
-            continue;
+    //// overdue is never stored, so it cannot be looked up in the status index ////
+    let stored_status = filter
+        .status
+        .as_ref()
+        .filter(|status| **status != Status::Overdue);
+    let invoices: Box<dyn Iterator<Item = StdResult<Invoice>>> =
+        if let Some(issuer) = &filter.issuer {
+            issued_invoices(deps, issuer, start_after)
+        } else if let Some(payee) = &filter.payee {
+            assigned_invoices(deps, payee, start_after)
+        } else if let Some(status) = stored_status {
+            Box::new(
+                INVOICE
+                    .idx
+                    .status
+                    .prefix(status.key().to_string())
+                    .range(deps.storage, start, None, Order::Ascending)
+                    .map(|item| item.map(|(_, invoice)| invoice)),
+            )
+        } else if let Some(denom) = &filter.denom {
+            Box::new(
+                INVOICE
+                    .idx
+                    .denom
+                    .prefix(denom.clone())
+                    .range(deps.storage, start, None, Order::Ascending)
+                    .map(|item| item.map(|(_, invoice)| invoice)),
+            )
+        } else {
+            Box::new(
+                INVOICE
+                    .range(deps.storage, start, None, Order::Ascending)
+                    .map(|item| item.map(|(_, invoice)| invoice)),
+            )
+        };
+
+    invoices
+        .filter(|item| match item {
+            Ok(invoice) => filter.matches(invoice, &env.block),
+            Err(_) => true,
+        })
+        .take(limit)
+        .map(|item| item.map(|invoice| with_current_state(invoice, &env)))
+        .collect()
+}
+
+/// a page of the invoices the address issued or has to pay, in id order, with the id to
+/// continue after if the page is full
+fn party_invoices(
+    deps: Deps,
+    address: &Addr,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<(Vec<Invoice>, Option<u64>)> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let mut invoices = issued_invoices(deps, address, start_after)
+        .take(limit)
+        .chain(assigned_invoices(deps, address, start_after).take(limit))
+        .collect::<StdResult<Vec<_>>>()?;
+    invoices.sort_by_key(|invoice| invoice.id);
+    invoices.truncate(limit);
+
+    let last_invoice_id = match invoices.last() {
+        Some(last) if invoices.len() == limit => Some(last.id),
+        _ => None,
+    };
+    Ok((invoices, last_invoice_id))
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct TotalsResponse {
+    pub totals: Vec<Coin>,
+    /// last invoice counted, passed as `start_after` for the next page, `None` on the last page
+    pub last_invoice_id: Option<u64>,
+}
+
+pub fn get_total_receivables(
+    deps: Deps,
+    address: Addr,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<TotalsResponse> {
+    let (invoices, last_invoice_id) = party_invoices(deps, &address, start_after, limit)?;
+    let mut totals: Vec<Coin> = vec![];
+    for invoice in invoices {
+        if invoice.from != address {
+            //// refunds owed on credit notes of paid invoices ////
+            add_credit_refunds(deps, invoice.id, &mut totals)?;
+        } else if !invoice.status.is_closed() {
+            add_coin(
+                &mut totals,
+                Coin {
+                    denom: invoice.receivable.denom.clone(),
+                    amount: invoice.outstanding_principal(),
+                },
+            );
         }
-        let invoice = invoice.unwrap();
-
-        if invoice.status != Status::Paid {
-            //// if total_receivables is umpty push it but if not empty , update the amount if denom matches
-            let mut found = false;
-            for receivable in total_receivables.iter_mut() {
-                if receivable.denom == invoice.receivable.denom {
-                    receivable.amount += invoice.receivable.amount - invoice.amount_paid.amount;
-                    found = true;
-                    break;
-                }
-            }
-            if !found {
-                total_receivables.push(Coin {
+    }
+    Ok(TotalsResponse {
+        totals,
+        last_invoice_id,
+    })
+}
+
+pub fn get_total_payables(
+    deps: Deps,
+    address: Addr,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<TotalsResponse> {
+    let (invoices, last_invoice_id) = party_invoices(deps, &address, start_after, limit)?;
+    let mut totals: Vec<Coin> = vec![];
+    for invoice in invoices {
+        if invoice.payee_address != address {
+            add_credit_refunds(deps, invoice.id, &mut totals)?;
+        } else if !invoice.status.is_closed() {
+            add_coin(
+                &mut totals,
+                Coin {
                     denom: invoice.receivable.denom.clone(),
-                    amount: invoice.receivable.amount - invoice.amount_paid.amount,
-                });
-            }
+                    amount: invoice.outstanding_principal(),
+                },
+            );
         }
     }
-    Ok(total_receivables)
+    Ok(TotalsResponse {
+        totals,
+        last_invoice_id,
+    })
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub struct AgingBuckets {
+    pub current: Vec<Coin>,
+    pub days_0_30: Vec<Coin>,
+    pub days_31_60: Vec<Coin>,
+    pub days_61_90: Vec<Coin>,
+    pub days_over_90: Vec<Coin>,
+}
+
+impl AgingBuckets {
+    fn add(&mut self, invoice: &Invoice, env: &Env) {
+        let outstanding = Coin {
+            denom: invoice.receivable.denom.clone(),
+            amount: invoice.outstanding_principal(),
+        };
+        let bucket = if !invoice.is_overdue(&env.block) {
+            &mut self.current
+        } else {
+            match invoice.days_overdue(&env.block) {
+                0..=30 => &mut self.days_0_30,
+                31..=60 => &mut self.days_31_60,
+                61..=90 => &mut self.days_61_90,
+                _ => &mut self.days_over_90,
+            }
+        };
+        add_coin(bucket, outstanding);
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct AgingReportResponse {
+    pub receivables: AgingBuckets,
+    pub payables: AgingBuckets,
+    /// last invoice counted, passed as `start_after` for the next page, `None` on the last page
+    pub last_invoice_id: Option<u64>,
 }
 
-pub fn get_total_payables(deps: Deps, address: Addr) -> StdResult<Vec<Coin>> {
-    let contact_info = CONTACT_INFO.may_load(deps.storage, &address)?;
-    if contact_info.is_none() {
-        return Ok(vec![]);
+pub fn get_aging_report(
+    deps: Deps,
+    env: Env,
+    address: Addr,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<AgingReportResponse> {
+    let (invoices, last_invoice_id) = party_invoices(deps, &address, start_after, limit)?;
+    let mut receivables = AgingBuckets::default();
+    let mut payables = AgingBuckets::default();
+
+    for invoice in invoices
+        .iter()
+        .filter(|invoice| !invoice.status.is_closed())
+    {
+        if invoice.from == address {
+            receivables.add(invoice, &env);
+        } else {
+            payables.add(invoice, &env);
+        }
     }
-    let contact_info = contact_info.unwrap();
 
-    let mut total_payables: Vec<Coin> = vec![];
-    for invoice_id in contact_info.assigned_invoices.iter() {
-        let invoice = INVOICE.may_load(deps.storage, &invoice_id)?;
-        if invoice.is_none() {
-            continue;
+    Ok(AgingReportResponse {
+        receivables,
+        payables,
+        last_invoice_id,
+    })
+}
+
+/// adds the refunds still owed on acknowledged credit notes of the invoice
+fn add_credit_refunds(deps: Deps, invoice_id: u64, totals: &mut Vec<Coin>) -> StdResult<()> {
+    let credit_note_ids = INVOICE_CREDIT_NOTES
+        .may_load(deps.storage, &invoice_id)?
+        .unwrap_or_default();
+    for credit_note_id in credit_note_ids {
+        let credit_note = CREDIT_NOTE.load(deps.storage, &credit_note_id)?;
+        if credit_note.status == CreditNoteStatus::Acknowledged && !credit_note.refund_due.is_zero()
+        {
+            let refund = Coin {
+                denom: credit_note.amount.denom,
+                amount: credit_note.refund_due,
+            };
+            add_coin(totals, refund);
         }
-        let invoice = invoice.unwrap();
-        if invoice.status != Status::Paid {
-            //// if total_payables is umpty push it but if not empty , update the amount if denom matches
-            let mut found = false;
-            for payable in total_payables.iter_mut() {
-                if payable.denom == invoice.receivable.denom {
-                    payable.amount += invoice.receivable.amount - invoice.amount_paid.amount;
-                    found = true;
-                    break;
-                }
+    }
+    Ok(())
+}
+
+pub fn get_credit_note(deps: Deps, credit_note_id: u64) -> StdResult<CreditNote> {
+    let credit_note = CREDIT_NOTE.load(deps.storage, &credit_note_id)?;
+    Ok(credit_note)
+}
+
+pub fn get_invoice_credit_notes(deps: Deps, invoice_id: u64) -> StdResult<Vec<CreditNote>> {
+    INVOICE_CREDIT_NOTES
+        .may_load(deps.storage, &invoice_id)?
+        .unwrap_or_default()
+        .iter()
+        .map(|credit_note_id| CREDIT_NOTE.load(deps.storage, credit_note_id))
+        .collect()
+}
+
+pub fn get_kyc_providers(
+    deps: Deps,
+    start_after: Option<Addr>,
+    limit: Option<u32>,
+) -> StdResult<Vec<Addr>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_ref().map(Bound::exclusive);
+    KYC_PROVIDERS
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect()
+}
+
+pub fn get_kyc_policy(deps: Deps) -> StdResult<KycPolicy> {
+    let policy = KYC_POLICY.may_load(deps.storage)?.unwrap_or_default();
+    Ok(policy)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct KycStatusResponse {
+    pub address: Addr,
+    /// `ReVerify` once an approval has expired
+    pub status: KYCStatus,
+    pub record: Option<KycRecord>,
+}
+
+pub fn get_kyc_status(deps: Deps, env: Env, address: Addr) -> StdResult<KycStatusResponse> {
+    let contact_info = CONTACT_INFO.load(deps.storage, &address)?;
+    Ok(KycStatusResponse {
+        status: contact_info.current_kyc_status(&env.block),
+        record: contact_info.kyc_record,
+        address,
+    })
+}
+
+pub fn get_arbitration(deps: Deps) -> StdResult<Option<ArbitrationConfig>> {
+    let arbitration = ARBITRATION.may_load(deps.storage)?;
+    Ok(arbitration)
+}
+
+pub fn get_dispute(deps: Deps, dispute_id: u64) -> StdResult<Dispute> {
+    let dispute = DISPUTE.load(deps.storage, dispute_id)?;
+    Ok(dispute)
+}
+
+/// returns a page of the open disputes on invoices the address issued or has to pay, in id
+/// order
+pub fn get_open_disputes(
+    deps: Deps,
+    address: Addr,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<Vec<Dispute>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let page = |index: &MultiIndex<Addr, Dispute, u64>| -> StdResult<Vec<Dispute>> {
+        let mut disputes = vec![];
+        for item in index.prefix(address.clone()).range(
+            deps.storage,
+            start_after.map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        ) {
+            let (_, dispute) = item?;
+            if disputes.len() == limit {
+                break;
             }
-            if !found {
-                total_payables.push(Coin {
-                    denom: invoice.receivable.denom.clone(),
-                    amount: invoice.receivable.amount - invoice.amount_paid.amount,
-                });
+            if dispute.status == DisputeStatus::Open {
+                disputes.push(dispute);
             }
         }
+        Ok(disputes)
+    };
+
+    //// the address is on one side of each dispute, so both pages merge without duplicates ////
+    let mut disputes = page(&DISPUTE.idx.issuer)?;
+    disputes.extend(page(&DISPUTE.idx.payee)?);
+    disputes.sort_by_key(|dispute| dispute.id);
+    disputes.truncate(limit);
+    Ok(disputes)
+}
+
+/// builds the cw3 `Propose` message a member of the arbitration multisig sends to rule on
+/// the dispute, the proposal cannot be executed after the ruling deadline
+pub fn get_ruling_proposal(
+    deps: Deps,
+    env: Env,
+    dispute_id: u64,
+    ruling: Ruling,
+) -> StdResult<CosmosMsg> {
+    let multisig = match ARBITRATION.load(deps.storage)?.arbitrator {
+        Arbitrator::Multisig(addr) => addr,
+        Arbitrator::Address(_) => {
+            return Err(StdError::generic_err("Arbitrator is not a multisig"))
+        }
+    };
+    let dispute = DISPUTE.load(deps.storage, dispute_id)?;
+
+    let resolve = CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: env.contract.address.to_string(),
+        msg: to_binary(&ExecuteMsg::ResolveDispute {
+            dispute_id,
+            ruling: ruling.clone(),
+        })?,
+        funds: vec![],
+    });
+    Cw3Contract(multisig).proposal(
+        format!("Resolve dispute {}", dispute_id),
+        format!("Rule {:?} on invoice {}", ruling, dispute.invoice_id),
+        vec![resolve],
+        None,
+        Some(Expiration::AtTime(dispute.ruling_deadline)),
+    )
+}
+
+pub fn get_fee_config(deps: Deps) -> StdResult<FeeConfig> {
+    let config = FEE_CONFIG.may_load(deps.storage)?.unwrap_or_default();
+    Ok(config)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct AssetFeesResponse {
+    pub denom: String,
+    pub payment_fee_bps: u16,
+    pub cap: Option<Uint128>,
+    /// collected and not yet withdrawn
+    pub collected: Uint128,
+}
+
+pub fn get_asset_fees(deps: Deps, denom: String) -> StdResult<AssetFeesResponse> {
+    let config = FEE_CONFIG.may_load(deps.storage)?.unwrap_or_default();
+    let collected = TREASURY.may_load(deps.storage, &denom)?.unwrap_or_default();
+    Ok(AssetFeesResponse {
+        cap: config.cap(&denom),
+        payment_fee_bps: config.payment_fee_bps,
+        denom,
+        collected,
+    })
+}
+
+pub fn get_treasury(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<Vec<Coin>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+    TREASURY
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(denom, amount)| Coin { denom, amount }))
+        .collect()
+}
+
+pub fn get_fee_exemptions(
+    deps: Deps,
+    start_after: Option<Addr>,
+    limit: Option<u32>,
+) -> StdResult<Vec<Addr>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_ref().map(Bound::exclusive);
+    FEE_EXEMPT
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect()
+}
+
+pub fn get_delegation(deps: Deps, owner: Addr, delegate: Addr) -> StdResult<Option<Delegation>> {
+    DELEGATIONS.may_load(deps.storage, (&owner, &delegate))
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct DelegationResponse {
+    pub delegate: Addr,
+    pub delegation: Delegation,
+}
+
+pub fn get_delegations(
+    deps: Deps,
+    owner: Addr,
+    start_after: Option<Addr>,
+    limit: Option<u32>,
+) -> StdResult<Vec<DelegationResponse>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_ref().map(Bound::exclusive);
+    DELEGATIONS
+        .prefix(&owner)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            item.map(|(delegate, delegation)| DelegationResponse {
+                delegate,
+                delegation,
+            })
+        })
+        .collect()
+}
+
+/// true if `sender` could send `ExecuteAsDelegate` with the message and funds of `msg` right now
+pub fn can_execute(
+    deps: Deps,
+    env: Env,
+    owner: Addr,
+    sender: String,
+    msg: CosmosMsg,
+) -> StdResult<CanExecuteResponse> {
+    let sender = deps.api.addr_validate(&sender)?;
+    let can_execute = match msg {
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr,
+            msg,
+            funds,
+        }) if contract_addr == env.contract.address => from_binary::<ExecuteMsg>(&msg)
+            .and_then(|msg| {
+                check_delegation(deps.storage, &env.block, &owner, &sender, &msg, &funds)
+            })
+            .is_ok(),
+        _ => false,
+    };
+    Ok(CanExecuteResponse { can_execute })
+}
+
+pub fn get_approval_policy(deps: Deps, address: Addr) -> StdResult<Option<ApprovalPolicy>> {
+    APPROVAL_POLICY.may_load(deps.storage, &address)
+}
+
+pub fn get_pending_approval(deps: Deps, invoice_id: u64) -> StdResult<Option<PendingApproval>> {
+    PENDING_APPROVALS.may_load(deps.storage, invoice_id)
+}
+
+pub fn get_pending_approvals(
+    deps: Deps,
+    approver: Option<Addr>,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<Vec<PendingApproval>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    PENDING_APPROVALS
+        .range(deps.storage, start, None, Order::Ascending)
+        .map(|item| item.map(|(_, pending)| pending))
+        .filter(|item| {
+            item.as_ref().map_or(true, |pending| {
+                approver.as_ref().is_none_or(|a| pending.approver == *a)
+            })
+        })
+        .take(limit)
+        .collect()
+}
+
+pub fn get_approval_proposal(deps: Deps, env: Env, invoice_id: u64) -> StdResult<CosmosMsg> {
+    let pending = PENDING_APPROVALS.load(deps.storage, invoice_id)?;
+    let invoice = INVOICE.load(deps.storage, invoice_id)?;
+
+    let approve = CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: env.contract.address.to_string(),
+        msg: to_binary(&ExecuteMsg::ApproveInvoice { invoice_id })?,
+        funds: vec![],
+    });
+    let description = match &pending.action {
+        ApprovalAction::Accept {} => format!(
+            "Accept invoice {} of {} from {}",
+            invoice_id, invoice.receivable, invoice.from
+        ),
+        ApprovalAction::Pay { amount, .. } => format!(
+            "Pay {} of invoice {} to {}",
+            amount, invoice_id, invoice.from
+        ),
+    };
+    Cw3Contract(pending.approver).proposal(
+        format!("Approve invoice {}", invoice_id),
+        description,
+        vec![approve],
+        None,
+        None,
+    )
+}
+
+/// adds the coin to the list, merging it with an existing entry of the same denom
+fn add_coin(coins: &mut Vec<Coin>, coin: Coin) {
+    match coins.iter_mut().find(|c| c.denom == coin.denom) {
+        Some(existing) => existing.amount += coin.amount,
+        None => coins.push(coin),
     }
-    Ok(total_payables)
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
@@ -225,60 +949,109 @@ pub struct ContactResponse {
     pub company_name: String,
 }
 
-pub fn get_pending_contact_requests(deps: Deps, address: Addr) -> StdResult<Vec<ContactResponse>> {
-    let contact_info = CONTACT_INFO.may_load(deps.storage, &address)?;
-    if contact_info.is_none() {
-        return Ok(vec![]);
-    }
-    let contact_info = contact_info.unwrap();
+/// returns a page of the profiles `address` is paired with in `map`, in address order
+fn contact_page<T>(
+    deps: Deps,
+    map: Map<(&Addr, &Addr), T>,
+    address: &Addr,
+    start_after: Option<Addr>,
+    limit: Option<u32>,
+) -> StdResult<Vec<ContactResponse>>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_ref().map(Bound::exclusive);
 
-    let received_requests = contact_info.received_requests.clone();
-    let mut response = vec![];
-    for contact in received_requests.iter() {
-        let contact_info = CONTACT_INFO.load(deps.storage, &contact)?;
-        response.push(ContactResponse {
-            name: contact_info.name,
-            address: contact_info.owner,
-            company_name: contact_info.company_name,
-        });
-    }
-    Ok(response)
+    map.prefix(address)
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|other| {
+            let contact_info = CONTACT_INFO.load(deps.storage, &other?)?;
+            Ok(ContactResponse {
+                name: contact_info.name,
+                address: contact_info.owner,
+                company_name: contact_info.company_name,
+            })
+        })
+        .collect()
 }
 
-pub fn get_sent_contact_requests(deps: Deps, address: Addr) -> StdResult<Vec<ContactResponse>> {
-    let contact_info = CONTACT_INFO.may_load(deps.storage, &address)?;
-    if contact_info.is_none() {
-        return Ok(vec![]);
-    }
-    let contact_info = contact_info.unwrap();
-    let sent_requests = contact_info.sent_requests.clone();
-    let mut response = vec![];
-    for contact in sent_requests.iter() {
-        let contact_info = CONTACT_INFO.load(deps.storage, &contact)?;
-        response.push(ContactResponse {
-            name: contact_info.name,
-            address: contact_info.owner,
-            company_name: contact_info.company_name,
-        });
-    }
-    Ok(response)
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct AddressBookResponse {
+    pub contact: ContactResponse,
+    pub entry: Contact,
 }
 
-pub fn get_all_contacts(deps: Deps, address: Addr) -> StdResult<Vec<ContactResponse>> {
-    let contact_info = CONTACT_INFO.may_load(deps.storage, &address)?;
-    if contact_info.is_none() {
-        return Ok(vec![]);
-    }
-    let contact_info = contact_info.unwrap();
-    let contacts = contact_info.contacts.clone();
-    let mut response = vec![];
-    for contact in contacts.iter() {
-        let contact_info = CONTACT_INFO.load(deps.storage, &contact)?;
-        response.push(ContactResponse {
-            name: contact_info.name,
-            address: contact_info.owner,
-            company_name: contact_info.company_name,
+pub fn get_contacts(
+    deps: Deps,
+    owner: Addr,
+    tag: Option<ContactTag>,
+    start_after: Option<Addr>,
+    limit: Option<u32>,
+) -> StdResult<Vec<AddressBookResponse>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_ref().map(Bound::exclusive);
+
+    let mut entries = vec![];
+    for item in CONTACTS
+        .prefix(&owner)
+        .range(deps.storage, start, None, Order::Ascending)
+    {
+        if entries.len() == limit {
+            break;
+        }
+        let (address, entry) = item?;
+        let tagged = match &tag {
+            Some(filter) => entry.tag.as_ref().is_some_and(|tag| tag.matches(filter)),
+            None => true,
+        };
+        if !tagged {
+            continue;
+        }
+        let profile = CONTACT_INFO.load(deps.storage, &address)?;
+        entries.push(AddressBookResponse {
+            contact: ContactResponse {
+                name: profile.name,
+                address: profile.owner,
+                company_name: profile.company_name,
+            },
+            entry,
         });
     }
-    Ok(response)
+    Ok(entries)
+}
+
+pub fn resolve_contact_alias(deps: Deps, owner: Addr, alias: String) -> StdResult<Addr> {
+    CONTACT_ALIASES
+        .may_load(deps.storage, (&owner, &alias))?
+        .ok_or_else(|| StdError::generic_err("Alias not found"))
+}
+
+pub fn get_pending_contact_requests(
+    deps: Deps,
+    address: Addr,
+    start_after: Option<Addr>,
+    limit: Option<u32>,
+) -> StdResult<Vec<ContactResponse>> {
+    contact_page(deps, RECEIVED_REQUESTS, &address, start_after, limit)
+}
+
+pub fn get_sent_contact_requests(
+    deps: Deps,
+    address: Addr,
+    start_after: Option<Addr>,
+    limit: Option<u32>,
+) -> StdResult<Vec<ContactResponse>> {
+    contact_page(deps, SENT_REQUESTS, &address, start_after, limit)
+}
+
+pub fn get_all_contacts(
+    deps: Deps,
+    address: Addr,
+    start_after: Option<Addr>,
+    limit: Option<u32>,
+) -> StdResult<Vec<ContactResponse>> {
+    contact_page(deps, CONTACTS, &address, start_after, limit)
 }
@@ -0,0 +1,412 @@
+use crate::fee::charge_creation_fee;
+use crate::invoice::{issue_invoice, validate_payment_terms};
+use crate::state::*;
+use cosmwasm_std::{Coin, DepsMut, Env, MessageInfo, Response, StdError, Uint128};
+
+use crate::error::ContractError;
+
+pub fn create_recurring(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    template: RecurringTemplate,
+) -> Result<Response, ContractError> {
+    let RecurringTemplate {
+        payee_address,
+        receivable,
+        service_type,
+        doc_uri,
+        payment_terms,
+        interval_days,
+        payment_days,
+        start_at,
+        end_date,
+        max_count,
+    } = template;
+
+    if interval_days == 0 || payment_days == 0 {
+        return Err(StdError::generic_err("Interval and payment days must be positive").into());
+    }
+    if max_count == Some(0) {
+        return Err(StdError::generic_err("Count must be positive").into());
+    }
+
+    let start_at = start_at.unwrap_or(env.block.time);
+    if start_at < env.block.time {
+        return Err(StdError::generic_err("Start must not be in the past").into());
+    }
+    if end_date.is_some_and(|end_date| end_date < start_at) {
+        return Err(StdError::generic_err("End date must be after the start").into());
+    }
+    if let Some(terms) = &payment_terms {
+        let due_date = start_at.plus_seconds(payment_days * SECONDS_PER_DAY);
+        validate_payment_terms(terms, start_at, due_date)?;
+    }
+
+    //// Address cannot be sender////
+    if info.sender == payee_address {
+        return Err(StdError::generic_err("Receiver and Sender cannot be same").into());
+    }
+
+    let config = CONFIG.load(deps.storage)?;
+    if !config
+        .accepted_assets
+        .iter()
+        .any(|asset| asset.matches(&receivable.denom))
+    {
+        return Err(StdError::generic_err("Asset not accepted").into());
+    }
+
+    //// contact and kyc checks are repeated by every generated invoice ////
+    if !CONTACT_INFO.has(deps.storage, &info.sender) {
+        return Err(StdError::generic_err("Profile does not exist").into());
+    }
+    if !CONTACTS.has(deps.storage, (&info.sender, &payee_address)) {
+        return Err(StdError::generic_err("Receiver not in contact list").into());
+    }
+
+    //// the template prepays the creation fee of every invoice it can issue ////
+    let creation_fee = charge_creation_fee(deps.storage, &info, max_count)?;
+
+    let recurring_id = RECURRING_ID.may_load(deps.storage)?.unwrap_or_default() + 1;
+    let recurring = RecurringInvoice {
+        id: recurring_id,
+        from: info.sender.clone(),
+        payee_address,
+        receivable,
+        service_type,
+        doc_uri,
+        payment_terms,
+        interval_days,
+        payment_days,
+        next_issue_at: start_at,
+        end_date,
+        max_count,
+        invoices: vec![],
+        cancelled: false,
+    };
+    RECURRING_INVOICE.save(deps.storage, recurring_id, &recurring)?;
+    RECURRING_ID.save(deps.storage, &recurring_id)?;
+
+    let mut response = Response::new()
+        .add_attribute("method", "create_recurring")
+        .add_attribute("recurring_id", recurring_id.to_string())
+        .add_attribute("sender", info.sender);
+    if let Some(fee) = creation_fee {
+        response = response.add_attribute("creation_fee", fee.to_string());
+    }
+    Ok(response)
+}
+
+pub fn cancel_recurring(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    recurring_id: u64,
+) -> Result<Response, ContractError> {
+    let mut recurring = RECURRING_INVOICE.load(deps.storage, recurring_id)?;
+
+    //// only the issuer can stop the subscription ////
+    if recurring.from != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    if recurring.cancelled {
+        return Err(StdError::generic_err("Recurring invoice already cancelled").into());
+    }
+
+    recurring.cancelled = true;
+    RECURRING_INVOICE.save(deps.storage, recurring_id, &recurring)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "cancel_recurring")
+        .add_attribute("recurring_id", recurring_id.to_string()))
+}
+
+pub fn trigger_recurring(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    recurring_id: u64,
+) -> Result<Response, ContractError> {
+    let mut recurring = RECURRING_INVOICE.load(deps.storage, recurring_id)?;
+    if recurring.is_finished() {
+        return Err(StdError::generic_err("Recurring invoice finished").into());
+    }
+    if env.block.time < recurring.next_issue_at {
+        return Err(StdError::generic_err("Next invoice not yet due").into());
+    }
+
+    //// do not accept funds ////
+    if !info.funds.is_empty() {
+        return Err(StdError::generic_err("Funds not accepted").into());
+    }
+
+    //// anyone can trigger, the issuer paid the creation fee with the template ////
+    let invoice_id = get_invoice_id(deps.as_ref());
+    let due_date = env
+        .block
+        .time
+        .plus_seconds(recurring.payment_days * SECONDS_PER_DAY);
+    let params = InvoiceParams {
+        payee_address: recurring.payee_address.clone(),
+        receivable: recurring.receivable.clone(),
+        amount_paid: Coin {
+            denom: recurring.receivable.denom.clone(),
+            amount: Uint128::zero(),
+        },
+        service_type: recurring.service_type.clone(),
+        doc_uri: recurring.doc_uri.clone(),
+        due_date,
+        payment_terms: recurring.payment_terms.clone(),
+        settlement: None,
+        installments: None,
+        line_items: None,
+    };
+    let response = issue_invoice(deps.branch(), env, recurring.from.clone(), params, None)?;
+
+    //// one invoice per trigger, missed periods are caught up by triggering again ////
+    recurring.invoices.push(invoice_id);
+    recurring.next_issue_at = recurring
+        .next_issue_at
+        .plus_seconds(recurring.interval_days * SECONDS_PER_DAY);
+    RECURRING_INVOICE.save(deps.storage, recurring_id, &recurring)?;
+
+    Ok(response
+        .add_attribute("method", "trigger_recurring")
+        .add_attribute("recurring_id", recurring_id.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fee::set_fee_config;
+    use crate::query::{get_asset_fees, get_recurring, get_recurring_invoices};
+    use crate::testing::*;
+    use cosmwasm_std::testing::{mock_env, mock_info};
+    use cosmwasm_std::{coin, Addr};
+
+    fn template() -> RecurringTemplate {
+        RecurringTemplate {
+            payee_address: Addr::unchecked(PAYEE),
+            receivable: coin(250, "uatom"),
+            service_type: ServiceType::Service,
+            doc_uri: "ipfs://subscription".to_string(),
+            payment_terms: None,
+            interval_days: 30,
+            payment_days: 15,
+            start_at: None,
+            end_date: None,
+            max_count: None,
+        }
+    }
+
+    #[test]
+    fn recurring_invoices_are_issued_lazily() {
+        let mut deps = setup();
+        create_recurring(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            RecurringTemplate {
+                max_count: Some(2),
+                ..template()
+            },
+        )
+        .unwrap();
+        let recurring_id = RECURRING_ID.load(&deps.storage).unwrap();
+
+        //// the first invoice is due right away and anyone can trigger it ////
+        trigger_recurring(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            recurring_id,
+        )
+        .unwrap();
+        let invoice_id = INVOICE_ID.load(&deps.storage).unwrap() - 1;
+        let invoice = INVOICE.load(&deps.storage, invoice_id).unwrap();
+        assert_eq!(invoice.from, Addr::unchecked(ISSUER));
+        assert_eq!(invoice.receivable, coin(250, "uatom"));
+        assert_eq!(
+            invoice.due_date,
+            Some(mock_env().block.time.plus_seconds(15 * SECONDS_PER_DAY))
+        );
+
+        let err = trigger_recurring(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            recurring_id,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            StdError::generic_err("Next invoice not yet due").into()
+        );
+
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(30 * SECONDS_PER_DAY);
+        let next = get_recurring(deps.as_ref(), env.clone(), recurring_id)
+            .unwrap()
+            .next_instance
+            .unwrap();
+        assert!(next.is_due);
+        assert_eq!(next.issue_at, env.block.time);
+
+        trigger_recurring(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            recurring_id,
+        )
+        .unwrap();
+
+        //// the count is reached, nothing more is issued ////
+        let res = get_recurring(deps.as_ref(), env.clone(), recurring_id).unwrap();
+        assert_eq!(res.recurring.invoices.len(), 2);
+        assert_eq!(res.next_instance, None);
+        env.block.time = env.block.time.plus_seconds(30 * SECONDS_PER_DAY);
+        let err = trigger_recurring(deps.as_mut(), env, mock_info("anyone", &[]), recurring_id)
+            .unwrap_err();
+        assert_eq!(
+            err,
+            StdError::generic_err("Recurring invoice finished").into()
+        );
+    }
+
+    #[test]
+    fn only_the_issuer_cancels_a_recurring_invoice() {
+        let mut deps = setup();
+        create_recurring(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            template(),
+        )
+        .unwrap();
+        let recurring_id = RECURRING_ID.load(&deps.storage).unwrap();
+
+        let err = cancel_recurring(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PAYEE, &[]),
+            recurring_id,
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        cancel_recurring(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            recurring_id,
+        )
+        .unwrap();
+        let err = trigger_recurring(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            recurring_id,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            StdError::generic_err("Recurring invoice finished").into()
+        );
+    }
+
+    #[test]
+    fn the_template_prepays_the_creation_fee() {
+        let mut deps = setup();
+        set_fee_config(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ADMIN, &[]),
+            0,
+            Some(coin(10, "uatom")),
+            vec![],
+        )
+        .unwrap();
+
+        //// an open ended template cannot prepay its invoices ////
+        let err = create_recurring(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[coin(10, "uatom")]),
+            template(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            StdError::generic_err("Invoice count required to prepay the creation fee").into()
+        );
+        let template = RecurringTemplate {
+            max_count: Some(3),
+            ..template()
+        };
+        let err = create_recurring(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[coin(10, "uatom")]),
+            template.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            StdError::generic_err("Creation fee of 30uatom required").into()
+        );
+        create_recurring(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[coin(30, "uatom")]),
+            template,
+        )
+        .unwrap();
+        let recurring_id = RECURRING_ID.load(&deps.storage).unwrap();
+
+        //// triggers issue the invoice without charging the caller ////
+        let err = trigger_recurring(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[coin(10, "uatom")]),
+            recurring_id,
+        )
+        .unwrap_err();
+        assert_eq!(err, StdError::generic_err("Funds not accepted").into());
+        trigger_recurring(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            recurring_id,
+        )
+        .unwrap();
+        let fees = get_asset_fees(deps.as_ref(), "uatom".to_string()).unwrap();
+        assert_eq!(fees.collected, Uint128::new(30));
+    }
+
+    #[test]
+    fn recurring_invoices_are_listed_by_party() {
+        let mut deps = setup();
+        for _ in 0..3 {
+            create_recurring(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(ISSUER, &[]),
+                template(),
+            )
+            .unwrap();
+        }
+
+        let page =
+            get_recurring_invoices(deps.as_ref(), Addr::unchecked(ISSUER), None, Some(2)).unwrap();
+        let ids: Vec<u64> = page.issued.iter().map(|recurring| recurring.id).collect();
+        assert_eq!(ids, vec![1, 2]);
+        assert!(page.received.is_empty());
+
+        let page =
+            get_recurring_invoices(deps.as_ref(), Addr::unchecked(PAYEE), Some(2), None).unwrap();
+        let ids: Vec<u64> = page.received.iter().map(|recurring| recurring.id).collect();
+        assert_eq!(ids, vec![3]);
+        assert!(page.issued.is_empty());
+    }
+}
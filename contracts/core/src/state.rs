@@ -2,18 +2,31 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::error::ContractError;
-use cosmwasm_std::{Addr, Coin, Deps, DepsMut, Response};
-use cw_storage_plus::{Item, Map};
+use crate::helpers::denom_key;
+use cosmwasm_std::{
+    Addr, Binary, BlockInfo, Coin, Decimal, Deps, DepsMut, Empty, MessageInfo, Response, StdError,
+    StdResult, Timestamp, Uint128,
+};
+use cw20::Denom;
+use cw_storage_plus::{Index, IndexList, IndexedMap, Item, Map, MultiIndex};
+use cw_utils::Expiration;
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct Asset {
     pub name: String,
-    pub denom: String,
+    pub denom: Denom,
     pub decimal: u64,
     pub uri: Option<String>,
 }
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+
+impl Asset {
+    /// returns true if the invoice denom (native denom or cw20 address) refers to this asset
+    pub fn matches(&self, denom: &str) -> bool {
+        denom_key(&self.denom) == denom
+    }
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct Config {
     pub nft_address: Addr,
@@ -23,11 +36,78 @@ pub struct Config {
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
+pub struct AdminList {
+    pub admins: Vec<Addr>,
+    pub mutable: bool,
+}
+
+impl AdminList {
+    /// returns true if the address is a registered admin
+    pub fn is_admin(&self, addr: impl AsRef<str>) -> bool {
+        let addr = addr.as_ref();
+        self.admins.iter().any(|a| a.as_ref() == addr)
+    }
+
+    /// returns true if the address is a registered admin and the config is mutable
+    pub fn can_modify(&self, addr: &str) -> bool {
+        self.mutable && self.is_admin(addr)
+    }
+}
+
+pub const ADMIN_LIST: Item<AdminList> = Item::new("admin_list");
+
+/// an owner's address book entry for one of its contacts, not visible to the contact
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
 pub struct Contact {
-    pub alias: String,
+    pub alias: Option<String>,
     pub contact_address: Addr,
+    pub tag: Option<ContactTag>,
+    /// asset used for invoices raised to this contact with an empty receivable denom
+    pub default_asset: Option<Denom>,
+    /// terms applied to invoices raised to this contact without explicit terms
+    pub default_terms: Option<PaymentTerms>,
+}
+
+impl Contact {
+    pub fn new(contact_address: Addr) -> Self {
+        Contact {
+            alias: None,
+            contact_address,
+            tag: None,
+            default_asset: None,
+            default_terms: None,
+        }
+    }
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ContactTag {
+    Customer,
+    Supplier,
+    Both,
+}
+
+impl ContactTag {
+    /// `Both` matches either filter, and a `Both` filter only matches `Both`
+    pub fn matches(&self, filter: &ContactTag) -> bool {
+        self == filter || *self == ContactTag::Both
+    }
+}
+
+/// accepted contacts with the owner's address book entry, keyed by (owner, contact)
+pub const CONTACTS: Map<(&Addr, &Addr), Contact> = Map::new("contacts");
+
+/// pending contact requests keyed by (requester, recipient)
+pub const SENT_REQUESTS: Map<(&Addr, &Addr), Empty> = Map::new("sent_requests");
+
+/// pending contact requests keyed by (recipient, requester)
+pub const RECEIVED_REQUESTS: Map<(&Addr, &Addr), Empty> = Map::new("received_requests");
+
+/// contact addresses keyed by (owner, alias), aliases are unique per owner
+pub const CONTACT_ALIASES: Map<(&Addr, &str), Addr> = Map::new("contact_aliases");
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct Metadata {
@@ -36,6 +116,87 @@ pub struct Metadata {
     pub payee_address: Addr,
     pub uri: String,
     pub receivable: Coin,
+    #[serde(default)]
+    pub line_items: Vec<LineItem>,
+    /// receivable before taxes
+    #[serde(default)]
+    pub subtotal: Uint128,
+    #[serde(default)]
+    pub tax_total: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct LineItem {
+    pub description: String,
+    pub quantity: Decimal,
+    /// price of one unit in the receivable denom, before tax
+    pub unit_price: Uint128,
+    pub tax_rate: Decimal,
+    /// code of the jurisdiction levying the tax, e.g. "DE" or "US-CA"
+    pub tax_jurisdiction: Option<String>,
+}
+
+impl LineItem {
+    pub fn net_amount(&self) -> Uint128 {
+        self.unit_price * self.quantity
+    }
+
+    pub fn tax_amount(&self) -> Uint128 {
+        self.net_amount() * self.tax_rate
+    }
+}
+
+/// tax charged at one rate in one jurisdiction
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct TaxLine {
+    pub tax_jurisdiction: Option<String>,
+    pub tax_rate: Decimal,
+    pub taxable: Uint128,
+    pub tax: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct TaxSummary {
+    pub subtotal: Uint128,
+    pub tax_total: Uint128,
+    pub total: Uint128,
+    pub taxes: Vec<TaxLine>,
+}
+
+impl TaxSummary {
+    /// totals the line items, grouping taxes by jurisdiction and rate
+    pub fn from_line_items(line_items: &[LineItem]) -> Self {
+        let mut summary = TaxSummary {
+            subtotal: Uint128::zero(),
+            tax_total: Uint128::zero(),
+            total: Uint128::zero(),
+            taxes: vec![],
+        };
+        for item in line_items {
+            let (net, tax) = (item.net_amount(), item.tax_amount());
+            summary.subtotal += net;
+            summary.tax_total += tax;
+            match summary.taxes.iter_mut().find(|line| {
+                line.tax_jurisdiction == item.tax_jurisdiction && line.tax_rate == item.tax_rate
+            }) {
+                Some(line) => {
+                    line.taxable += net;
+                    line.tax += tax;
+                }
+                None => summary.taxes.push(TaxLine {
+                    tax_jurisdiction: item.tax_jurisdiction.clone(),
+                    tax_rate: item.tax_rate,
+                    taxable: net,
+                    tax,
+                }),
+            }
+        }
+        summary.total = summary.subtotal + summary.tax_total;
+        summary
+    }
 }
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
@@ -46,16 +207,290 @@ pub struct ContactInfo {
     pub phone_number: String,
     pub owner: Addr,
     pub email_id: String,
-    pub sent_requests: Vec<Addr>,
-    pub received_requests: Vec<Addr>,
-    pub contacts: Vec<Addr>,
     pub kyc_status: KYCStatus,
-    pub assigned_invoices: Vec<u64>,
-    pub generated_invoices: Vec<u64>,
+    pub kyc_record: Option<KycRecord>,
+    /// set for private profiles, whose plaintext PII fields are left empty
+    pub private_pii: Option<PrivatePii>,
+    /// public key contacts encrypt the PII they share with this profile to
+    pub viewing_key: Option<Binary>,
+    #[serde(default)]
+    pub profile_status: ProfileStatus,
+}
+
+impl ContactInfo {
+    /// errors unless the profile can take part in new invoices and contact requests
+    pub fn ensure_active(&self) -> StdResult<()> {
+        match self.profile_status {
+            ProfileStatus::Active => Ok(()),
+            ProfileStatus::Deactivated => Err(StdError::generic_err(format!(
+                "Profile {} is deactivated",
+                self.owner
+            ))),
+            ProfileStatus::Erased => Err(StdError::generic_err(format!(
+                "Profile {} is erased",
+                self.owner
+            ))),
+        }
+    }
+
+    /// plaintext value of a PII field, empty for private profiles
+    pub fn pii_value(&self, field: &PiiField) -> &str {
+        match field {
+            PiiField::Name => &self.name,
+            PiiField::EmailId => &self.email_id,
+            PiiField::PhoneNumber => &self.phone_number,
+            PiiField::Address => &self.address,
+        }
+    }
+
+    /// returns the stored KYC status, or `ReVerify` if an approval has expired
+    pub fn current_kyc_status(&self, block: &BlockInfo) -> KYCStatus {
+        let expired = self
+            .kyc_record
+            .as_ref()
+            .and_then(|record| record.expires_at)
+            .is_some_and(|expires_at| block.time >= expires_at);
+        if self.kyc_status == KYCStatus::Approved && expired {
+            KYCStatus::ReVerify
+        } else {
+            self.kyc_status.clone()
+        }
+    }
 }
 
 pub const CONTACT_INFO: Map<&Addr, ContactInfo> = Map::new("contact_info");
 
+/// fields left unset keep their value, plaintext fields only apply to public profiles
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub struct ProfileUpdate {
+    pub name: Option<String>,
+    pub email_id: Option<String>,
+    pub phone_number: Option<String>,
+    pub company_name: Option<String>,
+    pub address: Option<String>,
+    pub commitments: Option<PiiCommitments>,
+    pub encrypted_pii: Option<Binary>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ProfileStatus {
+    #[default]
+    Active,
+    /// left by its owner, existing invoices stay queryable
+    Deactivated,
+    /// PII wiped by an admin after deactivation
+    Erased,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PiiField {
+    Name,
+    EmailId,
+    PhoneNumber,
+    Address,
+}
+
+/// sha256 of the salt length as 8 big endian bytes, the salt and the value for each PII field,
+/// salts and values stay off-chain
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PiiCommitments {
+    pub name: Binary,
+    pub email_id: Binary,
+    pub phone_number: Binary,
+    pub address: Binary,
+}
+
+impl PiiCommitments {
+    pub fn get(&self, field: &PiiField) -> &Binary {
+        match field {
+            PiiField::Name => &self.name,
+            PiiField::EmailId => &self.email_id,
+            PiiField::PhoneNumber => &self.phone_number,
+            PiiField::Address => &self.address,
+        }
+    }
+
+    pub fn validate(&self) -> StdResult<()> {
+        for commitment in [
+            &self.name,
+            &self.email_id,
+            &self.phone_number,
+            &self.address,
+        ] {
+            if commitment.len() != 32 {
+                return Err(StdError::generic_err("Commitments must be sha256 hashes"));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PrivatePii {
+    pub commitments: PiiCommitments,
+    /// PII encrypted client side by the owner, opaque to the contract
+    pub encrypted: Option<Binary>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OrgRole {
+    /// raises invoices and credit notes
+    Issuer,
+    /// accepts or rejects invoices and manages contacts
+    Approver,
+    Payer,
+    Viewer,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct OrgProfile {
+    /// treasury receiving the org's invoice nfts, payments and refunds instead of the group
+    pub payout: Addr,
+}
+
+/// what the group admin sets when creating the org's profile with `CreateOrgProfile`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct OrgProfileParams {
+    pub group: String,
+    pub name: String,
+    pub email_id: String,
+    pub phone_number: String,
+    pub company_name: String,
+    pub address: String,
+    pub payout: String,
+}
+
+/// organisation profiles, keyed by the address of the cw4 group holding their members
+pub const ORGS: Map<&Addr, OrgProfile> = Map::new("orgs");
+
+/// roles of org members keyed by (org, member), dropped when the group removes the member
+pub const ORG_ROLES: Map<(&Addr, &Addr), Vec<OrgRole>> = Map::new("org_roles");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DelegateAction {
+    Accept,
+    Pay,
+    Create,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct DelegatePermissions {
+    pub accept: bool,
+    pub pay: bool,
+    pub create: bool,
+}
+
+impl DelegatePermissions {
+    pub fn allows(&self, action: &DelegateAction) -> bool {
+        match action {
+            DelegateAction::Accept => self.accept,
+            DelegateAction::Pay => self.pay,
+            DelegateAction::Create => self.create,
+        }
+    }
+}
+
+/// what a delegate may do for the owner, in the spirit of cw1 subkeys. Delegates pay with their
+/// own funds, core never holds the owner's, so the limits cap the exposure a delegate takes on
+/// for the owner: receivables it accepts and payments on invoices it did not accept itself
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Delegation {
+    pub permissions: DelegatePermissions,
+    /// remaining exposure per denom, denoms without a limit cannot be accepted or paid
+    pub spend_limits: Vec<Coin>,
+    pub expires: Expiration,
+}
+
+impl Delegation {
+    /// takes the coin off the remaining spend limit of its denom
+    pub fn spend(&mut self, coin: &Coin) -> StdResult<()> {
+        let limit = self
+            .spend_limits
+            .iter_mut()
+            .find(|limit| limit.denom == coin.denom)
+            .ok_or_else(|| StdError::generic_err(format!("No allowance for {}", coin.denom)))?;
+        limit.amount = limit
+            .amount
+            .checked_sub(coin.amount)
+            .map_err(|_| StdError::generic_err("Allowance exceeded"))?;
+        Ok(())
+    }
+
+    /// gives back a spend taken for an action that did not go through
+    pub fn restore(&mut self, coin: &Coin) {
+        if let Some(limit) = self
+            .spend_limits
+            .iter_mut()
+            .find(|limit| limit.denom == coin.denom)
+        {
+            limit.amount += coin.amount;
+        }
+    }
+}
+
+/// keyed by (owner, delegate)
+pub const DELEGATIONS: Map<(&Addr, &Addr), Delegation> = Map::new("delegations");
+
+/// invoices above a threshold need the approval of the payee's cw3 multisig to be accepted or paid
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ApprovalPolicy {
+    pub multisig: Addr,
+    /// receivables above the amount of their denom need approval, other denoms never do
+    pub thresholds: Vec<Coin>,
+}
+
+impl ApprovalPolicy {
+    pub fn requires_approval(&self, receivable: &Coin) -> bool {
+        self.thresholds.iter().any(|threshold| {
+            threshold.denom == receivable.denom && receivable.amount > threshold.amount
+        })
+    }
+}
+
+pub const APPROVAL_POLICY: Map<&Addr, ApprovalPolicy> = Map::new("approval_policy");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalAction {
+    Accept {},
+    /// the payment is held by core until the multisig approves or rejects it
+    Pay {
+        denom: Denom,
+        amount: Uint128,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PendingApproval {
+    pub invoice_id: u64,
+    pub action: ApprovalAction,
+    pub approver: Addr,
+    /// payee, or the org member acting for it
+    pub requested_by: Addr,
+    pub requested_at: Timestamp,
+}
+
+pub const PENDING_APPROVALS: Map<u64, PendingApproval> = Map::new("pending_approvals");
+
+/// addresses a profile refuses requests and invoices from, keyed by (blocker, blocked)
+pub const BLOCKED: Map<(&Addr, &Addr), Empty> = Map::new("blocked");
+
+/// PII a profile encrypted to a contact's viewing key, keyed by (owner, contact)
+pub const SHARED_PII: Map<(&Addr, &Addr), Binary> = Map::new("shared_pii");
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 
@@ -72,8 +507,146 @@ pub enum Status {
     Accepted,
     Paid,
     PartiallyPaid,
+    Rejected,
+    Cancelled,
+    Voided,
+    Refunded,
+    /// closed by a split ruling, the escrow was shared between the payee and the holder
+    Settled,
+    /// never stored, computed at query time for open invoices past their due date
+    Overdue,
+}
+
+impl Status {
+    /// name of the status in the invoice status index
+    pub fn key(&self) -> &'static str {
+        match self {
+            Status::Raised => "raised",
+            Status::Accepted => "accepted",
+            Status::Paid => "paid",
+            Status::PartiallyPaid => "partially_paid",
+            Status::Rejected => "rejected",
+            Status::Cancelled => "cancelled",
+            Status::Voided => "voided",
+            Status::Refunded => "refunded",
+            Status::Settled => "settled",
+            Status::Overdue => "overdue",
+        }
+    }
+
+    /// returns true if the invoice can no longer be accepted or paid
+    pub fn is_closed(&self) -> bool {
+        matches!(
+            self,
+            Status::Paid
+                | Status::Rejected
+                | Status::Cancelled
+                | Status::Voided
+                | Status::Refunded
+                | Status::Settled
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LateFee {
+    /// fraction of the receivable charged once the grace period is over
+    Rate(Decimal),
+    /// fixed amount in the receivable denom charged once the grace period is over
+    Flat(Uint128),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PaymentTerms {
+    /// fraction of the outstanding principal waived when settled within the discount window
+    pub discount_rate: Option<Decimal>,
+    /// number of days after issuing during which the discount applies
+    pub discount_days: u64,
+    pub late_fee: Option<LateFee>,
+    /// number of days after the due date before the late fee is charged
+    pub grace_period_days: u64,
+}
+
+/// breakdown of what it takes to settle an invoice at a given block
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct AmountDue {
+    pub principal: Uint128,
+    pub discount: Uint128,
+    pub penalty: Uint128,
+    pub total: Coin,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PaymentRecord {
+    pub payer: Addr,
+    pub amount: Coin,
+    pub principal: Uint128,
+    pub discount: Uint128,
+    pub penalty: Uint128,
+    pub paid_at: Timestamp,
+    /// who the payment was forwarded to, `None` while it is held in escrow
+    pub recipient: Option<Addr>,
+    /// group member that paid for an org payer
+    pub member: Option<Addr>,
+    /// kept by the protocol out of `amount` when it was forwarded, zero while escrowed
+    #[serde(default)]
+    pub protocol_fee: Uint128,
+}
+
+impl PaymentRecord {
+    /// what the recipient was sent
+    pub fn net(&self) -> Uint128 {
+        self.amount.amount - self.protocol_fee
+    }
 }
 
+pub const INVOICE_PAYMENTS: Map<&u64, Vec<PaymentRecord>> = Map::new("invoice_payments");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Installment {
+    pub amount: Uint128,
+    pub due_date: Timestamp,
+    /// principal applied to this installment so far
+    #[serde(default)]
+    pub paid: Uint128,
+}
+
+impl Installment {
+    pub fn is_paid(&self) -> bool {
+        self.paid >= self.amount
+    }
+
+    /// returns true if the installment is not fully paid and its due date has passed
+    pub fn is_overdue(&self, block: &BlockInfo) -> bool {
+        !self.is_paid() && block.time > self.due_date
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SettlementMode {
+    /// payments are forwarded to the issuer right away
+    #[default]
+    Direct,
+    /// payments are held by the contract until the payee confirms delivery or `release_at` passes
+    Escrow { release_at: Expiration },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct EscrowBalance {
+    pub denom: Denom,
+    pub amount: Uint128,
+    pub delivery_confirmed: bool,
+}
+
+pub const ESCROW: Map<&u64, EscrowBalance> = Map::new("escrow");
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum KYCStatus {
@@ -84,8 +657,135 @@ pub enum KYCStatus {
     ReVerify,
 }
 
+impl KYCStatus {
+    pub fn key(&self) -> &'static str {
+        match self {
+            KYCStatus::Unverified => "unverified",
+            KYCStatus::InProcess => "in_process",
+            KYCStatus::Rejected => "rejected",
+            KYCStatus::Approved => "approved",
+            KYCStatus::ReVerify => "re_verify",
+        }
+    }
+
+    /// transitions a KYC provider may apply, `ReVerify` also covers approvals that expired
+    pub fn can_transition_to(&self, next: &KYCStatus) -> bool {
+        matches!(
+            (self, next),
+            (KYCStatus::Unverified, KYCStatus::InProcess)
+                | (KYCStatus::InProcess, KYCStatus::Approved)
+                | (KYCStatus::InProcess, KYCStatus::Rejected)
+                | (KYCStatus::Rejected, KYCStatus::InProcess)
+                | (KYCStatus::Approved, KYCStatus::Approved)
+                | (KYCStatus::Approved, KYCStatus::Rejected)
+                | (KYCStatus::Approved, KYCStatus::ReVerify)
+                | (KYCStatus::ReVerify, KYCStatus::InProcess)
+                | (KYCStatus::ReVerify, KYCStatus::Approved)
+                | (KYCStatus::ReVerify, KYCStatus::Rejected)
+        )
+    }
+}
+
+/// off-chain evidence a KYC provider based its decision on
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct KycAttestation {
+    pub hash: String,
+    pub uri: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct KycRecord {
+    /// provider that last changed the KYC status
+    pub provider: Addr,
+    pub updated_at: Timestamp,
+    /// approvals past this time have to be verified again
+    pub expires_at: Option<Timestamp>,
+    pub attestation: Option<KycAttestation>,
+}
+
+pub const KYC_PROVIDERS: Map<&Addr, Empty> = Map::new("kyc_providers");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum KycRequirement {
+    None,
+    /// a provider has started verification and has not rejected it
+    Started,
+    Approved,
+}
+
+impl KycRequirement {
+    pub fn is_met_by(&self, status: &KYCStatus) -> bool {
+        match self {
+            KycRequirement::None => true,
+            KycRequirement::Started => {
+                !matches!(status, KYCStatus::Unverified | KYCStatus::Rejected)
+            }
+            KycRequirement::Approved => *status == KYCStatus::Approved,
+        }
+    }
+}
+
+/// KYC each side of an invoice needs to create and pay it
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
+pub struct KycPolicy {
+    pub issuer: KycRequirement,
+    pub payer: KycRequirement,
+}
+
+impl Default for KycPolicy {
+    fn default() -> Self {
+        KycPolicy {
+            issuer: KycRequirement::Approved,
+            payer: KycRequirement::Started,
+        }
+    }
+}
+
+pub const KYC_POLICY: Item<KycPolicy> = Item::new("kyc_policy");
+
+pub const MAX_FEE_BPS: u16 = 10_000;
+
+/// protocol fees, nothing is charged until an admin sets them
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct FeeConfig {
+    /// share of every payment kept when it reaches the receivable holder, in basis points,
+    /// escrowed payments are charged on release and refunded in full
+    pub payment_fee_bps: u16,
+    /// flat fee sent along with `CreateInvoice` or `CreateRecurring`
+    pub creation_fee: Option<Coin>,
+    /// highest payment fee per denom, for each forwarded payment or escrow release
+    pub caps: Vec<Coin>,
+}
+
+impl FeeConfig {
+    pub fn cap(&self, denom: &str) -> Option<Uint128> {
+        self.caps
+            .iter()
+            .find(|cap| cap.denom == denom)
+            .map(|cap| cap.amount)
+    }
+
+    pub fn payment_fee(&self, denom: &str, amount: Uint128) -> Uint128 {
+        let fee = amount.multiply_ratio(self.payment_fee_bps, MAX_FEE_BPS);
+        self.cap(denom).map_or(fee, |cap| fee.min(cap))
+    }
+}
+
+pub const FEE_CONFIG: Item<FeeConfig> = Item::new("fee_config");
+
+/// profiles that pay no protocol fees
+pub const FEE_EXEMPT: Map<&Addr, Empty> = Map::new("fee_exempt");
+
+/// collected fees not yet withdrawn, keyed by denom
+pub const TREASURY: Map<&str, Uint128> = Map::new("treasury");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
 pub struct Invoice {
     pub id: u64,
     pub from: Addr,
@@ -97,9 +797,440 @@ pub struct Invoice {
     pub amount_paid: Coin,
     pub service_type: ServiceType,
     pub status: Status,
+    pub remarks: Option<String>,
+    pub due_date: Option<Timestamp>,
+    pub issued_at: Option<Timestamp>,
+    pub accepted_at: Option<Timestamp>,
+    pub paid_at: Option<Timestamp>,
+    pub payment_terms: Option<PaymentTerms>,
+    #[serde(default)]
+    pub discount_applied: Uint128,
+    #[serde(default)]
+    pub penalty_paid: Uint128,
+    #[serde(default)]
+    pub settlement: SettlementMode,
+    /// payment schedule, empty if the invoice is due in one payment
+    #[serde(default)]
+    pub installments: Vec<Installment>,
+    #[serde(default)]
+    pub line_items: Vec<LineItem>,
+    /// acknowledged credit notes applied against the principal
+    #[serde(default)]
+    pub credited: Uint128,
+    /// open dispute freezing payments and transfers of the invoice
+    pub dispute_id: Option<u64>,
+    /// group member that raised the invoice for an org issuer
+    pub created_by: Option<Addr>,
+    /// group member that accepted the invoice for an org payee
+    pub accepted_by: Option<Addr>,
+}
+
+impl Invoice {
+    /// errors while a dispute freezes the invoice
+    pub fn ensure_not_disputed(&self) -> StdResult<()> {
+        if self.dispute_id.is_some() {
+            return Err(StdError::generic_err("Invoice is under dispute"));
+        }
+        Ok(())
+    }
+
+    /// returns true if the invoice is still open and its due date has passed
+    pub fn is_overdue(&self, block: &BlockInfo) -> bool {
+        match self.due_date {
+            Some(due_date) => !self.status.is_closed() && block.time > due_date,
+            None => false,
+        }
+    }
+
+    /// returns the stored status, or `Overdue` if the invoice is open and past its due date
+    pub fn current_status(&self, block: &BlockInfo) -> Status {
+        if self.is_overdue(block) {
+            Status::Overdue
+        } else {
+            self.status.clone()
+        }
+    }
+
+    /// principal not yet paid, waived by discount or credited
+    pub fn outstanding_principal(&self) -> Uint128 {
+        self.receivable.amount - self.amount_paid.amount - self.discount_applied - self.credited
+    }
+
+    /// returns the principal, discount and penalty that settle the invoice at this block
+    pub fn amount_due(&self, block: &BlockInfo) -> AmountDue {
+        let mut due = AmountDue {
+            principal: Uint128::zero(),
+            discount: Uint128::zero(),
+            penalty: Uint128::zero(),
+            total: Coin {
+                denom: self.receivable.denom.clone(),
+                amount: Uint128::zero(),
+            },
+        };
+        if self.status.is_closed() {
+            return due;
+        }
+
+        due.principal = self.outstanding_principal();
+        if let Some(terms) = &self.payment_terms {
+            //// early payment discount on whatever principal is still outstanding ////
+            if let (Some(rate), Some(issued_at)) = (terms.discount_rate, self.issued_at) {
+                if block.time <= issued_at.plus_seconds(terms.discount_days * SECONDS_PER_DAY) {
+                    due.discount = due.principal * rate;
+                }
+            }
+
+            //// one time late fee once the grace period is over ////
+            if let (Some(late_fee), Some(due_date)) = (&terms.late_fee, self.due_date) {
+                let grace_end = due_date.plus_seconds(terms.grace_period_days * SECONDS_PER_DAY);
+                if block.time > grace_end {
+                    let fee = match late_fee {
+                        LateFee::Rate(rate) => self.receivable.amount * *rate,
+                        LateFee::Flat(amount) => *amount,
+                    };
+                    due.penalty = fee.saturating_sub(self.penalty_paid);
+                }
+            }
+        }
+
+        due.total.amount = due.principal - due.discount + due.penalty;
+        due
+    }
+
+    /// applies settled principal to the earliest unpaid installments first
+    pub fn apply_to_installments(&mut self, mut amount: Uint128) {
+        for installment in self.installments.iter_mut() {
+            let applied = (installment.amount - installment.paid).min(amount);
+            installment.paid += applied;
+            amount -= applied;
+        }
+    }
+
+    /// returns the number of whole days since the due date, zero if not yet due
+    pub fn days_overdue(&self, block: &BlockInfo) -> u64 {
+        match self.due_date {
+            Some(due_date) if block.time > due_date => {
+                (block.time.seconds() - due_date.seconds()) / SECONDS_PER_DAY
+            }
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct RecurringInvoice {
+    pub id: u64,
+    pub from: Addr,
+    pub payee_address: Addr,
+    pub receivable: Coin,
+    pub service_type: ServiceType,
+    pub doc_uri: String,
+    pub payment_terms: Option<PaymentTerms>,
+    /// days between two invoices
+    pub interval_days: u64,
+    /// days each invoice is due after it is issued
+    pub payment_days: u64,
+    /// when the next invoice can be issued
+    pub next_issue_at: Timestamp,
+    /// no invoice is issued after this time
+    pub end_date: Option<Timestamp>,
+    /// maximum number of invoices to issue
+    pub max_count: Option<u64>,
+    /// invoices issued from this template so far
+    pub invoices: Vec<u64>,
+    pub cancelled: bool,
+}
+
+impl RecurringInvoice {
+    /// returns true if no more invoices will be issued from this template
+    pub fn is_finished(&self) -> bool {
+        self.cancelled
+            || self
+                .max_count
+                .is_some_and(|count| self.invoices.len() as u64 >= count)
+            || self
+                .end_date
+                .is_some_and(|end_date| self.next_issue_at > end_date)
+    }
+}
+
+/// what an issuer sets when raising an invoice with `CreateInvoice`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct InvoiceParams {
+    pub payee_address: Addr,
+    pub receivable: Coin,
+    pub amount_paid: Coin,
+    pub service_type: ServiceType,
+    pub doc_uri: String,
+    pub due_date: Timestamp,
+    pub payment_terms: Option<PaymentTerms>,
+    pub settlement: Option<SettlementMode>,
+    pub installments: Option<Vec<Installment>>,
+    pub line_items: Option<Vec<LineItem>>,
+}
+
+/// what an issuer sets when registering a recurring invoice
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct RecurringTemplate {
+    pub payee_address: Addr,
+    pub receivable: Coin,
+    pub service_type: ServiceType,
+    pub doc_uri: String,
+    pub payment_terms: Option<PaymentTerms>,
+    pub interval_days: u64,
+    pub payment_days: u64,
+    /// first issue time, defaults to now
+    pub start_at: Option<Timestamp>,
+    pub end_date: Option<Timestamp>,
+    pub max_count: Option<u64>,
 }
 
-pub const INVOICE: Map<&u64, Invoice> = Map::new("invoice");
+pub struct RecurringIndexes<'a> {
+    pub issuer: MultiIndex<'a, Addr, RecurringInvoice, u64>,
+    pub payee: MultiIndex<'a, Addr, RecurringInvoice, u64>,
+}
+
+impl<'a> IndexList<RecurringInvoice> for RecurringIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<RecurringInvoice>> + '_> {
+        let v: Vec<&dyn Index<RecurringInvoice>> = vec![&self.issuer, &self.payee];
+        Box::new(v.into_iter())
+    }
+}
+
+pub const RECURRING_INVOICE: IndexedMap<u64, RecurringInvoice, RecurringIndexes> = IndexedMap::new(
+    "recurring_invoice",
+    RecurringIndexes {
+        issuer: MultiIndex::new(
+            |_, recurring| recurring.from.clone(),
+            "recurring_invoice",
+            "recurring_invoice__issuer",
+        ),
+        payee: MultiIndex::new(
+            |_, recurring| recurring.payee_address.clone(),
+            "recurring_invoice",
+            "recurring_invoice__payee",
+        ),
+    },
+);
+
+pub const RECURRING_ID: Item<u64> = Item::new("recurring_id");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CreditNoteStatus {
+    /// waiting for the payee to acknowledge it
+    Pending,
+    Acknowledged,
+    Cancelled,
+    /// the refundable balance was paid back to the payee
+    Refunded,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct CreditNote {
+    pub id: u64,
+    pub invoice_id: u64,
+    pub from: Addr,
+    pub payee_address: Addr,
+    pub amount: Coin,
+    pub reason: String,
+    pub status: CreditNoteStatus,
+    pub created_at: Timestamp,
+    pub acknowledged_at: Option<Timestamp>,
+    /// part of the credit exceeding the outstanding principal, owed back to the payee
+    pub refund_due: Uint128,
+}
+
+pub const CREDIT_NOTE: Map<&u64, CreditNote> = Map::new("credit_note");
+
+pub const CREDIT_NOTE_ID: Item<u64> = Item::new("credit_note_id");
+
+/// credit note ids issued against each invoice, oldest first
+pub const INVOICE_CREDIT_NOTES: Map<&u64, Vec<u64>> = Map::new("invoice_credit_notes");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Arbitrator {
+    /// a single account rules on disputes
+    Address(Addr),
+    /// a cw3 multisig rules on disputes through its proposals
+    Multisig(Addr),
+}
+
+impl Arbitrator {
+    pub fn addr(&self) -> &Addr {
+        match self {
+            Arbitrator::Address(addr) | Arbitrator::Multisig(addr) => addr,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ArbitrationConfig {
+    pub arbitrator: Arbitrator,
+    /// days both parties have to submit evidence after a dispute is opened
+    pub evidence_days: u64,
+    /// days the arbitrator has to rule once the evidence period is over
+    pub ruling_days: u64,
+}
+
+pub const ARBITRATION: Item<ArbitrationConfig> = Item::new("arbitration");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DisputeStatus {
+    Open,
+    Resolved,
+    /// the arbitrator did not rule before the deadline
+    Expired,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Ruling {
+    /// escrowed funds go to the receivable holder and the invoice stays open
+    Release {},
+    /// escrowed funds go back to the payee and the invoice is refunded
+    Refund {},
+    /// the payee gets `payee_share` of the escrow, the holder the rest, and the invoice is refunded
+    Split { payee_share: Decimal },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Evidence {
+    pub submitter: Addr,
+    pub uri: String,
+    pub submitted_at: Timestamp,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Dispute {
+    pub id: u64,
+    pub invoice_id: u64,
+    pub from: Addr,
+    pub payee_address: Addr,
+    pub opened_by: Addr,
+    pub status: DisputeStatus,
+    pub evidence: Vec<Evidence>,
+    pub opened_at: Timestamp,
+    pub evidence_deadline: Timestamp,
+    pub ruling_deadline: Timestamp,
+    pub ruling: Option<Ruling>,
+    pub closed_at: Option<Timestamp>,
+}
+
+pub struct DisputeIndexes<'a> {
+    pub issuer: MultiIndex<'a, Addr, Dispute, u64>,
+    pub payee: MultiIndex<'a, Addr, Dispute, u64>,
+}
+
+impl<'a> IndexList<Dispute> for DisputeIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Dispute>> + '_> {
+        let v: Vec<&dyn Index<Dispute>> = vec![&self.issuer, &self.payee];
+        Box::new(v.into_iter())
+    }
+}
+
+pub const DISPUTE: IndexedMap<u64, Dispute, DisputeIndexes> = IndexedMap::new(
+    "dispute",
+    DisputeIndexes {
+        issuer: MultiIndex::new(
+            |_, dispute| dispute.from.clone(),
+            "dispute",
+            "dispute__issuer",
+        ),
+        payee: MultiIndex::new(
+            |_, dispute| dispute.payee_address.clone(),
+            "dispute",
+            "dispute__payee",
+        ),
+    },
+);
+
+pub const DISPUTE_ID: Item<u64> = Item::new("dispute_id");
+
+pub const SECONDS_PER_DAY: u64 = 86_400;
+
+/// criteria an invoice has to meet, unset fields match every invoice
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub struct InvoiceFilter {
+    pub issuer: Option<Addr>,
+    pub payee: Option<Addr>,
+    /// status at the current block, so `overdue` can be filtered on
+    pub status: Option<Status>,
+    pub denom: Option<String>,
+}
+
+impl InvoiceFilter {
+    pub fn matches(&self, invoice: &Invoice, block: &BlockInfo) -> bool {
+        self.issuer
+            .as_ref()
+            .is_none_or(|issuer| invoice.from == *issuer)
+            && self
+                .payee
+                .as_ref()
+                .is_none_or(|payee| invoice.payee_address == *payee)
+            && self
+                .denom
+                .as_ref()
+                .is_none_or(|denom| invoice.receivable.denom == *denom)
+            && self
+                .status
+                .as_ref()
+                .is_none_or(|status| invoice.current_status(block) == *status)
+    }
+}
+
+/// secondary indexes over the invoices, keyed by the invoice id
+pub struct InvoiceIndexes<'a> {
+    pub issuer: MultiIndex<'a, Addr, Invoice, u64>,
+    pub payee: MultiIndex<'a, Addr, Invoice, u64>,
+    /// stored status, `Overdue` is never indexed
+    pub status: MultiIndex<'a, String, Invoice, u64>,
+    pub denom: MultiIndex<'a, String, Invoice, u64>,
+}
+
+impl<'a> IndexList<Invoice> for InvoiceIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Invoice>> + '_> {
+        let v: Vec<&dyn Index<Invoice>> =
+            vec![&self.issuer, &self.payee, &self.status, &self.denom];
+        Box::new(v.into_iter())
+    }
+}
+
+pub const INVOICE: IndexedMap<u64, Invoice, InvoiceIndexes> = IndexedMap::new(
+    "invoice",
+    InvoiceIndexes {
+        issuer: MultiIndex::new(
+            |_, invoice| invoice.from.clone(),
+            "invoice",
+            "invoice__issuer",
+        ),
+        payee: MultiIndex::new(
+            |_, invoice| invoice.payee_address.clone(),
+            "invoice",
+            "invoice__payee",
+        ),
+        status: MultiIndex::new(
+            |_, invoice| invoice.status.key().to_string(),
+            "invoice",
+            "invoice__status",
+        ),
+        denom: MultiIndex::new(
+            |_, invoice| invoice.receivable.denom.clone(),
+            "invoice",
+            "invoice__denom",
+        ),
+    },
+);
 
 pub const CONFIG: Item<Config> = Item::new("config");
 
@@ -113,20 +1244,56 @@ pub fn get_invoice_id(deps: Deps) -> u64 {
 
 pub fn set_config(
     deps: DepsMut,
+    info: MessageInfo,
     nft_address: Addr,
     owner: Addr,
     accepted_assets: Vec<Asset>,
 ) -> Result<Response, ContractError> {
-    
+    //// only admins can update config ////
+    let admin_list = ADMIN_LIST.load(deps.storage)?;
+    if !admin_list.is_admin(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let nft_address = deps.api.addr_validate(nft_address.as_str())?;
+    let owner = deps.api.addr_validate(owner.as_str())?;
+    for asset in accepted_assets.iter() {
+        if let Denom::Cw20(address) = &asset.denom {
+            deps.api.addr_validate(address.as_str())?;
+        }
+    }
+
     let config = Config {
-        nft_address: nft_address,
-        owner: owner,
-        accepted_assets: accepted_assets,
+        nft_address,
+        owner,
+        accepted_assets,
     };
-    CONFIG.save(deps.storage, &config).unwrap();
-    Ok(Response::new())
+    CONFIG.save(deps.storage, &config)?;
+    Ok(Response::new()
+        .add_attribute("method", "set_config")
+        .add_attribute("sender", info.sender))
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub struct MigrateMsg {
+    /// only report how many records each pending step would visit, nothing is written
+    #[serde(default)]
+    pub dry_run: bool,
+    /// maximum number of records migrated in this call, migrate again to continue
+    pub batch_size: Option<u32>,
+}
+
+/// migration that did not fit in one batch, execute messages are refused until it is finished
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
 #[serde(rename_all = "snake_case")]
-pub struct MigrateMsg {}
+pub struct PendingMigration {
+    /// version of the stored data before the migration started
+    pub from_version: String,
+    /// name of the step to resume
+    pub step: String,
+    /// last key the step migrated
+    pub start_after: Option<Binary>,
+}
+
+pub const PENDING_MIGRATION: Item<PendingMigration> = Item::new("pending_migration");
@@ -0,0 +1,158 @@
+use crate::contract::{execute, instantiate};
+use crate::msg::{ExecuteMsg, InstantiateMsg};
+use crate::state::*;
+use cosmwasm_std::testing::{
+    mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage,
+};
+use cosmwasm_std::{
+    coin, from_binary, to_binary, Addr, ContractResult, Decimal, OwnedDeps, QuerierResult,
+    SystemError, SystemResult, WasmQuery,
+};
+use cw20::Denom;
+use cw721::{Cw721QueryMsg, OwnerOfResponse};
+
+pub const ADMIN: &str = "admin";
+pub const ISSUER: &str = "issuer";
+pub const PAYEE: &str = "payee";
+pub const KYC_PROVIDER: &str = "kyc_provider";
+
+pub type MockDeps = OwnedDeps<MockStorage, MockApi, MockQuerier>;
+
+/// instantiates core with an admin and two KYC approved profiles that are in each other's contacts
+pub fn setup() -> MockDeps {
+    let mut deps = mock_dependencies();
+    let msg = InstantiateMsg {
+        admins: vec![ADMIN.to_string()],
+        mutable: true,
+    };
+    instantiate(deps.as_mut(), mock_env(), mock_info(ADMIN, &[]), msg).unwrap();
+
+    let msgs = vec![
+        (
+            ADMIN,
+            ExecuteMsg::SetConfig {
+                nft_address: Addr::unchecked("nft"),
+                owner: Addr::unchecked(ADMIN),
+                accepted_assets: vec![Asset {
+                    name: "atom".to_string(),
+                    denom: Denom::Native("uatom".to_string()),
+                    decimal: 6,
+                    uri: None,
+                }],
+            },
+        ),
+        (ISSUER, create_profile_msg()),
+        (PAYEE, create_profile_msg()),
+        (
+            ADMIN,
+            ExecuteMsg::AddKycProviders {
+                providers: vec![KYC_PROVIDER.to_string()],
+            },
+        ),
+        (
+            ISSUER,
+            ExecuteMsg::CreateRequest {
+                address: Addr::unchecked(PAYEE),
+            },
+        ),
+        (
+            PAYEE,
+            ExecuteMsg::AcceptRequest {
+                address: Addr::unchecked(ISSUER),
+            },
+        ),
+    ];
+    for (sender, msg) in msgs {
+        execute(deps.as_mut(), mock_env(), mock_info(sender, &[]), msg).unwrap();
+    }
+    for address in [ISSUER, PAYEE] {
+        for status in [KYCStatus::InProcess, KYCStatus::Approved] {
+            let msg = ExecuteMsg::UpdateKyc {
+                address: Addr::unchecked(address),
+                status,
+                expires_at: None,
+                attestation: None,
+            };
+            execute(deps.as_mut(), mock_env(), mock_info(KYC_PROVIDER, &[]), msg).unwrap();
+        }
+    }
+    mock_nft_owner(&mut deps, ISSUER);
+    deps
+}
+
+/// makes the mocked nft contract report `owner` as the holder of every token
+pub fn mock_nft_owner(deps: &mut MockDeps, owner: &str) {
+    let owner = owner.to_string();
+    deps.querier.update_wasm(move |query| match query {
+        WasmQuery::Smart { msg, .. } => match from_binary(msg) {
+            Ok(Cw721QueryMsg::OwnerOf { .. }) => {
+                let res = OwnerOfResponse {
+                    owner: owner.clone(),
+                    approvals: vec![],
+                };
+                SystemResult::Ok(ContractResult::Ok(to_binary(&res).unwrap()))
+            }
+            _ => unsupported_query(query),
+        },
+        _ => unsupported_query(query),
+    });
+}
+
+/// fails a mocked query the test did not expect, instead of panicking inside the querier
+pub fn unsupported_query(query: &WasmQuery) -> QuerierResult {
+    SystemResult::Err(SystemError::UnsupportedRequest {
+        kind: format!("{:?}", query),
+    })
+}
+
+/// same as `setup`, plus a 1000uatom "2/10 net 30" invoice raised by the issuer
+pub fn setup_with_invoice(settlement: Option<SettlementMode>) -> (MockDeps, u64) {
+    let mut deps = setup();
+    let msg = ExecuteMsg::CreateInvoice {
+        payee_address: Addr::unchecked(PAYEE),
+        receivable: coin(1000, "uatom"),
+        amount_paid: coin(0, "uatom"),
+        service_type: ServiceType::Service,
+        doc_uri: "ipfs://invoice".to_string(),
+        due_date: mock_env().block.time.plus_seconds(30 * SECONDS_PER_DAY),
+        payment_terms: Some(PaymentTerms {
+            discount_rate: Some(Decimal::percent(2)),
+            discount_days: 10,
+            late_fee: Some(LateFee::Rate(Decimal::percent(5))),
+            grace_period_days: 5,
+        }),
+        settlement,
+        installments: None,
+        line_items: None,
+    };
+    execute(deps.as_mut(), mock_env(), mock_info(ISSUER, &[]), msg).unwrap();
+
+    let invoice_id = INVOICE_ID.load(&deps.storage).unwrap() - 1;
+    (deps, invoice_id)
+}
+
+pub fn create_profile_msg() -> ExecuteMsg {
+    ExecuteMsg::CreateProfile {
+        name: "name".to_string(),
+        email_id: "name@example.com".to_string(),
+        phone_number: "0000000000".to_string(),
+        company_name: "company".to_string(),
+        address: "street".to_string(),
+    }
+}
+
+/// a plain 1000uatom invoice from the issuer to the payee, due in 30 days
+pub fn create_invoice_msg() -> ExecuteMsg {
+    ExecuteMsg::CreateInvoice {
+        payee_address: Addr::unchecked(PAYEE),
+        receivable: coin(1000, "uatom"),
+        amount_paid: coin(0, "uatom"),
+        service_type: ServiceType::Service,
+        doc_uri: "ipfs://invoice".to_string(),
+        due_date: mock_env().block.time.plus_seconds(30 * SECONDS_PER_DAY),
+        payment_terms: None,
+        settlement: None,
+        installments: None,
+        line_items: None,
+    }
+}
@@ -0,0 +1,86 @@
+#[cfg(not(feature = "library"))]
+use cosmwasm_std::entry_point;
+use cosmwasm_std::{
+    to_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult,
+};
+
+use crate::error::ContractError;
+use crate::market;
+use crate::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg};
+use crate::query::*;
+use crate::state::*;
+use cw2::set_contract_version;
+// version info for migration info
+const CONTRACT_NAME: &str = env!("CARGO_PKG_NAME");
+const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn instantiate(
+    deps: DepsMut,
+    _env: Env,
+    _info: MessageInfo,
+    msg: InstantiateMsg,
+) -> StdResult<Response> {
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    let config = Config {
+        core_address: deps.api.addr_validate(&msg.core_address)?,
+    };
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "instantiate")
+        .add_attribute("core_address", config.core_address))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn execute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    match msg {
+        ExecuteMsg::ReceiveNft(msg) => market::receive_nft(deps, env, info, msg),
+        ExecuteMsg::Receive(msg) => market::receive_cw20(deps, env, info, msg),
+        ExecuteMsg::UpdateListing { invoice_id, price } => {
+            market::update_listing(deps, env, info, invoice_id, price)
+        }
+        ExecuteMsg::CancelListing { invoice_id } => {
+            market::cancel_listing(deps, env, info, invoice_id)
+        }
+        ExecuteMsg::Buy { invoice_id } => market::buy(deps, env, info, invoice_id),
+        ExecuteMsg::PlaceBid {
+            invoice_id,
+            expires,
+        } => market::place_bid(deps, env, info, invoice_id, expires),
+        ExecuteMsg::CancelBid { invoice_id } => market::cancel_bid(deps, env, info, invoice_id),
+        ExecuteMsg::AcceptBid { invoice_id, bidder } => {
+            market::accept_bid(deps, env, info, invoice_id, bidder)
+        }
+    }
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::GetConfig {} => to_binary(&get_config(deps)?),
+        QueryMsg::GetListing { invoice_id } => to_binary(&get_listing(deps, env, invoice_id)?),
+        QueryMsg::GetListings { start_after, limit } => {
+            to_binary(&get_listings(deps, env, start_after, limit)?)
+        }
+        QueryMsg::GetBids { invoice_id } => to_binary(&get_bids(deps, invoice_id)?),
+    }
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    let ver = cw2::get_contract_version(deps.storage)?;
+    // ensure we are migrating from an allowed contract
+    if ver.contract != CONTRACT_NAME {
+        return Err(StdError::generic_err("Can only upgrade from same type").into());
+    }
+    // set the new version
+    cw2::set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    Ok(Response::default())
+}
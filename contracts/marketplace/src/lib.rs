@@ -0,0 +1,18 @@
+/*!
+Marketplace for factoring RWA invoices.
+
+Issuers list the NFT of an accepted invoice by sending it to this contract
+with cw721 `SendNft`, at a price that is usually a discount to the amount
+still due. Financiers can buy a listing at its fixed price or place expiring
+bids, escrowed by the contract, that the seller may accept. Once sold, the
+buyer holds the invoice NFT and every later `PayInvoice` on core settles to
+them.
+*/
+
+pub mod contract;
+pub mod error;
+pub mod market;
+pub mod msg;
+pub mod query;
+pub mod state;
+pub use crate::error::ContractError;
@@ -0,0 +1,756 @@
+use crate::msg::{NftReceiveMsg, ReceiveMsg};
+use crate::state::*;
+use cosmwasm_std::{
+    from_binary, to_binary, Addr, Coin, CosmosMsg, Deps, DepsMut, Env, MessageInfo, Order,
+    Response, StdError, StdResult, Uint128, WasmMsg,
+};
+use cw20::{Cw20ReceiveMsg, Denom};
+use cw721::{Cw721ExecuteMsg, Cw721ReceiveMsg};
+use cw_utils::Expiration;
+use rwa_core::helpers::{denom_key, transfer_msg};
+use rwa_core::msg::QueryMsg as CoreQueryMsg;
+use rwa_core::state::{
+    Config as CoreConfig, EscrowBalance, Invoice, PaymentRecord, SettlementMode, Status,
+};
+
+use crate::error::ContractError;
+
+pub fn receive_nft(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    wrapper: Cw721ReceiveMsg,
+) -> Result<Response, ContractError> {
+    //// only invoice nfts minted by core can be listed ////
+    let core_config = query_core_config(deps.as_ref())?;
+    if info.sender != core_config.nft_address {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let seller = deps.api.addr_validate(&wrapper.sender)?;
+    let invoice_id: u64 = wrapper
+        .token_id
+        .parse()
+        .map_err(|_| StdError::generic_err("Invalid invoice id"))?;
+    match from_binary(&wrapper.msg)? {
+        NftReceiveMsg::List { price } => list_invoice(deps, env, seller, invoice_id, price),
+    }
+}
+
+fn list_invoice(
+    deps: DepsMut,
+    env: Env,
+    seller: Addr,
+    invoice_id: u64,
+    price: Coin,
+) -> Result<Response, ContractError> {
+    validate_price(deps.as_ref(), &price)?;
+
+    //// only accepted invoices that are still open can be factored ////
+    let invoice = query_invoice(deps.as_ref(), invoice_id)?;
+    if invoice.status == Status::Raised || invoice.status.is_closed() {
+        return Err(StdError::generic_err("Invoice is not open for factoring").into());
+    }
+    invoice.ensure_not_disputed()?;
+
+    //// goods still awaiting delivery keep their payments in escrow ////
+    if invoice.settlement != SettlementMode::Direct {
+        let escrow: Option<EscrowBalance> = deps.querier.query_wasm_smart(
+            CONFIG.load(deps.storage)?.core_address,
+            &CoreQueryMsg::GetEscrow { invoice_id },
+        )?;
+        if !escrow.map(|e| e.delivery_confirmed).unwrap_or_default() {
+            return Err(StdError::generic_err("Delivery not yet confirmed").into());
+        }
+    }
+
+    //// only payments made from now on are collected for the seller ////
+    let payments_before = query_payments(deps.as_ref(), invoice_id)?.len() as u64;
+    let listing = Listing {
+        invoice_id,
+        seller: seller.clone(),
+        price: price.clone(),
+        listed_at: env.block.time,
+        payments_before,
+    };
+    LISTINGS.save(deps.storage, invoice_id, &listing)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "list_invoice")
+        .add_attribute("invoice_id", invoice_id.to_string())
+        .add_attribute("seller", seller)
+        .add_attribute("price", price.to_string()))
+}
+
+pub fn update_listing(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    invoice_id: u64,
+    price: Coin,
+) -> Result<Response, ContractError> {
+    let mut listing = LISTINGS.load(deps.storage, invoice_id)?;
+    if listing.seller != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    validate_price(deps.as_ref(), &price)?;
+    listing.price = price.clone();
+    LISTINGS.save(deps.storage, invoice_id, &listing)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "update_listing")
+        .add_attribute("invoice_id", invoice_id.to_string())
+        .add_attribute("price", price.to_string()))
+}
+
+pub fn cancel_listing(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    invoice_id: u64,
+) -> Result<Response, ContractError> {
+    let listing = LISTINGS.load(deps.storage, invoice_id)?;
+    if listing.seller != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    //// nft goes back to the seller ////
+    let messages = close_listing(deps, &env, &listing, &listing.seller)?;
+
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_attribute("method", "cancel_listing")
+        .add_attribute("invoice_id", invoice_id.to_string()))
+}
+
+pub fn buy(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    invoice_id: u64,
+) -> Result<Response, ContractError> {
+    let funds = match info.funds.as_slice() {
+        [fund] => fund,
+        _ => return Err(StdError::generic_err("Accepts only one token").into()),
+    };
+    execute_buy(
+        deps,
+        env,
+        info.sender,
+        Denom::Native(funds.denom.clone()),
+        funds.amount,
+        invoice_id,
+    )
+}
+
+pub fn place_bid(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    invoice_id: u64,
+    expires: Expiration,
+) -> Result<Response, ContractError> {
+    let funds = match info.funds.as_slice() {
+        [fund] => fund,
+        _ => return Err(StdError::generic_err("Accepts only one token").into()),
+    };
+    execute_place_bid(
+        deps,
+        env,
+        info.sender,
+        Denom::Native(funds.denom.clone()),
+        funds.amount,
+        invoice_id,
+        expires,
+    )
+}
+
+pub fn receive_cw20(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    wrapper: Cw20ReceiveMsg,
+) -> Result<Response, ContractError> {
+    //// the cw20 contract is the sender, the buyer is wrapped inside ////
+    let sender = deps.api.addr_validate(&wrapper.sender)?;
+    let denom = Denom::Cw20(info.sender);
+    match from_binary(&wrapper.msg)? {
+        ReceiveMsg::Buy { invoice_id } => {
+            execute_buy(deps, env, sender, denom, wrapper.amount, invoice_id)
+        }
+        ReceiveMsg::PlaceBid {
+            invoice_id,
+            expires,
+        } => execute_place_bid(
+            deps,
+            env,
+            sender,
+            denom,
+            wrapper.amount,
+            invoice_id,
+            expires,
+        ),
+    }
+}
+
+fn execute_buy(
+    deps: DepsMut,
+    env: Env,
+    buyer: Addr,
+    denom: Denom,
+    amount: Uint128,
+    invoice_id: u64,
+) -> Result<Response, ContractError> {
+    let listing = LISTINGS.load(deps.storage, invoice_id)?;
+    if listing.seller == buyer {
+        return Err(StdError::generic_err("Seller cannot buy own listing").into());
+    }
+
+    //// fixed price listings have to be paid exactly ////
+    if denom_key(&denom) != listing.price.denom || amount != listing.price.amount {
+        return Err(StdError::generic_err("Payment does not match listing price").into());
+    }
+
+    let mut messages = vec![transfer_msg(&denom, &listing.seller, amount)?];
+    messages.extend(close_listing(deps, &env, &listing, &buyer)?);
+
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_attribute("method", "buy")
+        .add_attribute("invoice_id", invoice_id.to_string())
+        .add_attribute("buyer", buyer)
+        .add_attribute("price", listing.price.to_string()))
+}
+
+fn execute_place_bid(
+    deps: DepsMut,
+    env: Env,
+    bidder: Addr,
+    denom: Denom,
+    amount: Uint128,
+    invoice_id: u64,
+    expires: Expiration,
+) -> Result<Response, ContractError> {
+    let listing = LISTINGS.load(deps.storage, invoice_id)?;
+    if listing.seller == bidder {
+        return Err(StdError::generic_err("Seller cannot bid on own listing").into());
+    }
+
+    if expires.is_expired(&env.block) {
+        return Err(StdError::generic_err("Bid already expired").into());
+    }
+
+    let amount = Coin {
+        denom: denom_key(&denom),
+        amount,
+    };
+    validate_price(deps.as_ref(), &amount)?;
+
+    //// one open bid per bidder, cancel it first to change it ////
+    if BIDS.has(deps.storage, (invoice_id, &bidder)) {
+        return Err(StdError::generic_err("Bid already placed").into());
+    }
+
+    let bid = Bid {
+        invoice_id,
+        bidder: bidder.clone(),
+        denom,
+        amount: amount.amount,
+        expires,
+    };
+    BIDS.save(deps.storage, (invoice_id, &bidder), &bid)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "place_bid")
+        .add_attribute("invoice_id", invoice_id.to_string())
+        .add_attribute("bidder", bidder)
+        .add_attribute("amount", amount.to_string()))
+}
+
+pub fn cancel_bid(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    invoice_id: u64,
+) -> Result<Response, ContractError> {
+    let bid = BIDS
+        .may_load(deps.storage, (invoice_id, &info.sender))?
+        .ok_or_else(|| StdError::generic_err("No bid found"))?;
+    BIDS.remove(deps.storage, (invoice_id, &info.sender));
+
+    let refund = refund_bid_msg(&bid)?;
+
+    Ok(Response::new()
+        .add_message(refund)
+        .add_attribute("method", "cancel_bid")
+        .add_attribute("invoice_id", invoice_id.to_string())
+        .add_attribute("bidder", info.sender))
+}
+
+pub fn accept_bid(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    invoice_id: u64,
+    bidder: Addr,
+) -> Result<Response, ContractError> {
+    let listing = LISTINGS.load(deps.storage, invoice_id)?;
+    if listing.seller != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let bid = BIDS
+        .may_load(deps.storage, (invoice_id, &bidder))?
+        .ok_or_else(|| StdError::generic_err("No bid found"))?;
+    if bid.expires.is_expired(&env.block) {
+        return Err(StdError::generic_err("Bid expired").into());
+    }
+    BIDS.remove(deps.storage, (invoice_id, &bidder));
+
+    let mut messages = vec![transfer_msg(&bid.denom, &listing.seller, bid.amount)?];
+    messages.extend(close_listing(deps, &env, &listing, &bidder)?);
+
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_attribute("method", "accept_bid")
+        .add_attribute("invoice_id", invoice_id.to_string())
+        .add_attribute("buyer", bidder)
+        .add_attribute("price", format!("{}{}", bid.amount, denom_key(&bid.denom))))
+}
+
+/// removes the listing, hands the nft to `recipient`, refunds the remaining bids
+/// and forwards invoice payments received while listed to the seller
+fn close_listing(
+    deps: DepsMut,
+    env: &Env,
+    listing: &Listing,
+    recipient: &Addr,
+) -> StdResult<Vec<CosmosMsg>> {
+    let invoice_id = listing.invoice_id;
+    let invoice = query_invoice(deps.as_ref(), invoice_id)?;
+    //// a disputed invoice cannot change hands until the arbitrator rules ////
+    invoice.ensure_not_disputed()?;
+    //// once paid or voided there is nothing left to collect, only the seller takes it back ////
+    if *recipient != listing.seller && invoice.status.is_closed() {
+        return Err(StdError::generic_err("Invoice is closed"));
+    }
+    LISTINGS.remove(deps.storage, invoice_id);
+
+    let core_config = query_core_config(deps.as_ref())?;
+    let mut messages: Vec<CosmosMsg> = vec![CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: core_config.nft_address.to_string(),
+        msg: to_binary(&Cw721ExecuteMsg::TransferNft {
+            recipient: recipient.to_string(),
+            token_id: invoice_id.to_string(),
+        })?,
+        funds: vec![],
+    })];
+
+    let bids = BIDS
+        .prefix(invoice_id)
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for (bidder, bid) in bids {
+        BIDS.remove(deps.storage, (invoice_id, &bidder));
+        messages.push(refund_bid_msg(&bid)?);
+    }
+
+    //// core pays the nft holder, which was this contract while listed ////
+    let payments = query_payments(deps.as_ref(), invoice_id)?;
+    let received = payments
+        .iter()
+        .skip(listing.payments_before as usize)
+        .filter(|p| p.recipient.as_ref() == Some(&env.contract.address))
+        .fold(Uint128::zero(), |total, p| total + p.net());
+    if !received.is_zero() {
+        let denom = resolve_denom(deps.as_ref(), &payments[0].amount.denom)?;
+        messages.push(transfer_msg(&denom, &listing.seller, received)?);
+    }
+
+    Ok(messages)
+}
+
+fn refund_bid_msg(bid: &Bid) -> StdResult<CosmosMsg> {
+    transfer_msg(&bid.denom, &bid.bidder, bid.amount)
+}
+
+/// prices and bids must be in an asset accepted by core
+fn validate_price(deps: Deps, price: &Coin) -> StdResult<()> {
+    if price.amount.is_zero() {
+        return Err(StdError::generic_err("Price cannot be zero"));
+    }
+    resolve_denom(deps, &price.denom)?;
+    Ok(())
+}
+
+/// maps an invoice denom (native denom or cw20 address) to the accepted core asset
+fn resolve_denom(deps: Deps, denom: &str) -> StdResult<Denom> {
+    let core_config = query_core_config(deps)?;
+    core_config
+        .accepted_assets
+        .into_iter()
+        .find(|asset| asset.matches(denom))
+        .map(|asset| asset.denom)
+        .ok_or_else(|| StdError::generic_err("Asset not accepted"))
+}
+
+pub fn query_core_config(deps: Deps) -> StdResult<CoreConfig> {
+    let config = CONFIG.load(deps.storage)?;
+    deps.querier
+        .query_wasm_smart(config.core_address, &CoreQueryMsg::GetConfig {})
+}
+
+pub fn query_invoice(deps: Deps, invoice_id: u64) -> StdResult<Invoice> {
+    let config = CONFIG.load(deps.storage)?;
+    deps.querier.query_wasm_smart(
+        config.core_address,
+        &CoreQueryMsg::GetInvoice { invoice_id },
+    )
+}
+
+fn query_payments(deps: Deps, invoice_id: u64) -> StdResult<Vec<PaymentRecord>> {
+    let config = CONFIG.load(deps.storage)?;
+    deps.querier.query_wasm_smart(
+        config.core_address,
+        &CoreQueryMsg::GetInvoicePayments { invoice_id },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::instantiate;
+    use crate::msg::InstantiateMsg;
+    use crate::query::get_listing;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{
+        coin, BankMsg, ContractResult, Decimal, SubMsg, SystemResult, Timestamp, WasmQuery,
+    };
+    use rwa_core::state::{Asset, ServiceType, SECONDS_PER_DAY};
+    use rwa_core::testing::{unsupported_query, MockDeps};
+
+    const SELLER: &str = "issuer";
+    const BUYER: &str = "financier";
+    const INVOICE_ID: u64 = 1000001;
+
+    fn invoice(env: &Env, due_amount: Coin) -> Invoice {
+        Invoice {
+            id: INVOICE_ID,
+            from: Addr::unchecked(SELLER),
+            payee_address: Addr::unchecked("payee"),
+            nft_id: INVOICE_ID,
+            doc_uri: "ipfs://invoice".to_string(),
+            due_amount: due_amount.clone(),
+            receivable: due_amount,
+            amount_paid: coin(0, "uatom"),
+            service_type: ServiceType::Service,
+            status: Status::Accepted,
+            remarks: None,
+            due_date: Some(env.block.time.plus_seconds(73 * SECONDS_PER_DAY)),
+            issued_at: Some(env.block.time),
+            accepted_at: Some(env.block.time),
+            paid_at: None,
+            payment_terms: None,
+            discount_applied: Uint128::zero(),
+            penalty_paid: Uint128::zero(),
+            settlement: SettlementMode::Direct,
+            installments: vec![],
+            line_items: vec![],
+            credited: Uint128::zero(),
+            dispute_id: None,
+            created_by: None,
+            accepted_by: None,
+        }
+    }
+
+    /// instantiates the marketplace against a mocked core that reports `payments`
+    fn setup(payments: Vec<PaymentRecord>) -> MockDeps {
+        let mut deps = mock_dependencies();
+        let msg = InstantiateMsg {
+            core_address: "core".to_string(),
+        };
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        mock_core(&mut deps, Status::Accepted, payments);
+        deps
+    }
+
+    /// makes the mocked core report the invoice in `status` with `payments`
+    fn mock_core(deps: &mut MockDeps, status: Status, payments: Vec<PaymentRecord>) {
+        let invoice = Invoice {
+            status,
+            ..invoice(&mock_env(), coin(1000, "uatom"))
+        };
+        deps.querier.update_wasm(move |query| match query {
+            WasmQuery::Smart { msg, .. } => {
+                let res = match from_binary(msg) {
+                    Ok(CoreQueryMsg::GetConfig {}) => to_binary(&CoreConfig {
+                        nft_address: Addr::unchecked("nft"),
+                        owner: Addr::unchecked("admin"),
+                        accepted_assets: vec![Asset {
+                            name: "atom".to_string(),
+                            denom: Denom::Native("uatom".to_string()),
+                            decimal: 6,
+                            uri: None,
+                        }],
+                    }),
+                    Ok(CoreQueryMsg::GetInvoice { .. }) => to_binary(&invoice),
+                    Ok(CoreQueryMsg::GetInvoicePayments { .. }) => to_binary(&payments),
+                    _ => return unsupported_query(query),
+                };
+                SystemResult::Ok(ContractResult::Ok(res.unwrap()))
+            }
+            _ => unsupported_query(query),
+        });
+    }
+
+    fn list(deps: &mut MockDeps, price: Coin) {
+        let msg = Cw721ReceiveMsg {
+            sender: SELLER.to_string(),
+            token_id: INVOICE_ID.to_string(),
+            msg: to_binary(&NftReceiveMsg::List { price }).unwrap(),
+        };
+        receive_nft(deps.as_mut(), mock_env(), mock_info("nft", &[]), msg).unwrap();
+    }
+
+    fn transfer_nft_msg(recipient: &str) -> SubMsg {
+        SubMsg::new(WasmMsg::Execute {
+            contract_addr: "nft".to_string(),
+            msg: to_binary(&Cw721ExecuteMsg::TransferNft {
+                recipient: recipient.to_string(),
+                token_id: INVOICE_ID.to_string(),
+            })
+            .unwrap(),
+            funds: vec![],
+        })
+    }
+
+    #[test]
+    fn only_invoice_nfts_can_be_listed() {
+        let mut deps = setup(vec![]);
+        let msg = Cw721ReceiveMsg {
+            sender: SELLER.to_string(),
+            token_id: INVOICE_ID.to_string(),
+            msg: to_binary(&NftReceiveMsg::List {
+                price: coin(900, "uatom"),
+            })
+            .unwrap(),
+        };
+        let err =
+            receive_nft(deps.as_mut(), mock_env(), mock_info("other_nft", &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+    }
+
+    #[test]
+    fn buyer_pays_listing_price_and_takes_the_nft() {
+        let mut deps = setup(vec![]);
+        list(&mut deps, coin(900, "uatom"));
+
+        let listing = get_listing(deps.as_ref(), mock_env(), INVOICE_ID).unwrap();
+        assert_eq!(listing.implied_yield, Some(Decimal::from_ratio(1u64, 9u64)));
+        assert_eq!(
+            listing.annualized_yield,
+            Some(Decimal::from_ratio(5u64, 9u64))
+        );
+
+        let err = buy(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(BUYER, &[coin(800, "uatom")]),
+            INVOICE_ID,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            StdError::generic_err("Payment does not match listing price").into()
+        );
+
+        let res = buy(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(BUYER, &[coin(900, "uatom")]),
+            INVOICE_ID,
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: SELLER.to_string(),
+                    amount: vec![coin(900, "uatom")],
+                }),
+                transfer_nft_msg(BUYER),
+            ]
+        );
+        assert!(!LISTINGS.has(&deps.storage, INVOICE_ID));
+    }
+
+    #[test]
+    fn accepting_a_bid_refunds_the_others() {
+        let mut deps = setup(vec![]);
+        list(&mut deps, coin(950, "uatom"));
+
+        let expires = Expiration::AtTime(mock_env().block.time.plus_seconds(SECONDS_PER_DAY));
+        for (bidder, amount) in [("alice", 900), ("bob", 920)] {
+            place_bid(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(bidder, &[coin(amount, "uatom")]),
+                INVOICE_ID,
+                expires,
+            )
+            .unwrap();
+        }
+
+        let err = accept_bid(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(BUYER, &[]),
+            INVOICE_ID,
+            Addr::unchecked("bob"),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        //// expired bids can no longer be accepted ////
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(2 * SECONDS_PER_DAY);
+        let err = accept_bid(
+            deps.as_mut(),
+            env,
+            mock_info(SELLER, &[]),
+            INVOICE_ID,
+            Addr::unchecked("bob"),
+        )
+        .unwrap_err();
+        assert_eq!(err, StdError::generic_err("Bid expired").into());
+
+        let res = accept_bid(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(SELLER, &[]),
+            INVOICE_ID,
+            Addr::unchecked("bob"),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: SELLER.to_string(),
+                    amount: vec![coin(920, "uatom")],
+                }),
+                transfer_nft_msg("bob"),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "alice".to_string(),
+                    amount: vec![coin(900, "uatom")],
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn closed_invoices_only_go_back_to_the_seller() {
+        let mut deps = setup(vec![]);
+        list(&mut deps, coin(950, "uatom"));
+        let expires = Expiration::AtTime(mock_env().block.time.plus_seconds(SECONDS_PER_DAY));
+        for (bidder, amount) in [("alice", 900), ("bob", 920)] {
+            place_bid(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(bidder, &[coin(amount, "uatom")]),
+                INVOICE_ID,
+                expires,
+            )
+            .unwrap();
+        }
+
+        //// the invoice was paid in full while listed ////
+        mock_core(&mut deps, Status::Paid, vec![]);
+        let err = accept_bid(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(SELLER, &[]),
+            INVOICE_ID,
+            Addr::unchecked("bob"),
+        )
+        .unwrap_err();
+        assert_eq!(err, StdError::generic_err("Invoice is closed").into());
+        let err = buy(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(BUYER, &[coin(950, "uatom")]),
+            INVOICE_ID,
+        )
+        .unwrap_err();
+        assert_eq!(err, StdError::generic_err("Invoice is closed").into());
+
+        //// bids are refunded in the asset they were placed in, without asking core ////
+        deps.querier.update_wasm(unsupported_query);
+        let res = cancel_bid(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            INVOICE_ID,
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "alice".to_string(),
+                amount: vec![coin(900, "uatom")],
+            })]
+        );
+    }
+
+    #[test]
+    fn payments_received_while_listed_go_to_the_seller() {
+        let listed_at: Timestamp = mock_env().block.time;
+        let record = |recipient: &str, paid_at: Timestamp| PaymentRecord {
+            payer: Addr::unchecked("payee"),
+            amount: coin(100, "uatom"),
+            principal: Uint128::new(100),
+            discount: Uint128::zero(),
+            penalty: Uint128::zero(),
+            paid_at,
+            recipient: Some(Addr::unchecked(recipient)),
+            member: None,
+            //// core kept a 1% protocol fee before forwarding ////
+            protocol_fee: Uint128::new(1),
+        };
+        let mut payments = vec![record(SELLER, listed_at.minus_seconds(10))];
+        let mut deps = setup(payments.clone());
+        list(&mut deps, coin(900, "uatom"));
+        payments.push(record(mock_env().contract.address.as_str(), listed_at));
+        mock_core(&mut deps, Status::PartiallyPaid, payments);
+
+        let res = cancel_listing(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(SELLER, &[]),
+            INVOICE_ID,
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                transfer_nft_msg(SELLER),
+                SubMsg::new(BankMsg::Send {
+                    to_address: SELLER.to_string(),
+                    amount: vec![coin(99, "uatom")],
+                }),
+            ]
+        );
+
+        //// relisting in the same block does not forward the payment twice ////
+        list(&mut deps, coin(900, "uatom"));
+        let res = cancel_listing(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(SELLER, &[]),
+            INVOICE_ID,
+        )
+        .unwrap();
+        assert_eq!(res.messages, vec![transfer_nft_msg(SELLER)]);
+    }
+}
@@ -0,0 +1,82 @@
+use cosmwasm_std::{Addr, Coin};
+use cw20::Cw20ReceiveMsg;
+use cw721::Cw721ReceiveMsg;
+use cw_utils::Expiration;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
+#[serde(rename_all = "snake_case")]
+pub struct InstantiateMsg {
+    pub core_address: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    /// lists an invoice NFT sent with cw721 `SendNft`
+    ReceiveNft(Cw721ReceiveMsg),
+    /// buys or bids with cw20 tokens sent with cw20 `Send`
+    Receive(Cw20ReceiveMsg),
+    UpdateListing {
+        invoice_id: u64,
+        price: Coin,
+    },
+    CancelListing {
+        invoice_id: u64,
+    },
+    Buy {
+        invoice_id: u64,
+    },
+    PlaceBid {
+        invoice_id: u64,
+        expires: Expiration,
+    },
+    CancelBid {
+        invoice_id: u64,
+    },
+    AcceptBid {
+        invoice_id: u64,
+        bidder: Addr,
+    },
+}
+
+/// hook messages embedded in a cw721 `SendNft`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NftReceiveMsg {
+    List { price: Coin },
+}
+
+/// hook messages embedded in a cw20 `Send`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiveMsg {
+    Buy {
+        invoice_id: u64,
+    },
+    PlaceBid {
+        invoice_id: u64,
+        expires: Expiration,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    GetConfig {},
+    GetListing {
+        invoice_id: u64,
+    },
+    GetListings {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    GetBids {
+        invoice_id: u64,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
+#[serde(rename_all = "snake_case")]
+pub struct MigrateMsg {}
@@ -0,0 +1,90 @@
+use crate::market::query_invoice;
+use crate::state::*;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{Coin, Decimal, Deps, Env, Order, StdResult, Timestamp};
+use cw_storage_plus::Bound;
+use rwa_core::state::SECONDS_PER_DAY;
+
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 30;
+
+pub fn get_config(deps: Deps) -> StdResult<Config> {
+    let config = CONFIG.load(deps.storage)?;
+    Ok(config)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ListingResponse {
+    pub listing: Listing,
+    /// amount currently due on the invoice
+    pub due_amount: Coin,
+    pub due_date: Option<Timestamp>,
+    /// return of buying at the listing price and collecting the due amount
+    pub implied_yield: Option<Decimal>,
+    /// `implied_yield` scaled to a 365 day year, if the invoice is not yet due
+    pub annualized_yield: Option<Decimal>,
+}
+
+pub fn get_listing(deps: Deps, env: Env, invoice_id: u64) -> StdResult<ListingResponse> {
+    let listing = LISTINGS.load(deps.storage, invoice_id)?;
+    listing_response(deps, &env, listing)
+}
+
+pub fn get_listings(
+    deps: Deps,
+    env: Env,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<Vec<ListingResponse>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    LISTINGS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (_, listing) = item?;
+            listing_response(deps, &env, listing)
+        })
+        .collect()
+}
+
+pub fn get_bids(deps: Deps, invoice_id: u64) -> StdResult<Vec<Bid>> {
+    BIDS.prefix(invoice_id)
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, bid)| bid))
+        .collect()
+}
+
+fn listing_response(deps: Deps, env: &Env, listing: Listing) -> StdResult<ListingResponse> {
+    //// core reports the effective amount due at this block ////
+    let invoice = query_invoice(deps, listing.invoice_id)?;
+    let due_amount = invoice.due_amount;
+
+    let mut implied_yield = None;
+    let mut annualized_yield = None;
+    if due_amount.denom == listing.price.denom {
+        let gain = due_amount.amount.saturating_sub(listing.price.amount);
+        let rate = Decimal::from_ratio(gain, listing.price.amount);
+        implied_yield = Some(rate);
+
+        if let Some(due_date) = invoice.due_date {
+            let days =
+                due_date.seconds().saturating_sub(env.block.time.seconds()) / SECONDS_PER_DAY;
+            if days > 0 {
+                annualized_yield = Some(rate * Decimal::from_ratio(365u64, days));
+            }
+        }
+    }
+
+    Ok(ListingResponse {
+        listing,
+        due_amount,
+        due_date: invoice.due_date,
+        implied_yield,
+        annualized_yield,
+    })
+}
@@ -0,0 +1,42 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{Addr, Coin, Timestamp, Uint128};
+use cw20::Denom;
+use cw_storage_plus::{Item, Map};
+use cw_utils::Expiration;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Config {
+    /// core contract whose invoice NFTs are traded here
+    pub core_address: Addr,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Listing {
+    pub invoice_id: u64,
+    pub seller: Addr,
+    pub price: Coin,
+    pub listed_at: Timestamp,
+    /// invoice payment records made before the listing, later ones were paid to this contract
+    pub payments_before: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Bid {
+    pub invoice_id: u64,
+    pub bidder: Addr,
+    /// asset the bid is held in, refunds do not depend on it still being accepted by core
+    pub denom: Denom,
+    pub amount: Uint128,
+    pub expires: Expiration,
+}
+
+pub const CONFIG: Item<Config> = Item::new("config");
+
+pub const LISTINGS: Map<u64, Listing> = Map::new("listings");
+
+pub const BIDS: Map<(u64, &Addr), Bid> = Map::new("bids");
@@ -0,0 +1,164 @@
+#[cfg(not(feature = "library"))]
+use cosmwasm_std::entry_point;
+use cosmwasm_std::{
+    to_binary, Binary, Decimal, Deps, DepsMut, Env, MessageInfo, Reply, Response, StdError,
+    StdResult, SubMsg, WasmMsg,
+};
+use cw20::MinterResponse;
+use cw_utils::parse_reply_instantiate_data;
+use rwa_core::msg::QueryMsg as CoreQueryMsg;
+use rwa_core::state::Config as CoreConfig;
+
+use crate::error::ContractError;
+use crate::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, TokenInstantiateMsg};
+use crate::pool;
+use crate::query::*;
+use crate::state::*;
+use cw2::set_contract_version;
+// version info for migration info
+const CONTRACT_NAME: &str = env!("CARGO_PKG_NAME");
+const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+const INSTANTIATE_SHARE_TOKEN_REPLY_ID: u64 = 1;
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn instantiate(
+    mut deps: DepsMut,
+    env: Env,
+    _info: MessageInfo,
+    msg: InstantiateMsg,
+) -> Result<Response, ContractError> {
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    if msg.max_advance_rate > Decimal::one() {
+        return Err(StdError::generic_err("Advance rate cannot exceed 1").into());
+    }
+
+    //// the pool asset has to be accepted for invoices on core ////
+    let core_address = deps.api.addr_validate(&msg.core_address)?;
+    let core_config: CoreConfig = deps
+        .querier
+        .query_wasm_smart(&core_address, &CoreQueryMsg::GetConfig {})?;
+    let asset = core_config
+        .accepted_assets
+        .into_iter()
+        .find(|asset| asset.denom == msg.asset)
+        .ok_or_else(|| StdError::generic_err("Asset not accepted"))?;
+
+    let config = Config {
+        core_address,
+        asset: msg.asset,
+        share_token: None,
+        underwriter: deps.api.addr_validate(&msg.underwriter)?,
+        max_advance_rate: msg.max_advance_rate,
+        unbonding_period: msg.unbonding_period,
+    };
+    CONFIG.save(deps.storage, &config)?;
+    POOL.save(deps.storage, &PoolState::default())?;
+    let admin = deps.api.addr_validate(&msg.admin)?;
+    ADMIN.set(deps.branch(), Some(admin))?;
+
+    //// the pool is the only minter of its share token ////
+    let token_msg = TokenInstantiateMsg {
+        name: msg.share_token_name,
+        symbol: msg.share_token_symbol,
+        decimals: asset.decimal as u8,
+        initial_balances: vec![],
+        mint: Some(MinterResponse {
+            minter: env.contract.address.to_string(),
+            cap: None,
+        }),
+    };
+    let instantiate = WasmMsg::Instantiate {
+        admin: Some(msg.admin),
+        code_id: msg.share_token_code_id,
+        msg: to_binary(&token_msg)?,
+        funds: vec![],
+        label: "rwa pool shares".to_string(),
+    };
+
+    Ok(Response::new()
+        .add_submessage(SubMsg::reply_on_success(
+            instantiate,
+            INSTANTIATE_SHARE_TOKEN_REPLY_ID,
+        ))
+        .add_attribute("method", "instantiate")
+        .add_attribute("core_address", config.core_address))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractError> {
+    if msg.id != INSTANTIATE_SHARE_TOKEN_REPLY_ID {
+        return Err(StdError::generic_err("Unknown reply id").into());
+    }
+    let res =
+        parse_reply_instantiate_data(msg).map_err(|err| StdError::generic_err(err.to_string()))?;
+
+    let share_token = deps.api.addr_validate(&res.contract_address)?;
+    CONFIG.update(deps.storage, |mut config| -> StdResult<_> {
+        config.share_token = Some(share_token.clone());
+        Ok(config)
+    })?;
+
+    Ok(Response::new().add_attribute("share_token", share_token))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn execute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    match msg {
+        ExecuteMsg::Deposit {} => pool::deposit(deps, env, info),
+        ExecuteMsg::Receive(msg) => pool::receive_cw20(deps, env, info, msg),
+        ExecuteMsg::ReceiveNft(msg) => pool::receive_nft(deps, env, info, msg),
+        ExecuteMsg::FundAdvance {
+            invoice_id,
+            advance_rate,
+            fee_rate,
+        } => pool::fund_advance(deps, env, info, invoice_id, advance_rate, fee_rate),
+        ExecuteMsg::RejectAdvance { invoice_id } => {
+            pool::reject_advance(deps, env, info, invoice_id)
+        }
+        ExecuteMsg::Collect { invoice_id } => pool::collect(deps, env, info, invoice_id),
+        ExecuteMsg::Claim {} => pool::claim(deps, env, info),
+        ExecuteMsg::UpdateConfig {
+            underwriter,
+            max_advance_rate,
+        } => pool::update_config(deps, env, info, underwriter, max_advance_rate),
+        ExecuteMsg::UpdateAdmin { admin } => {
+            let admin = admin
+                .map(|admin| deps.api.addr_validate(&admin))
+                .transpose()?;
+            Ok(ADMIN.execute_update_admin(deps, info, admin)?)
+        }
+    }
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::GetConfig {} => to_binary(&get_config(deps)?),
+        QueryMsg::GetAdmin {} => to_binary(&get_admin(deps)?),
+        QueryMsg::GetPool {} => to_binary(&get_pool(deps)?),
+        QueryMsg::GetAdvance { invoice_id } => to_binary(&get_advance(deps, invoice_id)?),
+        QueryMsg::GetAdvances { start_after, limit } => {
+            to_binary(&get_advances(deps, start_after, limit)?)
+        }
+        QueryMsg::GetClaims { address } => to_binary(&get_claims(deps, address)?),
+    }
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    let ver = cw2::get_contract_version(deps.storage)?;
+    // ensure we are migrating from an allowed contract
+    if ver.contract != CONTRACT_NAME {
+        return Err(StdError::generic_err("Can only upgrade from same type").into());
+    }
+    // set the new version
+    cw2::set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    Ok(Response::default())
+}
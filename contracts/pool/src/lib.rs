@@ -0,0 +1,18 @@
+/*!
+Liquidity pool that funds RWA invoices.
+
+Lenders deposit the pool asset and receive cw20 share tokens. An underwriter
+advances a percentage of an accepted invoice's receivable to its issuer,
+holding the invoice NFT as collateral, so the payee's `PayInvoice` on core
+settles to the pool and raises the share price once collected. Lenders
+withdraw by sending their shares back and claiming the asset after the
+unbonding period.
+*/
+
+pub mod contract;
+pub mod error;
+pub mod msg;
+pub mod pool;
+pub mod query;
+pub mod state;
+pub use crate::error::ContractError;
@@ -0,0 +1,105 @@
+use cosmwasm_std::{Addr, Decimal};
+use cw20::{Cw20Coin, Cw20ReceiveMsg, Denom, MinterResponse};
+use cw721::Cw721ReceiveMsg;
+use cw_utils::Duration;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct InstantiateMsg {
+    pub admin: String,
+    pub core_address: String,
+    pub asset: Denom,
+    pub underwriter: String,
+    pub max_advance_rate: Decimal,
+    pub unbonding_period: Duration,
+    /// code id of a cw20-base contract used for the share token
+    pub share_token_code_id: u64,
+    pub share_token_name: String,
+    pub share_token_symbol: String,
+}
+
+/// instantiate message of the cw20-base share token
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct TokenInstantiateMsg {
+    pub name: String,
+    pub symbol: String,
+    pub decimals: u8,
+    pub initial_balances: Vec<Cw20Coin>,
+    pub mint: Option<MinterResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    /// deposits native funds in exchange for shares
+    Deposit {},
+    /// deposits cw20 assets or withdraws shares sent with cw20 `Send`
+    Receive(Cw20ReceiveMsg),
+    /// requests an advance on an invoice NFT sent with cw721 `SendNft`
+    ReceiveNft(Cw721ReceiveMsg),
+    FundAdvance {
+        invoice_id: u64,
+        advance_rate: Decimal,
+        /// fee owed to the pool as a share of the advance
+        fee_rate: Decimal,
+    },
+    /// returns the NFT of an advance request that was not funded
+    RejectAdvance {
+        invoice_id: u64,
+    },
+    /// books invoice payments received by the pool
+    Collect {
+        invoice_id: u64,
+    },
+    /// pays out withdrawals whose unbonding period has passed
+    Claim {},
+    UpdateConfig {
+        underwriter: Option<Addr>,
+        max_advance_rate: Option<Decimal>,
+    },
+    UpdateAdmin {
+        admin: Option<String>,
+    },
+}
+
+/// hook messages embedded in a cw20 `Send`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiveMsg {
+    /// sent with the pool asset
+    Deposit {},
+    /// sent with share tokens, starts unbonding
+    Withdraw {},
+}
+
+/// hook messages embedded in a cw721 `SendNft`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NftReceiveMsg {
+    RequestAdvance {},
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    GetConfig {},
+    GetAdmin {},
+    GetPool {},
+    GetAdvance {
+        invoice_id: u64,
+    },
+    GetAdvances {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    GetClaims {
+        address: Addr,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
+#[serde(rename_all = "snake_case")]
+pub struct MigrateMsg {}
@@ -0,0 +1,843 @@
+use crate::msg::{NftReceiveMsg, ReceiveMsg};
+use crate::state::*;
+use cosmwasm_std::{
+    from_binary, to_binary, Addr, CosmosMsg, Decimal, Deps, DepsMut, Env, MessageInfo, Response,
+    StdError, StdResult, Uint128, WasmMsg,
+};
+use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg, Denom};
+use cw721::{Cw721ExecuteMsg, Cw721ReceiveMsg};
+use rwa_core::helpers::{denom_key, transfer_msg};
+use rwa_core::msg::QueryMsg as CoreQueryMsg;
+use rwa_core::state::{
+    Config as CoreConfig, EscrowBalance, Invoice, PaymentRecord, SettlementMode, Status,
+};
+
+use crate::error::ContractError;
+
+pub fn deposit(deps: DepsMut, _env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let amount = match (info.funds.as_slice(), &config.asset) {
+        ([fund], Denom::Native(denom)) if &fund.denom == denom => fund.amount,
+        _ => return Err(StdError::generic_err("Deposit only the pool asset").into()),
+    };
+    execute_deposit(deps, info.sender, amount)
+}
+
+pub fn receive_cw20(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    wrapper: Cw20ReceiveMsg,
+) -> Result<Response, ContractError> {
+    //// the cw20 contract is the sender, the lender is wrapped inside ////
+    let config = CONFIG.load(deps.storage)?;
+    let sender = deps.api.addr_validate(&wrapper.sender)?;
+    match from_binary(&wrapper.msg)? {
+        ReceiveMsg::Deposit {} => {
+            if config.asset != Denom::Cw20(info.sender) {
+                return Err(StdError::generic_err("Deposit only the pool asset").into());
+            }
+            execute_deposit(deps, sender, wrapper.amount)
+        }
+        ReceiveMsg::Withdraw {} => {
+            if config.share_token != Some(info.sender) {
+                return Err(ContractError::Unauthorized {});
+            }
+            execute_withdraw(deps, env, sender, wrapper.amount)
+        }
+    }
+}
+
+fn execute_deposit(
+    deps: DepsMut,
+    lender: Addr,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    if amount.is_zero() {
+        return Err(StdError::generic_err("Amount cannot be zero").into());
+    }
+    let config = CONFIG.load(deps.storage)?;
+    let share_token = config
+        .share_token
+        .ok_or_else(|| StdError::generic_err("Share token not instantiated"))?;
+
+    //// shares are minted at the current share price ////
+    let mut pool = POOL.load(deps.storage)?;
+    let shares = if pool.total_shares.is_zero() {
+        amount
+    } else if pool.total_value().is_zero() {
+        return Err(StdError::generic_err("Pool has no value backing its shares").into());
+    } else {
+        amount.multiply_ratio(pool.total_shares, pool.total_value())
+    };
+    if shares.is_zero() {
+        return Err(StdError::generic_err("Deposit too small").into());
+    }
+    pool.total_shares += shares;
+    pool.liquidity += amount;
+    POOL.save(deps.storage, &pool)?;
+
+    let mint = WasmMsg::Execute {
+        contract_addr: share_token.to_string(),
+        msg: to_binary(&Cw20ExecuteMsg::Mint {
+            recipient: lender.to_string(),
+            amount: shares,
+        })?,
+        funds: vec![],
+    };
+
+    Ok(Response::new()
+        .add_message(mint)
+        .add_attribute("method", "deposit")
+        .add_attribute("lender", lender)
+        .add_attribute("amount", amount)
+        .add_attribute("shares", shares))
+}
+
+fn execute_withdraw(
+    deps: DepsMut,
+    env: Env,
+    lender: Addr,
+    shares: Uint128,
+) -> Result<Response, ContractError> {
+    if shares.is_zero() {
+        return Err(StdError::generic_err("Amount cannot be zero").into());
+    }
+    let config = CONFIG.load(deps.storage)?;
+    let share_token = config
+        .share_token
+        .ok_or_else(|| StdError::generic_err("Share token not instantiated"))?;
+
+    //// only assets that are not advanced can be withdrawn ////
+    let mut pool = POOL.load(deps.storage)?;
+    let amount = shares.multiply_ratio(pool.total_value(), pool.total_shares);
+    if amount > pool.liquidity {
+        return Err(StdError::generic_err("Insufficient liquidity").into());
+    }
+    pool.total_shares -= shares;
+    pool.liquidity -= amount;
+    POOL.save(deps.storage, &pool)?;
+
+    let release_at = config.unbonding_period.after(&env.block);
+    CLAIMS.create_claim(deps.storage, &lender, amount, release_at)?;
+
+    //// the shares were sent to the pool, burn them ////
+    let burn = WasmMsg::Execute {
+        contract_addr: share_token.to_string(),
+        msg: to_binary(&Cw20ExecuteMsg::Burn { amount: shares })?,
+        funds: vec![],
+    };
+
+    Ok(Response::new()
+        .add_message(burn)
+        .add_attribute("method", "withdraw")
+        .add_attribute("lender", lender)
+        .add_attribute("shares", shares)
+        .add_attribute("amount", amount)
+        .add_attribute("release_at", release_at.to_string()))
+}
+
+pub fn claim(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+    let amount = CLAIMS.claim_tokens(deps.storage, &info.sender, &env.block, None)?;
+    if amount.is_zero() {
+        return Err(StdError::generic_err("Nothing to claim").into());
+    }
+
+    let config = CONFIG.load(deps.storage)?;
+    let msg = transfer_msg(&config.asset, &info.sender, amount)?;
+
+    Ok(Response::new()
+        .add_message(msg)
+        .add_attribute("method", "claim")
+        .add_attribute("lender", info.sender)
+        .add_attribute("amount", amount))
+}
+
+pub fn receive_nft(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    wrapper: Cw721ReceiveMsg,
+) -> Result<Response, ContractError> {
+    //// only invoice nfts minted by core are taken as collateral ////
+    let core_config = query_core_config(deps.as_ref())?;
+    if info.sender != core_config.nft_address {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let issuer = deps.api.addr_validate(&wrapper.sender)?;
+    let invoice_id: u64 = wrapper
+        .token_id
+        .parse()
+        .map_err(|_| StdError::generic_err("Invalid invoice id"))?;
+    match from_binary(&wrapper.msg)? {
+        NftReceiveMsg::RequestAdvance {} => {
+            let invoice = query_invoice(deps.as_ref(), invoice_id)?;
+            if invoice.from != issuer {
+                return Err(StdError::generic_err("Only the issuer can request an advance").into());
+            }
+            if invoice.status == Status::Raised || invoice.status.is_closed() {
+                return Err(StdError::generic_err("Invoice is not open for advances").into());
+            }
+            if ADVANCES.has(deps.storage, invoice_id) {
+                return Err(StdError::generic_err("Advance already requested").into());
+            }
+            //// payments made before the request were not paid to the pool ////
+            let payments_before = query_payments(deps.as_ref(), invoice_id)?.len() as u64;
+            let advance = Advance {
+                invoice_id,
+                issuer: issuer.clone(),
+                advanced: Uint128::zero(),
+                fee: Uint128::zero(),
+                collected: Uint128::zero(),
+                payments_before,
+                funded_at: None,
+            };
+            ADVANCES.save(deps.storage, invoice_id, &advance)?;
+
+            Ok(Response::new()
+                .add_attribute("method", "request_advance")
+                .add_attribute("invoice_id", invoice_id.to_string())
+                .add_attribute("issuer", issuer))
+        }
+    }
+}
+
+pub fn fund_advance(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    invoice_id: u64,
+    advance_rate: Decimal,
+    fee_rate: Decimal,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if config.underwriter != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    if advance_rate.is_zero() || advance_rate > config.max_advance_rate {
+        return Err(StdError::generic_err("Invalid advance rate").into());
+    }
+
+    let mut advance = ADVANCES.load(deps.storage, invoice_id)?;
+    if advance.funded_at.is_some() {
+        return Err(StdError::generic_err("Advance already funded").into());
+    }
+
+    //// only accepted invoices without payments are funded ////
+    let invoice = query_invoice(deps.as_ref(), invoice_id)?;
+    if invoice.status != Status::Accepted {
+        return Err(StdError::generic_err("Invoice is not accepted").into());
+    }
+    invoice.ensure_not_disputed()?;
+    if invoice.receivable.denom != denom_key(&config.asset) {
+        return Err(StdError::generic_err("Invoice is not in the pool asset").into());
+    }
+
+    //// escrow releases are not recorded as payments, so delivery has to be confirmed ////
+    if invoice.settlement != SettlementMode::Direct {
+        let escrow: Option<EscrowBalance> = deps.querier.query_wasm_smart(
+            &config.core_address,
+            &CoreQueryMsg::GetEscrow { invoice_id },
+        )?;
+        if !escrow.map(|e| e.delivery_confirmed).unwrap_or_default() {
+            return Err(StdError::generic_err("Delivery not yet confirmed").into());
+        }
+    }
+
+    let amount = invoice.receivable.amount * advance_rate;
+    let mut pool = POOL.load(deps.storage)?;
+    if amount > pool.liquidity {
+        return Err(StdError::generic_err("Insufficient liquidity").into());
+    }
+    pool.liquidity -= amount;
+    pool.outstanding += amount;
+    POOL.save(deps.storage, &pool)?;
+
+    advance.advanced = amount;
+    advance.fee = amount * fee_rate;
+    advance.funded_at = Some(env.block.time);
+    ADVANCES.save(deps.storage, invoice_id, &advance)?;
+
+    let msg = transfer_msg(&config.asset, &advance.issuer, amount)?;
+
+    Ok(Response::new()
+        .add_message(msg)
+        .add_attribute("method", "fund_advance")
+        .add_attribute("invoice_id", invoice_id.to_string())
+        .add_attribute("issuer", advance.issuer)
+        .add_attribute("amount", amount)
+        .add_attribute("fee", advance.fee))
+}
+
+pub fn reject_advance(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    invoice_id: u64,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let advance = ADVANCES.load(deps.storage, invoice_id)?;
+    if config.underwriter != info.sender && advance.issuer != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    if advance.funded_at.is_some() {
+        return Err(StdError::generic_err("Advance already funded").into());
+    }
+    ADVANCES.remove(deps.storage, invoice_id);
+
+    //// the nft goes back with any payments received while the pool held it ////
+    let mut messages = vec![return_nft_msg(deps.as_ref(), &advance)?];
+    let received = received_payments(deps.as_ref(), &env, &advance)?;
+    if !received.is_zero() {
+        messages.push(transfer_msg(&config.asset, &advance.issuer, received)?);
+    }
+
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_attribute("method", "reject_advance")
+        .add_attribute("invoice_id", invoice_id.to_string()))
+}
+
+pub fn collect(
+    deps: DepsMut,
+    env: Env,
+    _info: MessageInfo,
+    invoice_id: u64,
+) -> Result<Response, ContractError> {
+    let mut advance = ADVANCES.load(deps.storage, invoice_id)?;
+    if advance.funded_at.is_none() {
+        return Err(StdError::generic_err("Advance not funded").into());
+    }
+
+    //// anyone can book the payments core sent to the pool ////
+    let received = received_payments(deps.as_ref(), &env, &advance)?;
+    let collected = received.saturating_sub(advance.collected);
+    let outstanding = advance.outstanding();
+
+    //// the pool keeps the advance and its fee, the rest of the receivable is the issuer's ////
+    let repayment = advance.repayment();
+    let kept = received
+        .min(repayment)
+        .saturating_sub(advance.collected.min(repayment));
+    let forwarded = collected - kept;
+    advance.collected = received;
+
+    let mut pool = POOL.load(deps.storage)?;
+    pool.liquidity += kept;
+    pool.outstanding -= outstanding - advance.outstanding();
+
+    let mut response = Response::new();
+    if !forwarded.is_zero() {
+        let config = CONFIG.load(deps.storage)?;
+        response = response
+            .add_message(transfer_msg(&config.asset, &advance.issuer, forwarded)?)
+            .add_attribute("forwarded", forwarded);
+    }
+
+    //// a closed invoice will not pay any more, write off what is left ////
+    let invoice = query_invoice(deps.as_ref(), invoice_id)?;
+    if invoice.status.is_closed() {
+        pool.outstanding -= advance.outstanding();
+        ADVANCES.remove(deps.storage, invoice_id);
+        response = response
+            .add_message(return_nft_msg(deps.as_ref(), &advance)?)
+            .add_attribute("written_off", advance.outstanding());
+    } else {
+        ADVANCES.save(deps.storage, invoice_id, &advance)?;
+    }
+    POOL.save(deps.storage, &pool)?;
+
+    Ok(response
+        .add_attribute("method", "collect")
+        .add_attribute("invoice_id", invoice_id.to_string())
+        .add_attribute("collected", collected))
+}
+
+pub fn update_config(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    underwriter: Option<Addr>,
+    max_advance_rate: Option<Decimal>,
+) -> Result<Response, ContractError> {
+    ADMIN.assert_admin(deps.as_ref(), &info.sender)?;
+
+    let mut config = CONFIG.load(deps.storage)?;
+    if let Some(underwriter) = underwriter {
+        config.underwriter = deps.api.addr_validate(underwriter.as_str())?;
+    }
+    if let Some(max_advance_rate) = max_advance_rate {
+        if max_advance_rate > Decimal::one() {
+            return Err(StdError::generic_err("Advance rate cannot exceed 1").into());
+        }
+        config.max_advance_rate = max_advance_rate;
+    }
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "update_config")
+        .add_attribute("sender", info.sender))
+}
+
+fn return_nft_msg(deps: Deps, advance: &Advance) -> StdResult<CosmosMsg> {
+    let core_config = query_core_config(deps)?;
+    Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: core_config.nft_address.to_string(),
+        msg: to_binary(&Cw721ExecuteMsg::TransferNft {
+            recipient: advance.issuer.to_string(),
+            token_id: advance.invoice_id.to_string(),
+        })?,
+        funds: vec![],
+    }))
+}
+
+/// sums the invoice payments core forwarded to the pool as the nft holder since the advance
+/// was requested, net of protocol fees
+fn received_payments(deps: Deps, env: &Env, advance: &Advance) -> StdResult<Uint128> {
+    let payments = query_payments(deps, advance.invoice_id)?;
+    Ok(payments
+        .iter()
+        .skip(advance.payments_before as usize)
+        .filter(|p| p.recipient.as_ref() == Some(&env.contract.address))
+        .fold(Uint128::zero(), |total, p| total + p.net()))
+}
+
+fn query_payments(deps: Deps, invoice_id: u64) -> StdResult<Vec<PaymentRecord>> {
+    let config = CONFIG.load(deps.storage)?;
+    deps.querier.query_wasm_smart(
+        config.core_address,
+        &CoreQueryMsg::GetInvoicePayments { invoice_id },
+    )
+}
+
+pub fn query_core_config(deps: Deps) -> StdResult<CoreConfig> {
+    let config = CONFIG.load(deps.storage)?;
+    deps.querier
+        .query_wasm_smart(config.core_address, &CoreQueryMsg::GetConfig {})
+}
+
+pub fn query_invoice(deps: Deps, invoice_id: u64) -> StdResult<Invoice> {
+    let config = CONFIG.load(deps.storage)?;
+    deps.querier.query_wasm_smart(
+        config.core_address,
+        &CoreQueryMsg::GetInvoice { invoice_id },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::instantiate;
+    use crate::msg::InstantiateMsg;
+    use crate::query::get_pool;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{coin, BankMsg, ContractResult, SubMsg, SystemResult, Timestamp, WasmQuery};
+    use cw_utils::Duration;
+    use rwa_core::state::{Asset, ServiceType, SECONDS_PER_DAY};
+    use rwa_core::testing::{unsupported_query, MockDeps};
+
+    const ADMIN_ADDR: &str = "admin";
+    const UNDERWRITER: &str = "underwriter";
+    const ISSUER: &str = "issuer";
+    const LENDER: &str = "lender";
+    const INVOICE_ID: u64 = 1000001;
+
+    /// makes the mocked core report the invoice in `status` with `paid` sent to the pool
+    fn mock_core(deps: &mut MockDeps, status: Status, paid: Vec<u128>) {
+        mock_core_with_fee(deps, status, paid, 0)
+    }
+
+    /// as `mock_core`, with core keeping `payment_fee_bps` of each payment
+    fn mock_core_with_fee(
+        deps: &mut MockDeps,
+        status: Status,
+        paid: Vec<u128>,
+        payment_fee_bps: u16,
+    ) {
+        let env = mock_env();
+        let invoice = Invoice {
+            id: INVOICE_ID,
+            from: Addr::unchecked(ISSUER),
+            payee_address: Addr::unchecked("payee"),
+            nft_id: INVOICE_ID,
+            doc_uri: "ipfs://invoice".to_string(),
+            due_amount: coin(1000, "uatom"),
+            receivable: coin(1000, "uatom"),
+            amount_paid: coin(0, "uatom"),
+            service_type: ServiceType::Goods,
+            status,
+            remarks: None,
+            due_date: Some(env.block.time.plus_seconds(30 * SECONDS_PER_DAY)),
+            issued_at: Some(env.block.time),
+            accepted_at: Some(env.block.time),
+            paid_at: None,
+            payment_terms: None,
+            discount_applied: Uint128::zero(),
+            penalty_paid: Uint128::zero(),
+            settlement: SettlementMode::Direct,
+            installments: vec![],
+            line_items: vec![],
+            credited: Uint128::zero(),
+            dispute_id: None,
+            created_by: None,
+            accepted_by: None,
+        };
+        let payments: Vec<PaymentRecord> = paid
+            .into_iter()
+            .map(|amount| PaymentRecord {
+                payer: Addr::unchecked("payee"),
+                amount: coin(amount, "uatom"),
+                principal: Uint128::new(amount),
+                discount: Uint128::zero(),
+                penalty: Uint128::zero(),
+                paid_at: Timestamp::from_seconds(0),
+                recipient: Some(env.contract.address.clone()),
+                member: None,
+                protocol_fee: Uint128::new(amount).multiply_ratio(payment_fee_bps, 10_000u128),
+            })
+            .collect();
+        deps.querier.update_wasm(move |query| match query {
+            WasmQuery::Smart { msg, .. } => {
+                let res = match from_binary(msg) {
+                    Ok(CoreQueryMsg::GetConfig {}) => to_binary(&CoreConfig {
+                        nft_address: Addr::unchecked("nft"),
+                        owner: Addr::unchecked(ADMIN_ADDR),
+                        accepted_assets: vec![Asset {
+                            name: "atom".to_string(),
+                            denom: Denom::Native("uatom".to_string()),
+                            decimal: 6,
+                            uri: None,
+                        }],
+                    }),
+                    Ok(CoreQueryMsg::GetInvoice { .. }) => to_binary(&invoice),
+                    Ok(CoreQueryMsg::GetInvoicePayments { .. }) => to_binary(&payments),
+                    _ => return unsupported_query(query),
+                };
+                SystemResult::Ok(ContractResult::Ok(res.unwrap()))
+            }
+            _ => unsupported_query(query),
+        });
+    }
+
+    /// instantiates the pool, registers its share token and deposits 1000uatom
+    fn setup() -> MockDeps {
+        let mut deps = mock_dependencies();
+        mock_core(&mut deps, Status::Accepted, vec![]);
+        let msg = InstantiateMsg {
+            admin: ADMIN_ADDR.to_string(),
+            core_address: "core".to_string(),
+            asset: Denom::Native("uatom".to_string()),
+            underwriter: UNDERWRITER.to_string(),
+            max_advance_rate: Decimal::percent(90),
+            unbonding_period: Duration::Time(7 * SECONDS_PER_DAY),
+            share_token_code_id: 1,
+            share_token_name: "pool shares".to_string(),
+            share_token_symbol: "RWAP".to_string(),
+        };
+        instantiate(deps.as_mut(), mock_env(), mock_info(ADMIN_ADDR, &[]), msg).unwrap();
+        CONFIG
+            .update(&mut deps.storage, |mut config| -> StdResult<_> {
+                config.share_token = Some(Addr::unchecked("shares"));
+                Ok(config)
+            })
+            .unwrap();
+
+        deposit(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(LENDER, &[coin(1000, "uatom")]),
+        )
+        .unwrap();
+        deps
+    }
+
+    fn request_advance(deps: &mut MockDeps) -> Result<Response, ContractError> {
+        let msg = Cw721ReceiveMsg {
+            sender: ISSUER.to_string(),
+            token_id: INVOICE_ID.to_string(),
+            msg: to_binary(&NftReceiveMsg::RequestAdvance {}).unwrap(),
+        };
+        receive_nft(deps.as_mut(), mock_env(), mock_info("nft", &[]), msg)
+    }
+
+    fn request_and_fund(deps: &mut MockDeps, advance_rate: Decimal, fee_rate: Decimal) {
+        request_advance(deps).unwrap();
+        fund_advance(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(UNDERWRITER, &[]),
+            INVOICE_ID,
+            advance_rate,
+            fee_rate,
+        )
+        .unwrap();
+    }
+
+    fn withdraw_msg(shares: u128) -> Cw20ReceiveMsg {
+        Cw20ReceiveMsg {
+            sender: LENDER.to_string(),
+            amount: Uint128::new(shares),
+            msg: to_binary(&ReceiveMsg::Withdraw {}).unwrap(),
+        }
+    }
+
+    #[test]
+    fn deposits_mint_shares_at_share_price() {
+        let mut deps = setup();
+        request_and_fund(&mut deps, Decimal::percent(80), Decimal::percent(25));
+
+        //// the invoice pays in full, lifting the pool value to 1200 ////
+        mock_core(&mut deps, Status::Paid, vec![1000]);
+        let res = collect(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            INVOICE_ID,
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), 1);
+        let pool = get_pool(deps.as_ref()).unwrap();
+        assert_eq!(pool.total_value, Uint128::new(1200));
+        assert_eq!(pool.outstanding, Uint128::zero());
+        assert_eq!(pool.share_price, Decimal::percent(120));
+
+        let res = deposit(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("other", &[coin(600, "uatom")]),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(WasmMsg::Execute {
+                contract_addr: "shares".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Mint {
+                    recipient: "other".to_string(),
+                    amount: Uint128::new(500),
+                })
+                .unwrap(),
+                funds: vec![],
+            })]
+        );
+    }
+
+    #[test]
+    fn underwriter_advances_against_accepted_invoices() {
+        let mut deps = setup();
+        request_advance(&mut deps).unwrap();
+
+        let err = fund_advance(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            INVOICE_ID,
+            Decimal::percent(80),
+            Decimal::percent(10),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        let err = fund_advance(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(UNDERWRITER, &[]),
+            INVOICE_ID,
+            Decimal::percent(95),
+            Decimal::percent(10),
+        )
+        .unwrap_err();
+        assert_eq!(err, StdError::generic_err("Invalid advance rate").into());
+
+        let res = fund_advance(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(UNDERWRITER, &[]),
+            INVOICE_ID,
+            Decimal::percent(80),
+            Decimal::percent(10),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: ISSUER.to_string(),
+                amount: vec![coin(800, "uatom")],
+            })]
+        );
+
+        //// a partial repayment is booked without closing the advance ////
+        mock_core(&mut deps, Status::PartiallyPaid, vec![300]);
+        collect(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            INVOICE_ID,
+        )
+        .unwrap();
+        let pool = get_pool(deps.as_ref()).unwrap();
+        assert_eq!(pool.liquidity, Uint128::new(500));
+        assert_eq!(pool.outstanding, Uint128::new(500));
+        assert_eq!(
+            ADVANCES.load(&deps.storage, INVOICE_ID).unwrap().collected,
+            Uint128::new(300)
+        );
+    }
+
+    #[test]
+    fn payments_beyond_the_advance_and_fee_go_to_the_issuer() {
+        let mut deps = setup();
+        request_and_fund(&mut deps, Decimal::percent(50), Decimal::percent(10));
+
+        mock_core(&mut deps, Status::PartiallyPaid, vec![300]);
+        let res = collect(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            INVOICE_ID,
+        )
+        .unwrap();
+        assert!(res.messages.is_empty());
+
+        //// of the remaining 700 the pool keeps 250, completing the 500 advance and 50 fee ////
+        mock_core(&mut deps, Status::Paid, vec![300, 700]);
+        let res = collect(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            INVOICE_ID,
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0],
+            SubMsg::new(BankMsg::Send {
+                to_address: ISSUER.to_string(),
+                amount: vec![coin(450, "uatom")],
+            })
+        );
+        let pool = get_pool(deps.as_ref()).unwrap();
+        assert_eq!(pool.liquidity, Uint128::new(1050));
+        assert_eq!(pool.outstanding, Uint128::zero());
+    }
+
+    #[test]
+    fn protocol_fees_are_not_counted_as_collected() {
+        let mut deps = setup();
+        request_and_fund(&mut deps, Decimal::percent(50), Decimal::percent(10));
+
+        //// core keeps 1%, so 990 of the 1000 paid reach the pool ////
+        mock_core_with_fee(&mut deps, Status::Paid, vec![1000], 100);
+        let res = collect(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            INVOICE_ID,
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0],
+            SubMsg::new(BankMsg::Send {
+                to_address: ISSUER.to_string(),
+                amount: vec![coin(440, "uatom")],
+            })
+        );
+        let pool = get_pool(deps.as_ref()).unwrap();
+        assert_eq!(pool.liquidity, Uint128::new(1050));
+    }
+
+    #[test]
+    fn rejected_requests_return_each_payment_once() {
+        let mut deps = setup();
+        request_advance(&mut deps).unwrap();
+        let err = request_advance(&mut deps).unwrap_err();
+        assert_eq!(
+            err,
+            StdError::generic_err("Advance already requested").into()
+        );
+
+        //// the payee pays the pool while the request is pending ////
+        mock_core(&mut deps, Status::PartiallyPaid, vec![300]);
+        let res = reject_advance(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            INVOICE_ID,
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[1],
+            SubMsg::new(BankMsg::Send {
+                to_address: ISSUER.to_string(),
+                amount: vec![coin(300, "uatom")],
+            })
+        );
+
+        //// requesting again does not pay the same 300 out twice ////
+        request_advance(&mut deps).unwrap();
+        let res = reject_advance(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ISSUER, &[]),
+            INVOICE_ID,
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), 1);
+
+        //// settled invoices are not taken as collateral ////
+        mock_core(&mut deps, Status::Paid, vec![300, 700]);
+        let err = request_advance(&mut deps).unwrap_err();
+        assert_eq!(
+            err,
+            StdError::generic_err("Invoice is not open for advances").into()
+        );
+    }
+
+    #[test]
+    fn withdrawals_unbond_before_they_can_be_claimed() {
+        let mut deps = setup();
+        request_and_fund(&mut deps, Decimal::percent(50), Decimal::zero());
+
+        //// advanced funds cannot be withdrawn ////
+        let err = receive_cw20(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("shares", &[]),
+            withdraw_msg(600),
+        )
+        .unwrap_err();
+        assert_eq!(err, StdError::generic_err("Insufficient liquidity").into());
+
+        let err = receive_cw20(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("fake_shares", &[]),
+            withdraw_msg(400),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        receive_cw20(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("shares", &[]),
+            withdraw_msg(400),
+        )
+        .unwrap();
+        let err = claim(deps.as_mut(), mock_env(), mock_info(LENDER, &[])).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Nothing to claim").into());
+
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(7 * SECONDS_PER_DAY);
+        let res = claim(deps.as_mut(), env, mock_info(LENDER, &[])).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: LENDER.to_string(),
+                amount: vec![coin(400, "uatom")],
+            })]
+        );
+        let pool = get_pool(deps.as_ref()).unwrap();
+        assert_eq!(pool.total_shares, Uint128::new(600));
+        assert_eq!(pool.liquidity, Uint128::new(100));
+    }
+}
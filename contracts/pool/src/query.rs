@@ -0,0 +1,70 @@
+use crate::state::*;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use controllers::{AdminResponse, ClaimsResponse};
+use cosmwasm_std::{Addr, Decimal, Deps, Order, StdResult, Uint128};
+use cw_storage_plus::Bound;
+
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 30;
+
+pub fn get_config(deps: Deps) -> StdResult<Config> {
+    let config = CONFIG.load(deps.storage)?;
+    Ok(config)
+}
+
+pub fn get_admin(deps: Deps) -> StdResult<AdminResponse> {
+    ADMIN.query_admin(deps)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PoolResponse {
+    pub total_shares: Uint128,
+    pub liquidity: Uint128,
+    pub outstanding: Uint128,
+    pub total_value: Uint128,
+    /// pool asset backing each share
+    pub share_price: Decimal,
+}
+
+pub fn get_pool(deps: Deps) -> StdResult<PoolResponse> {
+    let pool = POOL.load(deps.storage)?;
+    let share_price = if pool.total_shares.is_zero() {
+        Decimal::one()
+    } else {
+        Decimal::from_ratio(pool.total_value(), pool.total_shares)
+    };
+    Ok(PoolResponse {
+        total_shares: pool.total_shares,
+        liquidity: pool.liquidity,
+        outstanding: pool.outstanding,
+        total_value: pool.total_value(),
+        share_price,
+    })
+}
+
+pub fn get_advance(deps: Deps, invoice_id: u64) -> StdResult<Advance> {
+    let advance = ADVANCES.load(deps.storage, invoice_id)?;
+    Ok(advance)
+}
+
+pub fn get_advances(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<Vec<Advance>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    ADVANCES
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, advance)| advance))
+        .collect()
+}
+
+pub fn get_claims(deps: Deps, address: Addr) -> StdResult<ClaimsResponse> {
+    CLAIMS.query_claims(deps, &address)
+}
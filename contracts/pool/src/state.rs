@@ -0,0 +1,80 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use controllers::{Admin, Claims};
+use cosmwasm_std::{Addr, Decimal, Timestamp, Uint128};
+use cw20::Denom;
+use cw_storage_plus::{Item, Map};
+use cw_utils::Duration;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Config {
+    /// core contract whose invoices are funded by the pool
+    pub core_address: Addr,
+    /// asset lenders deposit and invoices are advanced in
+    pub asset: Denom,
+    /// cw20 share token minted to lenders, set once it is instantiated
+    pub share_token: Option<Addr>,
+    pub underwriter: Addr,
+    /// highest share of the receivable that can be advanced
+    pub max_advance_rate: Decimal,
+    pub unbonding_period: Duration,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub struct PoolState {
+    pub total_shares: Uint128,
+    /// assets held by the pool that are free to advance or withdraw
+    pub liquidity: Uint128,
+    /// advanced principal that has not been collected yet
+    pub outstanding: Uint128,
+}
+
+impl PoolState {
+    /// value backing the shares, outstanding advances are valued at principal
+    pub fn total_value(&self) -> Uint128 {
+        self.liquidity + self.outstanding
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Advance {
+    pub invoice_id: u64,
+    pub issuer: Addr,
+    /// zero until the underwriter funds the request
+    pub advanced: Uint128,
+    /// agreed with the underwriter on top of the advance, payments beyond both go to the issuer
+    #[serde(default)]
+    pub fee: Uint128,
+    /// invoice payments received by the pool so far
+    pub collected: Uint128,
+    /// invoice payment records made before the request, the pool only books later ones
+    #[serde(default)]
+    pub payments_before: u64,
+    pub funded_at: Option<Timestamp>,
+}
+
+impl Advance {
+    /// principal of the advance that has not been repaid yet
+    pub fn outstanding(&self) -> Uint128 {
+        self.advanced.saturating_sub(self.collected)
+    }
+
+    /// what the pool keeps from the invoice payments
+    pub fn repayment(&self) -> Uint128 {
+        self.advanced + self.fee
+    }
+}
+
+pub const ADMIN: Admin = Admin::new("admin");
+
+pub const CONFIG: Item<Config> = Item::new("config");
+
+pub const POOL: Item<PoolState> = Item::new("pool");
+
+pub const ADVANCES: Map<u64, Advance> = Map::new("advances");
+
+pub const CLAIMS: Claims = Claims::new("claims");